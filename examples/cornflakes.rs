@@ -1,19 +1,15 @@
 use ffiber::CDylibCompiler;
 use ffiber::types::{Type, SelfType};
 use color_eyre::eyre::Result;
+use std::path::Path;
 
 fn main() -> Result<()> {
     let mut compiler = CDylibCompiler::new_with_output_folder(
         "mlx5-datapath", ".");
-    compiler.add_dependency("bumpalo", vec![
-        ("git", "\"https://github.com/deeptir18/bumpalo\""),
-        ("features", "[\"collections\"]"),
-    ])?;
-    compiler.add_dependency("mlx5-datapath",
-        vec![("path", "\"/users/ygina/cornflakes/mlx5-datapath\"")])?;
-    compiler.add_dependency("cornflakes-libos",
-        vec![("path", "\"/users/ygina/cornflakes/cornflakes-libos\"")])?;
-    compiler.import("bumpalo::Bump")?;
+    // Adds mlx5-datapath, and its bumpalo and cornflakes-libos dependencies
+    // since the wrappers use them
+    compiler.add_dependencies_from_manifest(Path::new(
+        "/users/ygina/cornflakes/mlx5-datapath/Cargo.toml"))?;
     compiler.import(
         "mlx5_datapath::datapath::connection::Mlx5Connection")?;
     compiler.import("cornflakes_libos::datapath::{{Datapath, \
@@ -21,8 +17,8 @@ fn main() -> Result<()> {
 
     // Bump
     compiler.add_extern_c_function(
-        Type::new_struct("Bump"),
-        SelfType::Mut,
+        Type::new_struct("bumpalo::Bump"),
+        SelfType::RefMut,
         "reset",
         vec![],
         None,
@@ -32,11 +28,11 @@ fn main() -> Result<()> {
     // ReceivedPkt
     let struct_ty = Type::Struct {
         name: "ReceivedPkt".to_string(),
-        params: vec![Box::new(Type::new_struct("Mlx5Connection"))],
+        args: vec![Box::new(Type::new_struct("Mlx5Connection"))],
     };
     compiler.add_extern_c_function(
         struct_ty.clone(),
-        SelfType::Value,
+        SelfType::Ref,
         "msg_id",
         vec![],
         Some(Type::Primitive("u32".to_string())),
@@ -44,7 +40,7 @@ fn main() -> Result<()> {
     )?;
     compiler.add_extern_c_function(
         struct_ty.clone(),
-        SelfType::Value,
+        SelfType::Ref,
         "conn_id",
         vec![],
         Some(Type::Primitive("usize".to_string())),
@@ -54,7 +50,7 @@ fn main() -> Result<()> {
     // Mlx5Connection
     compiler.add_extern_c_function(
         Type::new_struct("Mlx5Connection"),
-        SelfType::Mut,
+        SelfType::RefMut,
         "set_copying_threshold",
         vec![
             ("copying_threshold", Type::Primitive("usize".to_string())),
@@ -64,7 +60,7 @@ fn main() -> Result<()> {
     )?;
     compiler.add_extern_c_function(
         Type::new_struct("Mlx5Connection"),
-        SelfType::Mut,
+        SelfType::RefMut,
         "add_memory_pool",
         vec![
             ("buf_size", Type::Primitive("usize".to_string())),
@@ -75,7 +71,7 @@ fn main() -> Result<()> {
     )?;
     compiler.add_extern_c_function(
         Type::new_struct("Mlx5Connection"),
-        SelfType::Mut,
+        SelfType::RefMut,
         "add_tx_mempool",
         vec![
             ("size", Type::Primitive("usize".to_string())),
@@ -0,0 +1,183 @@
+//! Writes the C header for the generated crate directly, so that it exists
+//! without building the crate or depending on cbindgen.
+
+use super::{CType, ConstantValue, ExternFunction, Interface};
+use crate::compiler::SerializationCompiler;
+use color_eyre::eyre::Result;
+use std::path::Path;
+
+/// Writes `<package_name>.h` to the package folder.
+pub fn gen_header(
+    package_name: &str,
+    package_folder: &Path,
+    interface: &Interface,
+) -> Result<()> {
+    let guard = format!("{}_H", package_name.to_uppercase());
+    let mut compiler = SerializationCompiler::new();
+    compiler.add_line(&format!("#ifndef {}", guard))?;
+    compiler.add_line(&format!("#define {}", guard))?;
+    compiler.add_newline()?;
+    for header in ["stdbool.h", "stddef.h", "stdint.h"] {
+        compiler.add_line(&format!("#include <{}>", header))?;
+    }
+    compiler.add_newline()?;
+    compiler.add_line("#ifdef __cplusplus")?;
+    compiler.add_line("extern \"C\" {")?;
+    compiler.add_line("#endif")?;
+    compiler.add_newline()?;
+
+    for constant in &interface.constants {
+        if let ConstantValue::Literal(value) = &constant.value {
+            compiler.add_line(&format!("#define {} {}", constant.name,
+                literal(value)))?;
+            compiler.add_newline()?;
+        }
+    }
+
+    for e in &interface.enums {
+        compiler.add_line("enum {")?;
+        for (i, variant) in e.variants.iter().enumerate() {
+            compiler.add_line(&format!("  {}_{} = {},", e.name, variant, i))?;
+        }
+        compiler.add_line("};")?;
+        compiler.add_line(&format!("typedef uintptr_t {};", e.name))?;
+        compiler.add_newline()?;
+    }
+
+    for name in interface.handle_names() {
+        compiler.add_line(&format!("typedef struct {0} {0};", name))?;
+    }
+    compiler.add_newline()?;
+
+    for constant in &interface.constants {
+        match &constant.value {
+            ConstantValue::Literal(_) => continue,
+            ConstantValue::Static => compiler.add_line(&format!(
+                "extern const {};", declaration(&constant.ty, &constant.name)))?,
+            ConstantValue::String(_, len) => compiler.add_line(&format!(
+                "extern const uint8_t {}[{}];", constant.name, len))?,
+        }
+        compiler.add_newline()?;
+    }
+
+    for func in &interface.functions {
+        add_doc_comment(&mut compiler, &func.docs)?;
+        compiler.add_line(&format!("{};", prototype(func)))?;
+        compiler.add_newline()?;
+    }
+
+    compiler.add_line("#ifdef __cplusplus")?;
+    compiler.add_line("}  // extern \"C\"")?;
+    compiler.add_line("#endif")?;
+    compiler.add_newline()?;
+    compiler.add_line(&format!("#endif  /* {} */", guard))?;
+    compiler.flush(&package_folder.join(format!("{}.h", package_name)))?;
+    Ok(())
+}
+
+/// Writes a doc comment in the style cbindgen uses.
+fn add_doc_comment(
+    compiler: &mut SerializationCompiler,
+    docs: &[String],
+) -> Result<()> {
+    if docs.is_empty() {
+        return Ok(());
+    }
+    compiler.add_line("/**")?;
+    for line in docs {
+        if line.is_empty() {
+            compiler.add_line(" *")?;
+        } else {
+            compiler.add_line(&format!(" * {}", line))?;
+        }
+    }
+    compiler.add_line(" */")?;
+    Ok(())
+}
+
+/// The C prototype of an extern function, without the trailing `;`.
+pub fn prototype(func: &ExternFunction) -> String {
+    let params = if func.params.is_empty() {
+        "void".to_string()
+    } else {
+        func.params.iter()
+            .map(|param| declaration(&param.ty, &param.name))
+            .collect::<Vec<_>>()
+            .join(", ")
+    };
+    let ret = func.ret.as_ref()
+        .map(type_name)
+        .unwrap_or_else(|| "void".to_string());
+    join_declarator(&ret, &format!("{}({})", func.name, params))
+}
+
+/// The C name of a type.
+pub fn type_name(ty: &CType) -> String {
+    scoped_type_name(ty, "")
+}
+
+/// The C name of a type, with `scope` prepended to the names of handle and
+/// enum typedefs, e.g., `::` from inside a C++ namespace.
+pub fn scoped_type_name(ty: &CType, scope: &str) -> String {
+    match ty {
+        CType::Primitive(ty) => primitive_name(ty).to_string(),
+        CType::Handle(name) => format!("{}{} *", scope, name),
+        CType::Enum(name) => format!("{}{}", scope, name),
+        CType::Pointer { ty, is_mut } => {
+            let inner = scoped_type_name(ty, scope);
+            match (inner.ends_with('*'), is_mut) {
+                (true, true) => format!("{}*", inner),
+                (true, false) => format!("{}const *", inner),
+                (false, true) => format!("{} *", inner),
+                (false, false) => format!("const {} *", inner),
+            }
+        }
+        CType::Void => "void *".to_string(),
+    }
+}
+
+/// The C name of a Rust primitive.
+pub fn primitive_name(ty: &str) -> &str {
+    match ty {
+        "usize" => "uintptr_t",
+        "isize" => "intptr_t",
+        "u8" => "uint8_t",
+        "u16" => "uint16_t",
+        "u32" => "uint32_t",
+        "u64" => "uint64_t",
+        "i8" => "int8_t",
+        "i16" => "int16_t",
+        "i32" => "int32_t",
+        "i64" => "int64_t",
+        "f32" => "float",
+        "f64" => "double",
+        "char" => "uint32_t",
+        ty => ty,
+    }
+}
+
+/// Declares a variable or parameter of the given type.
+pub fn declaration(ty: &CType, name: &str) -> String {
+    join_declarator(&type_name(ty), name)
+}
+
+/// Joins a type and a declarator, e.g., a name or a function signature.
+pub fn join_declarator(ty: &str, declarator: &str) -> String {
+    if ty.ends_with('*') {
+        format!("{}{}", ty, declarator)
+    } else {
+        format!("{} {}", ty, declarator)
+    }
+}
+
+/// Converts a Rust literal to C, dropping any type suffix.
+pub fn literal(value: &str) -> String {
+    if let Some(value) = value.strip_prefix('-') {
+        return format!("-{}", literal(value));
+    }
+    match syn::parse_str::<syn::Lit>(value) {
+        Ok(syn::Lit::Int(lit)) => lit.base10_digits().to_string(),
+        Ok(syn::Lit::Float(lit)) => lit.base10_digits().to_string(),
+        _ => value.to_string(),
+    }
+}
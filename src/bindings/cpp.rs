@@ -0,0 +1,437 @@
+//! Writes a C++ header that wraps the C header in RAII classes, one per
+//! handle type. Each class owns its handle through a move-only `Handle` that
+//! frees it on destruction.
+
+use super::{
+    c::{join_declarator, scoped_type_name},
+    CType, ExternFunction, FunctionKind, Interface, Ownership, ParamRole,
+};
+use crate::{compiler::SerializationCompiler, types::CppErrorStyle};
+use color_eyre::eyre::Result;
+use std::path::Path;
+
+/// Names that cannot be used as C++ identifiers, and get a trailing `_`.
+const KEYWORDS: &[&str] = &[
+    "alignas", "alignof", "and", "asm", "auto", "bool", "break", "case",
+    "catch", "char", "class", "const", "constexpr", "continue", "default",
+    "delete", "do", "double", "else", "enum", "explicit", "export", "extern",
+    "false", "float", "for", "friend", "goto", "if", "inline", "int", "long",
+    "mutable", "namespace", "new", "noexcept", "not", "nullptr", "operator",
+    "or", "private", "protected", "public", "register", "return", "short",
+    "signed", "sizeof", "static", "struct", "switch", "template", "this",
+    "throw", "true", "try", "typedef", "typename", "union", "unsigned",
+    "using", "virtual", "void", "volatile", "while", "xor",
+];
+
+/// The source of the generic handle owner and the error type, which the
+/// generated classes use.
+const PRELUDE: &str = r#"/// Owns a C handle, freeing it with `free` on destruction. A handle with no
+/// `free` function is borrowed and never freed.
+template <typename T>
+class Handle {
+public:
+  Handle() noexcept = default;
+  Handle(T *ptr, void (*free)(T *)) noexcept : ptr_(ptr), free_(free) {}
+  Handle(const Handle &) = delete;
+  Handle &operator=(const Handle &) = delete;
+  Handle(Handle &&other) noexcept
+      : ptr_(std::exchange(other.ptr_, nullptr)), free_(other.free_) {}
+  Handle &operator=(Handle &&other) noexcept {
+    if (this != &other) {
+      reset();
+      ptr_ = std::exchange(other.ptr_, nullptr);
+      free_ = other.free_;
+    }
+    return *this;
+  }
+  ~Handle() { reset(); }
+
+  T *get() const noexcept { return ptr_; }
+  T *release() noexcept { return std::exchange(ptr_, nullptr); }
+  void reset() noexcept {
+    if (ptr_ != nullptr && free_ != nullptr) {
+      free_(ptr_);
+    }
+    ptr_ = nullptr;
+  }
+  explicit operator bool() const noexcept { return ptr_ != nullptr; }
+
+private:
+  T *ptr_ = nullptr;
+  void (*free_)(T *) = nullptr;
+};
+
+/// A non-zero error code returned by a wrapped function.
+class Error : public std::runtime_error {
+public:
+  explicit Error(uint32_t code)
+      : std::runtime_error("error code " + std::to_string(code)),
+        code_(code) {}
+  uint32_t code() const noexcept { return code_; }
+
+private:
+  uint32_t code_;
+};
+"#;
+
+/// A C++ function that forwards to an extern function.
+struct Wrapper {
+    /// The C++ name of the function.
+    name: String,
+    /// The extern function it forwards to.
+    extern_name: String,
+    /// The declarations of its C++ parameters.
+    params: Vec<String>,
+    /// The C++ type of the value it returns, if any.
+    ret: Option<String>,
+    /// Statements declaring the out-parameters.
+    locals: Vec<String>,
+    /// The arguments of the extern function.
+    args: Vec<String>,
+    /// The expression converting the out-parameters to the return value.
+    ret_expr: Option<String>,
+    /// Whether the extern function returns an error code.
+    error_code: bool,
+    /// Whether the extern function returns false when there is no value,
+    /// and the wrapper returns a `std::optional`.
+    optional: bool,
+    /// Whether it is called on a handle, rather than a static function.
+    is_method: bool,
+    /// Whether it consumes the handle it is called on.
+    consumes_self: bool,
+    /// Whether the handle it returns borrows from the handle it is called
+    /// on, so that it must not be called on a temporary.
+    borrows_self: bool,
+}
+
+/// Writes `<package_name>.hpp` to the package folder. The header includes
+/// `<package_name>.h`, so the C header must be written natively as well.
+pub fn gen_header(
+    package_name: &str,
+    package_folder: &Path,
+    interface: &Interface,
+    error_style: CppErrorStyle,
+) -> Result<()> {
+    let guard = format!("{}_HPP", package_name.to_uppercase());
+    let mut compiler = SerializationCompiler::new();
+    compiler.add_line(&format!("#ifndef {}", guard))?;
+    compiler.add_line(&format!("#define {}", guard))?;
+    compiler.add_newline()?;
+    let mut headers = vec!["cstddef", "cstdint", "optional", "span",
+        "stdexcept", "string", "utility"];
+    if error_style == CppErrorStyle::Expected {
+        headers.push("expected");
+    }
+    for header in headers {
+        compiler.add_line(&format!("#include <{}>", header))?;
+    }
+    compiler.add_newline()?;
+    compiler.add_line(&format!("#include \"{}.h\"", package_name))?;
+    compiler.add_newline()?;
+    compiler.add_line(&format!("namespace {} {{", package_name))?;
+    compiler.add_newline()?;
+    for line in PRELUDE.lines() {
+        compiler.add_line(line)?;
+    }
+    compiler.add_newline()?;
+    if error_style == CppErrorStyle::Expected {
+        compiler.add_line("template <typename T>")?;
+        compiler.add_line("using Result = std::expected<T, Error>;")?;
+        compiler.add_newline()?;
+    }
+
+    for e in &interface.enums {
+        compiler.add_line(&format!("enum class {} : uintptr_t {{", e.name))?;
+        for (i, variant) in e.variants.iter().enumerate() {
+            compiler.add_line(&format!("  {} = {},", identifier(variant), i))?;
+        }
+        compiler.add_line("};")?;
+        compiler.add_newline()?;
+    }
+
+    // Classes
+    let mut class_names = interface.handle_names();
+    for func in &interface.functions {
+        if let FunctionKind::Wrapper { struct_name: Some(name), .. } = &func.kind {
+            if !class_names.contains(name) {
+                class_names.push(name.clone());
+            }
+        }
+    }
+    for name in &class_names {
+        compiler.add_line(&format!("class {};", name))?;
+    }
+    compiler.add_newline()?;
+    let mut classes = vec![];
+    for name in &class_names {
+        let wrappers = interface.functions.iter()
+            .filter(|func| match &func.kind {
+                FunctionKind::Wrapper { struct_name, .. } =>
+                    struct_name.as_ref() == Some(name),
+                FunctionKind::IterNext(iter_name) => iter_name == name,
+                _ => false,
+            })
+            .map(|func| wrapper(func, package_name))
+            .collect::<Vec<_>>();
+        add_class(&mut compiler, name, &wrappers, error_style)?;
+        classes.push((name, wrappers));
+    }
+    let functions = interface.functions.iter()
+        .filter(|func| matches!(func.kind,
+            FunctionKind::Wrapper { struct_name: None, .. }
+                | FunctionKind::DebugLiveHandles))
+        .map(|func| wrapper(func, package_name))
+        .collect::<Vec<_>>();
+    for wrapper in &functions {
+        compiler.add_line(&format!("{};", signature(wrapper, None,
+            error_style)))?;
+    }
+    compiler.add_newline()?;
+
+    // Definitions
+    for (name, wrappers) in &classes {
+        for wrapper in wrappers {
+            add_definition(&mut compiler, wrapper, Some(name), error_style)?;
+        }
+    }
+    for wrapper in &functions {
+        add_definition(&mut compiler, wrapper, None, error_style)?;
+    }
+
+    compiler.add_line(&format!("}}  // namespace {}", package_name))?;
+    compiler.add_newline()?;
+    compiler.add_line(&format!("#endif  // {}", guard))?;
+    compiler.flush(&package_folder.join(format!("{}.hpp", package_name)))?;
+    Ok(())
+}
+
+/// Declares the class that owns handles with the given typedef name.
+fn add_class(
+    compiler: &mut SerializationCompiler,
+    name: &str,
+    wrappers: &[Wrapper],
+    error_style: CppErrorStyle,
+) -> Result<()> {
+    compiler.add_line(&format!("class {} {{", name))?;
+    compiler.add_line("public:")?;
+    compiler.add_line(&format!("  explicit {0}(Handle<::{0}> handle) noexcept",
+        name))?;
+    compiler.add_line("      : handle_(std::move(handle)) {}")?;
+    compiler.add_newline()?;
+    compiler.add_line("  /// The C handle, which is still owned by this object.")?;
+    compiler.add_line(&format!(
+        "  ::{} *c_ptr() const noexcept {{ return handle_.get(); }}", name))?;
+    compiler.add_line("  /// Gives up ownership of the C handle.")?;
+    compiler.add_line(&format!(
+        "  ::{} *into_c_ptr() noexcept {{ return handle_.release(); }}",
+        name))?;
+    for wrapper in wrappers {
+        compiler.add_newline()?;
+        compiler.add_line(&format!("  /// Calls `{}`.", wrapper.extern_name))?;
+        let prefix = if wrapper.is_method { "" } else { "static " };
+        compiler.add_line(&format!("  {}{};", prefix,
+            signature(wrapper, None, error_style)))?;
+        if wrapper.borrows_self {
+            compiler.add_line(&format!("  {} = delete;", qualified_signature(
+                wrapper, None, " &&", error_style)))?;
+        }
+    }
+    compiler.add_newline()?;
+    compiler.add_line("private:")?;
+    compiler.add_line(&format!("  Handle<::{}> handle_;", name))?;
+    compiler.add_line("};")?;
+    compiler.add_newline()?;
+    Ok(())
+}
+
+/// The signature of a wrapper, qualified with its class name if it is being
+/// defined outside the class.
+fn signature(
+    wrapper: &Wrapper,
+    class_name: Option<&str>,
+    error_style: CppErrorStyle,
+) -> String {
+    let qualifier = if wrapper.consumes_self {
+        " &&"
+    } else if wrapper.borrows_self {
+        " &"
+    } else {
+        ""
+    };
+    qualified_signature(wrapper, class_name, qualifier, error_style)
+}
+
+/// The signature of a wrapper with the given ref-qualifier.
+fn qualified_signature(
+    wrapper: &Wrapper,
+    class_name: Option<&str>,
+    qualifier: &str,
+    error_style: CppErrorStyle,
+) -> String {
+    let ret = wrapper.ret.clone().unwrap_or_else(|| "void".to_string());
+    let ret = match (wrapper.error_code, error_style) {
+        (true, CppErrorStyle::Expected) => format!("Result<{}>", ret),
+        _ => ret,
+    };
+    let name = match class_name {
+        Some(class_name) => format!("{}::{}", class_name, wrapper.name),
+        None => wrapper.name.clone(),
+    };
+    join_declarator(&ret, &format!("{}({}){}", name,
+        wrapper.params.join(", "), qualifier))
+}
+
+/// Defines a wrapper after every class is declared.
+fn add_definition(
+    compiler: &mut SerializationCompiler,
+    wrapper: &Wrapper,
+    class_name: Option<&str>,
+    error_style: CppErrorStyle,
+) -> Result<()> {
+    compiler.add_line(&format!("inline {} {{",
+        signature(wrapper, class_name, error_style)))?;
+    for local in &wrapper.locals {
+        compiler.add_line(&format!("  {}", local))?;
+    }
+    let call = format!("::{}({})", wrapper.extern_name, wrapper.args.join(", "));
+    if wrapper.error_code {
+        compiler.add_line(&format!("  uint32_t code = {};", call))?;
+        compiler.add_line("  if (code != 0) {")?;
+        match error_style {
+            CppErrorStyle::Exceptions =>
+                compiler.add_line("    throw Error(code);")?,
+            CppErrorStyle::Expected =>
+                compiler.add_line("    return std::unexpected(Error(code));")?,
+        }
+        compiler.add_line("  }")?;
+    } else if wrapper.ret.is_some() && wrapper.ret_expr.is_none() {
+        compiler.add_line(&format!("  return {};", call))?;
+    } else if wrapper.optional {
+        compiler.add_line(&format!("  if (!{}) {{", call))?;
+        compiler.add_line("    return std::nullopt;")?;
+        compiler.add_line("  }")?;
+    } else {
+        compiler.add_line(&format!("  {};", call))?;
+    }
+    match &wrapper.ret_expr {
+        Some(ret_expr) => compiler.add_line(&format!("  return {};",
+            ret_expr))?,
+        None if wrapper.error_code
+                && error_style == CppErrorStyle::Expected =>
+            compiler.add_line("  return {};")?,
+        None => {}
+    }
+    compiler.add_line("}")?;
+    compiler.add_newline()?;
+    Ok(())
+}
+
+/// Converts the parameters of an extern function to those of its wrapper.
+fn wrapper(func: &ExternFunction, package_name: &str) -> Wrapper {
+    let error_code = matches!(func.kind,
+        FunctionKind::Wrapper { error_code: true, .. });
+    let mut wrapper = Wrapper {
+        name: identifier(func.short_name(package_name)),
+        extern_name: func.name.clone(),
+        params: vec![],
+        ret: None,
+        locals: vec![],
+        args: vec![],
+        ret_expr: None,
+        error_code,
+        optional: matches!(func.kind, FunctionKind::IterNext(_)),
+        is_method: false,
+        consumes_self: false,
+        borrows_self: false,
+    };
+    let mut params = func.params.iter().peekable();
+    while let Some(param) = params.next() {
+        let name = identifier(&param.name);
+        let has_len = params.peek().is_some_and(|next|
+            matches!(next.role, ParamRole::Len | ParamRole::ReturnLen));
+        match (param.role, &param.ty) {
+            (ParamRole::SelfHandle(ownership), _) => {
+                wrapper.is_method = true;
+                wrapper.consumes_self = ownership == Ownership::Transfer;
+                wrapper.args.push(if wrapper.consumes_self {
+                    "handle_.release()".to_string()
+                } else {
+                    "handle_.get()".to_string()
+                });
+            }
+            (ParamRole::Arg(Ownership::Transfer), CType::Handle(ty)) => {
+                wrapper.params.push(format!("{} {}", ty, name));
+                wrapper.args.push(format!("{}.into_c_ptr()", name));
+            }
+            (ParamRole::Arg(_), CType::Handle(ty)) => {
+                wrapper.params.push(format!("const {} &{}", ty, name));
+                wrapper.args.push(format!("{}.c_ptr()", name));
+            }
+            (ParamRole::Arg(_), CType::Enum(ty)) => {
+                wrapper.params.push(format!("{} {}", ty, name));
+                wrapper.args.push(format!("static_cast<::{}>({})", ty, name));
+            }
+            (ParamRole::Arg(_), CType::Pointer { ty, .. }) if has_len => {
+                params.next();
+                wrapper.params.push(format!("{} {}", span(ty), name));
+                wrapper.args.push(format!("{}.data()", name));
+                wrapper.args.push(format!("{}.size()", name));
+            }
+            (ParamRole::Arg(_) | ParamRole::Len, ty) => {
+                wrapper.params.push(join_declarator(
+                    &scoped_type_name(ty, "::"), &name));
+                wrapper.args.push(name);
+            }
+            (ParamRole::Return(_), CType::Pointer { ty, .. }) => {
+                wrapper.borrows_self = func.params.iter().any(|self_param|
+                    matches!(self_param.role, ParamRole::SelfHandle(_))
+                        && param.borrows.contains(&self_param.name));
+                wrapper.locals.push(format!("{} = {{}};", join_declarator(
+                    &scoped_type_name(ty, "::"), "ret")));
+                wrapper.args.push("&ret".to_string());
+                let (ret, ret_expr) = match &**ty {
+                    CType::Handle(class_name) => (
+                        class_name.clone(),
+                        format!("{0}(Handle<::{0}>(ret, {1}))", class_name,
+                            param.free_with.as_ref()
+                                .map(|free| format!("::{}", free))
+                                .unwrap_or_else(|| "nullptr".to_string())),
+                    ),
+                    CType::Enum(ty) =>
+                        (ty.clone(), format!("static_cast<{}>(ret)", ty)),
+                    CType::Pointer { ty, .. } if has_len => {
+                        params.next();
+                        wrapper.locals.push("uintptr_t ret_len = 0;"
+                            .to_string());
+                        wrapper.args.push("&ret_len".to_string());
+                        (span(ty), format!("{}(ret, ret_len)", span(ty)))
+                    }
+                    ty => (scoped_type_name(ty, "::"), "ret".to_string()),
+                };
+                wrapper.ret = Some(ret);
+                wrapper.ret_expr = Some(ret_expr);
+            }
+            (ParamRole::Return(_) | ParamRole::ReturnLen, _) => unreachable!(),
+        }
+    }
+    if wrapper.optional {
+        wrapper.ret = wrapper.ret.map(|ret| format!("std::optional<{}>", ret));
+    } else if !error_code && func.ret.is_some() {
+        wrapper.ret = func.ret.as_ref().map(|ret| scoped_type_name(ret, "::"));
+    }
+    wrapper
+}
+
+/// A `std::span` over a buffer of the given element type.
+fn span(ty: &CType) -> String {
+    format!("std::span<{} const>", scoped_type_name(ty, "::"))
+}
+
+/// The name as a C++ identifier.
+fn identifier(name: &str) -> String {
+    if KEYWORDS.contains(&name) {
+        format!("{}_", name)
+    } else {
+        name.to_string()
+    }
+}
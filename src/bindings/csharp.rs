@@ -0,0 +1,506 @@
+//! Writes C# bindings that declare every extern function with `[DllImport]`
+//! and wrap each handle type in a `SafeHandle` that frees the handle.
+//!
+//! Native signatures only use blittable types, so calls need no marshalling:
+//!
+//! | C             | Native   | Wrapper               |
+//! |---------------|----------|-----------------------|
+//! | `usize`       | `nuint`  | `nuint`               |
+//! | `isize`       | `nint`   | `nint`                |
+//! | `u8`..`u64`   | `byte`..`ulong` | same           |
+//! | `i8`..`i64`   | `sbyte`..`long` | same           |
+//! | `f32`, `f64`  | `float`, `double` | same         |
+//! | `bool`        | `byte`   | `bool`                |
+//! | `char`        | `uint`   | `uint`                |
+//! | enum          | `nuint`  | C# enum               |
+//! | handle        | `IntPtr` | `SafeHandle` subclass |
+//! | buffer, len   | `T*`, `nuint` | `ReadOnlySpan<T>` |
+//! | raw pointer   | `T*`     | `T*`                  |
+
+use super::{
+    camel_case, CType, ExternFunction, FunctionKind, Interface, Ownership,
+    ParamRole,
+};
+use crate::compiler::SerializationCompiler;
+use color_eyre::eyre::Result;
+use std::path::Path;
+
+/// Names that cannot be used as C# identifiers, and get a `@` prefix.
+const KEYWORDS: &[&str] = &[
+    "abstract", "as", "base", "bool", "break", "byte", "case", "catch",
+    "char", "checked", "class", "const", "continue", "decimal", "default",
+    "delegate", "do", "double", "else", "enum", "event", "explicit", "extern",
+    "false", "finally", "fixed", "float", "for", "foreach", "goto", "if",
+    "implicit", "in", "int", "interface", "internal", "is", "lock", "long",
+    "namespace", "new", "null", "object", "operator", "out", "override",
+    "params", "private", "protected", "public", "readonly", "ref", "return",
+    "sbyte", "sealed", "short", "sizeof", "stackalloc", "static", "string",
+    "struct", "switch", "this", "throw", "true", "try", "typeof", "uint",
+    "ulong", "unchecked", "unsafe", "ushort", "using", "virtual", "void",
+    "volatile", "while",
+];
+
+/// The source of the error type, which the generated methods throw.
+const PRELUDE: &str = r#"/// <summary>A non-zero error code returned by a wrapped function.</summary>
+public sealed class NativeException : Exception
+{
+    public NativeException(uint code) : base($"error code {code}")
+    {
+        Code = code;
+    }
+
+    public uint Code { get; }
+}
+"#;
+
+/// The source of the members shared by every handle type. `{name}` is
+/// replaced by the class name.
+const HANDLE: &str = r#"internal {name}(IntPtr ptr, Action<IntPtr>? free, params SafeHandle[] borrows)
+    : base(IntPtr.Zero, free != null || borrows.Length > 0)
+{
+    this.free = free;
+    this.borrows = borrows;
+    foreach (SafeHandle borrowed in borrows)
+    {
+        bool success = false;
+        borrowed.DangerousAddRef(ref success);
+    }
+    SetHandle(ptr);
+}
+
+private readonly Action<IntPtr>? free;
+
+/// <summary>The handles this handle borrows from, kept alive until it is
+/// released.</summary>
+private readonly SafeHandle[] borrows;
+
+public override bool IsInvalid => handle == IntPtr.Zero;
+
+protected override bool ReleaseHandle()
+{
+    free?.Invoke(handle);
+    foreach (SafeHandle borrowed in borrows)
+    {
+        borrowed.DangerousRelease();
+    }
+    return true;
+}
+
+/// <summary>Gives up ownership of the C handle.</summary>
+internal IntPtr TakeHandle()
+{
+    IntPtr ptr = handle;
+    SetHandleAsInvalid();
+    return ptr;
+}"#;
+
+/// A line of generated source and its indentation level.
+struct Lines(Vec<(usize, String)>);
+
+impl Lines {
+    fn push(&mut self, indent: usize, line: impl Into<String>) {
+        self.0.push((indent, line.into()));
+    }
+}
+
+/// Writes `<package_name>.cs` to the package folder. The bindings load
+/// `<package_name>_c` through the .NET library search path.
+pub fn gen_bindings(
+    package_name: &str,
+    package_folder: &Path,
+    interface: &Interface,
+) -> Result<()> {
+    let mut lines = Lines(vec![]);
+    lines.push(0, "// <auto-generated/>");
+    lines.push(0, "#nullable enable");
+    lines.push(0, "");
+    lines.push(0, "using System;");
+    lines.push(0, "using System.Collections;");
+    lines.push(0, "using System.Collections.Generic;");
+    lines.push(0, "using System.Runtime.InteropServices;");
+    lines.push(0, "");
+    lines.push(0, format!("namespace {}", camel_case(package_name)));
+    lines.push(0, "{");
+    for line in PRELUDE.lines() {
+        lines.push(1, line);
+    }
+
+    for e in &interface.enums {
+        lines.push(0, "");
+        lines.push(1, format!("public enum {} : ulong", e.name));
+        lines.push(1, "{");
+        for (i, variant) in e.variants.iter().enumerate() {
+            lines.push(2, format!("{} = {},", variant, i));
+        }
+        lines.push(1, "}");
+    }
+
+    // Native declarations
+    lines.push(0, "");
+    lines.push(1, "internal static unsafe class Native");
+    lines.push(1, "{");
+    lines.push(2, format!("private const string Library = \"{}_c\";",
+        package_name));
+    for func in &interface.functions {
+        lines.push(0, "");
+        lines.push(2, "[DllImport(Library, CallingConvention = \
+            CallingConvention.Cdecl, ExactSpelling = true)]");
+        let params = func.params.iter()
+            .map(|param| format!("{} {}", native_type(&param.ty),
+                identifier(&param.name)))
+            .collect::<Vec<_>>();
+        lines.push(2, format!("internal static extern {} {}({});",
+            func.ret.as_ref().map(native_type)
+                .unwrap_or_else(|| "void".to_string()),
+            func.name, params.join(", ")));
+    }
+    lines.push(1, "}");
+
+    // Handle types
+    let mut class_names = interface.handle_names();
+    for func in &interface.functions {
+        if let FunctionKind::Wrapper { struct_name: Some(name), .. } = &func.kind {
+            if !class_names.contains(name) {
+                class_names.push(name.clone());
+            }
+        }
+    }
+    for name in &class_names {
+        let class_name = camel_case(name);
+        let members = interface.functions.iter()
+            .filter(|func| match &func.kind {
+                FunctionKind::Wrapper { struct_name, .. } =>
+                    struct_name.as_ref() == Some(name),
+                FunctionKind::IterNext(iter_name) => iter_name == name,
+                _ => false,
+            })
+            .collect::<Vec<_>>();
+        let item_ty = members.iter()
+            .find(|func| matches!(func.kind, FunctionKind::IterNext(_)))
+            .map(|func| wrapper(func, package_name, &class_name).ret)
+            .filter(|ty| !ty.starts_with("ReadOnlySpan")
+                && !ty.ends_with('*'));
+        lines.push(0, "");
+        lines.push(1, format!("/// <summary>Owns a <c>{}</c> handle.</summary>",
+            name));
+        match &item_ty {
+            Some(item_ty) => lines.push(1, format!("public sealed class {} : \
+                SafeHandle, IEnumerable<{}>", class_name, item_ty)),
+            None => lines.push(1, format!("public sealed class {} : SafeHandle",
+                class_name)),
+        }
+        lines.push(1, "{");
+        for line in HANDLE.replace("{name}", &class_name).lines() {
+            lines.push(2, line);
+        }
+        for func in members {
+            lines.push(0, "");
+            add_method(&mut lines, &wrapper(func, package_name, &class_name));
+        }
+        if let Some(item_ty) = item_ty {
+            lines.push(0, "");
+            lines.push(2, format!("public IEnumerator<{}> GetEnumerator()",
+                item_ty));
+            lines.push(2, "{");
+            lines.push(3, "while (TryNext(out var item))");
+            lines.push(3, "{");
+            lines.push(4, "yield return item;");
+            lines.push(3, "}");
+            lines.push(2, "}");
+            lines.push(0, "");
+            lines.push(2, "IEnumerator IEnumerable.GetEnumerator() => \
+                GetEnumerator();");
+        }
+        lines.push(1, "}");
+    }
+
+    let functions = interface.functions.iter()
+        .filter(|func| matches!(func.kind,
+            FunctionKind::Wrapper { struct_name: None, .. }
+                | FunctionKind::DebugLiveHandles))
+        .collect::<Vec<_>>();
+    if !functions.is_empty() {
+        let class_name = "Functions";
+        lines.push(0, "");
+        lines.push(1, format!("public static class {}", class_name));
+        lines.push(1, "{");
+        for (i, func) in functions.into_iter().enumerate() {
+            if i > 0 {
+                lines.push(0, "");
+            }
+            add_method(&mut lines, &wrapper(func, package_name, class_name));
+        }
+        lines.push(1, "}");
+    }
+    lines.push(0, "}");
+
+    let mut compiler = SerializationCompiler::new();
+    for (indent, line) in lines.0 {
+        if line.is_empty() {
+            compiler.add_newline()?;
+        } else {
+            compiler.add_line(&format!("{}{}", "    ".repeat(indent), line))?;
+        }
+    }
+    compiler.flush(&package_folder.join(format!("{}.cs", package_name)))?;
+    Ok(())
+}
+
+/// A C# method that calls an extern function.
+struct Wrapper {
+    name: String,
+    extern_name: String,
+    params: Vec<String>,
+    /// The C# return type.
+    ret: String,
+    locals: Vec<String>,
+    /// Buffers pinned for the duration of the call, as `(pointer, span)`.
+    fixed: Vec<(String, String)>,
+    args: Vec<String>,
+    /// Handles that must stay alive until the call returns.
+    keep_alive: Vec<String>,
+    ret_expr: Option<String>,
+    error_code: bool,
+    is_next: bool,
+    is_static: bool,
+}
+
+/// Converts the parameters of an extern function to those of its wrapper.
+fn wrapper(func: &ExternFunction, package_name: &str, class_name: &str) -> Wrapper {
+    let is_next = matches!(func.kind, FunctionKind::IterNext(_));
+    let mut name = if is_next {
+        "TryNext".to_string()
+    } else {
+        camel_case(func.short_name(package_name))
+    };
+    if name == class_name {
+        name.push('_');
+    }
+    let mut wrapper = Wrapper {
+        name,
+        extern_name: func.name.clone(),
+        params: vec![],
+        ret: "void".to_string(),
+        locals: vec![],
+        fixed: vec![],
+        args: vec![],
+        keep_alive: vec![],
+        ret_expr: None,
+        error_code: matches!(func.kind,
+            FunctionKind::Wrapper { error_code: true, .. }),
+        is_next,
+        is_static: true,
+    };
+    let mut params = func.params.iter().peekable();
+    while let Some(param) = params.next() {
+        let name = identifier(&param.name);
+        let has_len = params.peek().is_some_and(|next|
+            matches!(next.role, ParamRole::Len | ParamRole::ReturnLen));
+        match (param.role, &param.ty) {
+            (ParamRole::SelfHandle(ownership), _) => {
+                wrapper.is_static = false;
+                if ownership == Ownership::Transfer {
+                    wrapper.args.push("TakeHandle()".to_string());
+                } else {
+                    wrapper.args.push("handle".to_string());
+                    wrapper.keep_alive.push("this".to_string());
+                }
+            }
+            (ParamRole::Arg(ownership), CType::Handle(ty)) => {
+                wrapper.params.push(format!("{} {}", camel_case(ty), name));
+                if ownership == Ownership::Transfer {
+                    wrapper.args.push(format!("{}.TakeHandle()", name));
+                } else {
+                    wrapper.args.push(format!("{}.DangerousGetHandle()", name));
+                    wrapper.keep_alive.push(name);
+                }
+            }
+            (ParamRole::Arg(_), CType::Pointer { ty, .. }) if has_len => {
+                params.next();
+                wrapper.params.push(format!("ReadOnlySpan<{}> {}",
+                    native_type(ty), name));
+                wrapper.fixed.push((
+                    format!("{}* {}_ptr", native_type(ty), param.name),
+                    name.clone(),
+                ));
+                wrapper.args.push(format!("{}_ptr", param.name));
+                wrapper.args.push(format!("(nuint){}.Length", name));
+            }
+            (ParamRole::Arg(_) | ParamRole::Len, ty) => {
+                wrapper.params.push(format!("{} {}", public_type(ty), name));
+                wrapper.args.push(to_native(&name, ty));
+            }
+            (ParamRole::Return(_), CType::Pointer { ty, .. }) => {
+                wrapper.locals.push(format!("{} ret;", native_type(ty)));
+                wrapper.args.push("&ret".to_string());
+                let (ret, ret_expr) = match &**ty {
+                    CType::Handle(ty) => (
+                        camel_case(ty),
+                        format!("new {}(ret, {}{})", camel_case(ty),
+                            param.free_with.as_ref()
+                                .map(|free| format!("Native.{}", free))
+                                .unwrap_or_else(|| "null".to_string()),
+                            param.borrows.iter()
+                                .map(|name| match func.params.iter()
+                                        .find(|p| &p.name == name) {
+                                    Some(p) if matches!(p.role,
+                                            ParamRole::SelfHandle(_)) =>
+                                        ", this".to_string(),
+                                    _ => format!(", {}", identifier(name)),
+                                })
+                                .collect::<String>()),
+                    ),
+                    CType::Pointer { ty: elem, .. } if has_len => {
+                        params.next();
+                        wrapper.locals.push("nuint ret_len;".to_string());
+                        wrapper.args.push("&ret_len".to_string());
+                        let span = format!("ReadOnlySpan<{}>", native_type(elem));
+                        let ret_expr = format!("new {}(ret, (int)ret_len)", span);
+                        (span, ret_expr)
+                    }
+                    ty => (public_type(ty), from_native("ret", ty)),
+                };
+                wrapper.ret = ret;
+                wrapper.ret_expr = Some(ret_expr);
+            }
+            (ParamRole::Return(_) | ParamRole::ReturnLen, _) => unreachable!(),
+        }
+    }
+    if !wrapper.error_code && !is_next {
+        if let Some(ret) = &func.ret {
+            wrapper.ret = public_type(ret);
+        }
+    }
+    wrapper
+}
+
+/// Adds a method that calls an extern function.
+fn add_method(lines: &mut Lines, wrapper: &Wrapper) {
+    lines.push(2, format!("/// <summary>Calls <c>{}</c>.</summary>",
+        wrapper.extern_name));
+    let modifiers = if wrapper.is_static {
+        "public static unsafe"
+    } else {
+        "public unsafe"
+    };
+    let mut params = wrapper.params.clone();
+    let ret = if wrapper.is_next {
+        params.push(format!("out {} item", wrapper.ret));
+        "bool"
+    } else {
+        &wrapper.ret
+    };
+    lines.push(2, format!("{} {} {}({})", modifiers, ret, wrapper.name,
+        params.join(", ")));
+    lines.push(2, "{");
+    let mut indent = 3;
+    for local in &wrapper.locals {
+        lines.push(indent, local.clone());
+    }
+    for (ptr, span) in &wrapper.fixed {
+        lines.push(indent, format!("fixed ({} = {})", ptr, span));
+        lines.push(indent, "{");
+        indent += 1;
+    }
+    let call = format!("Native.{}({})", wrapper.extern_name,
+        wrapper.args.join(", "));
+    let result = if wrapper.error_code {
+        lines.push(indent, format!("uint code = {};", call));
+        Some("code")
+    } else if wrapper.is_next {
+        lines.push(indent, format!("bool ok = {} != 0;", call));
+        Some("ok")
+    } else if wrapper.ret != "void" && wrapper.ret_expr.is_none() {
+        lines.push(indent, format!("{} result = {};", wrapper.ret, call));
+        Some("result")
+    } else {
+        lines.push(indent, format!("{};", call));
+        None
+    };
+    for name in &wrapper.keep_alive {
+        lines.push(indent, format!("GC.KeepAlive({});", name));
+    }
+    if wrapper.error_code {
+        lines.push(indent, "if (code != 0)");
+        lines.push(indent, "{");
+        lines.push(indent + 1, "throw new NativeException(code);");
+        lines.push(indent, "}");
+    }
+    match (&wrapper.ret_expr, result) {
+        (Some(ret_expr), Some("ok")) => {
+            lines.push(indent, format!("item = ok ? {} : default!;", ret_expr));
+            lines.push(indent, "return ok;");
+        }
+        (Some(ret_expr), _) => lines.push(indent, format!("return {};",
+            ret_expr)),
+        (None, Some("result")) => lines.push(indent, "return result;"),
+        (None, _) => {}
+    }
+    for _ in &wrapper.fixed {
+        indent -= 1;
+        lines.push(indent, "}");
+    }
+    lines.push(2, "}");
+}
+
+/// The blittable type of a C type in native declarations.
+fn native_type(ty: &CType) -> String {
+    match ty {
+        CType::Primitive(ty) => match ty.as_str() {
+            "usize" => "nuint",
+            "isize" => "nint",
+            "u8" | "bool" => "byte",
+            "u16" => "ushort",
+            "u32" | "char" => "uint",
+            "u64" => "ulong",
+            "i8" => "sbyte",
+            "i16" => "short",
+            "i32" => "int",
+            "i64" => "long",
+            "f32" => "float",
+            "f64" => "double",
+            _ => "IntPtr",
+        }.to_string(),
+        CType::Handle(_) | CType::Void => "IntPtr".to_string(),
+        CType::Enum(_) => "nuint".to_string(),
+        CType::Pointer { ty, .. } => format!("{}*", native_type(ty)),
+    }
+}
+
+/// The type of a C type in wrapper signatures.
+fn public_type(ty: &CType) -> String {
+    match ty {
+        CType::Primitive(ty) if ty == "bool" => "bool".to_string(),
+        CType::Handle(name) => camel_case(name),
+        CType::Enum(name) => name.clone(),
+        ty => native_type(ty),
+    }
+}
+
+/// Converts a wrapper value to its native type.
+fn to_native(expr: &str, ty: &CType) -> String {
+    match ty {
+        CType::Primitive(ty) if ty == "bool" =>
+            format!("({} ? (byte)1 : (byte)0)", expr),
+        CType::Enum(_) => format!("(nuint){}", expr),
+        _ => expr.to_string(),
+    }
+}
+
+/// Converts a native value to its wrapper type.
+fn from_native(expr: &str, ty: &CType) -> String {
+    match ty {
+        CType::Primitive(ty) if ty == "bool" => format!("{} != 0", expr),
+        CType::Enum(name) => format!("({}){}", name, expr),
+        _ => expr.to_string(),
+    }
+}
+
+/// The name as a C# identifier.
+fn identifier(name: &str) -> String {
+    if KEYWORDS.contains(&name) {
+        format!("@{}", name)
+    } else {
+        name.to_string()
+    }
+}
@@ -0,0 +1,395 @@
+//! Writes a Go package that calls the generated library through cgo, and
+//! wraps each handle type in a struct with a finalizer that frees the handle.
+
+use super::{
+    c::primitive_name, camel_case, CType, ExternFunction, FunctionKind,
+    Interface, Ownership, ParamRole,
+};
+use crate::compiler::SerializationCompiler;
+use color_eyre::eyre::Result;
+use std::{fs, path::Path};
+
+/// Names that cannot be used as Go identifiers, and get a trailing `_`.
+const KEYWORDS: &[&str] = &[
+    "break", "case", "chan", "const", "continue", "default", "defer", "else",
+    "fallthrough", "for", "func", "go", "goto", "if", "import", "interface",
+    "map", "package", "range", "return", "select", "struct", "switch", "type",
+    "var",
+];
+
+/// The source of the error type, which the generated functions return.
+const PRELUDE: &str = r#"// Error is a non-zero error code returned by a wrapped function.
+type Error struct {
+	Code uint32
+}
+
+func (e *Error) Error() string {
+	return fmt.Sprintf("error code %d", e.Code)
+}
+"#;
+
+/// The source of a handle type. `{name}` is replaced by the Go type and
+/// `{c_name}` by the C typedef.
+const HANDLE: &str = r#"// {name} owns a `{c_name}` handle, freeing it when garbage collected.
+type {name} struct {
+	ptr     *C.{c_name}
+	free    func(*C.{c_name})
+	borrows []any
+}
+
+// new{name} wraps a handle. A handle with no free function is borrowed and
+// never freed. A handle that borrows from other handles keeps them alive.
+func new{name}(ptr *C.{c_name}, free func(*C.{c_name}), borrows ...any) *{name} {
+	h := &{name}{ptr: ptr, free: free, borrows: borrows}
+	if free != nil {
+		runtime.SetFinalizer(h, (*{name}).Close)
+	}
+	return h
+}
+
+// Close frees the handle now rather than when it is garbage collected.
+func (h *{name}) Close() {
+	if h.ptr != nil && h.free != nil {
+		h.free(h.ptr)
+	}
+	h.ptr = nil
+	runtime.SetFinalizer(h, nil)
+}
+
+// CPtr returns the C handle, which is still owned by h.
+func (h *{name}) CPtr() *C.{c_name} {
+	return h.ptr
+}
+
+// take gives up ownership of the C handle.
+func (h *{name}) take() *C.{c_name} {
+	ptr := h.ptr
+	h.ptr = nil
+	runtime.SetFinalizer(h, nil)
+	return ptr
+}
+"#;
+
+/// Writes `go/<package_name>.go` to the package folder. The package includes
+/// the C header from the package folder and links the library from
+/// `target/release` or `target/debug`, so the C header must be written
+/// natively as well.
+pub fn gen_package(
+    package_name: &str,
+    package_folder: &Path,
+    interface: &Interface,
+) -> Result<()> {
+    let mut body = vec![];
+    for line in PRELUDE.lines() {
+        body.push(line.to_string());
+    }
+
+    for e in &interface.enums {
+        body.push(String::new());
+        body.push(format!("// {} is the index of a variant of the Rust enum.",
+            e.name));
+        body.push(format!("type {} uintptr", e.name));
+        body.push(String::new());
+        body.push("const (".to_string());
+        for (i, variant) in e.variants.iter().enumerate() {
+            body.push(format!("\t{}{} {} = {}", e.name, variant, e.name, i));
+        }
+        body.push(")".to_string());
+    }
+
+    // Handle types
+    let mut class_names = interface.handle_names();
+    for func in &interface.functions {
+        if let FunctionKind::Wrapper { struct_name: Some(name), .. } = &func.kind {
+            if !class_names.contains(name) {
+                class_names.push(name.clone());
+            }
+        }
+    }
+    // Package-level functions must not share a name with a type
+    let type_names: Vec<String> = class_names.iter()
+        .map(|name| camel_case(name))
+        .chain(interface.enums.iter().map(|e| e.name.clone()))
+        .chain(["Error".to_string()])
+        .collect();
+    for name in &class_names {
+        body.push(String::new());
+        let handle = HANDLE.replace("{name}", &camel_case(name))
+            .replace("{c_name}", name);
+        for line in handle.lines() {
+            body.push(line.to_string());
+        }
+        for func in &interface.functions {
+            let is_member = match &func.kind {
+                FunctionKind::Wrapper { struct_name, .. } =>
+                    struct_name.as_ref() == Some(name),
+                FunctionKind::IterNext(iter_name) => iter_name == name,
+                _ => false,
+            };
+            if is_member {
+                body.push(String::new());
+                add_function(&mut body, func, package_name, Some(name),
+                    &type_names);
+            }
+        }
+    }
+
+    for func in &interface.functions {
+        if matches!(func.kind, FunctionKind::Wrapper { struct_name: None, .. }
+                | FunctionKind::DebugLiveHandles) {
+            body.push(String::new());
+            add_function(&mut body, func, package_name, None, &type_names);
+        }
+    }
+
+    let go_folder = package_folder.join("go");
+    fs::create_dir_all(&go_folder)?;
+    let mut compiler = SerializationCompiler::new();
+    compiler.add_line(&format!("// Package {} calls lib{}_c through cgo.",
+        package_name, package_name))?;
+    compiler.add_line(&format!("package {}", package_name))?;
+    compiler.add_newline()?;
+    compiler.add_line("/*")?;
+    compiler.add_line("#cgo CFLAGS: -I${SRCDIR}/..")?;
+    compiler.add_line(&format!("#cgo LDFLAGS: \
+        -L${{SRCDIR}}/../target/release -L${{SRCDIR}}/../target/debug \
+        -Wl,-rpath,${{SRCDIR}}/../target/release \
+        -Wl,-rpath,${{SRCDIR}}/../target/debug -l{}_c", package_name))?;
+    compiler.add_line(&format!("#include \"{}.h\"", package_name))?;
+    compiler.add_line("*/")?;
+    compiler.add_line("import \"C\"")?;
+    compiler.add_newline()?;
+    compiler.add_line("import (")?;
+    for import in ["fmt", "runtime", "unsafe"] {
+        let prefix = format!("{}.", import);
+        if body.iter().any(|line| line.contains(&prefix)) {
+            compiler.add_line(&format!("\t\"{}\"", import))?;
+        }
+    }
+    compiler.add_line(")")?;
+    compiler.add_newline()?;
+    for line in body {
+        compiler.add_line(&line)?;
+    }
+    compiler.flush(&go_folder.join(format!("{}.go", package_name)))?;
+    Ok(())
+}
+
+/// Adds the Go function or method that calls an extern function. A function
+/// that is not a method and would share a name with one of `type_names`
+/// gets a `Func` suffix, e.g., `ConnSharedFunc` for `Conn_shared`.
+fn add_function(
+    body: &mut Vec<String>,
+    func: &ExternFunction,
+    package_name: &str,
+    class_name: Option<&str>,
+    type_names: &[String],
+) {
+    let is_next = matches!(func.kind, FunctionKind::IterNext(_));
+    let is_error_code = matches!(func.kind,
+        FunctionKind::Wrapper { error_code: true, .. });
+    let mut receiver = None;
+    let mut params = vec![];
+    let mut locals = vec![];
+    let mut args = vec![];
+    let mut ret: Option<(String, String)> = None;
+    let mut iter = func.params.iter().peekable();
+    while let Some(param) = iter.next() {
+        let name = identifier(&param.name);
+        let has_len = iter.peek().is_some_and(|next|
+            matches!(next.role, ParamRole::Len | ParamRole::ReturnLen));
+        match (param.role, &param.ty) {
+            (ParamRole::SelfHandle(ownership), _) => {
+                receiver = class_name.map(camel_case);
+                args.push(if ownership == Ownership::Transfer {
+                    "h.take()".to_string()
+                } else {
+                    "h.ptr".to_string()
+                });
+            }
+            (ParamRole::Arg(Ownership::Transfer), CType::Handle(ty)) => {
+                params.push(format!("{} *{}", name, camel_case(ty)));
+                args.push(format!("{}.take()", name));
+            }
+            (ParamRole::Arg(_), CType::Handle(ty)) => {
+                params.push(format!("{} *{}", name, camel_case(ty)));
+                args.push(format!("{}.ptr", name));
+            }
+            (ParamRole::Arg(_), CType::Pointer { ty, .. }) if has_len => {
+                iter.next();
+                params.push(format!("{} []{}", name, go_type(ty)));
+                args.push(format!("({})(unsafe.Pointer(unsafe.SliceData({})))",
+                    cgo_type(&CType::Pointer { ty: ty.clone(), is_mut: false }),
+                    name));
+                args.push(format!("C.uintptr_t(len({}))", name));
+            }
+            (ParamRole::Arg(_) | ParamRole::Len, ty) => {
+                params.push(format!("{} {}", name, go_type(ty)));
+                args.push(match ty {
+                    CType::Primitive(_) | CType::Enum(_) =>
+                        format!("{}({})", cgo_type(ty), name),
+                    CType::Pointer { .. } =>
+                        format!("({})({})", cgo_type(ty), name),
+                    _ => name,
+                });
+            }
+            (ParamRole::Return(_), CType::Pointer { ty, .. }) => {
+                locals.push(format!("var ret {}", cgo_type(ty)));
+                args.push("&ret".to_string());
+                ret = Some(match &**ty {
+                    CType::Handle(class_name) => (
+                        format!("*{}", camel_case(class_name)),
+                        format!("new{}(ret, {}{})", camel_case(class_name),
+                            param.free_with.as_ref()
+                                .map(|free| format!("func(ptr *C.{}) {{ \
+                                    C.{}(ptr) }}", class_name, free))
+                                .unwrap_or_else(|| "nil".to_string()),
+                            param.borrows.iter()
+                                .map(|name| match func.params.iter()
+                                        .find(|p| &p.name == name) {
+                                    Some(p) if matches!(p.role,
+                                            ParamRole::SelfHandle(_)) =>
+                                        ", h".to_string(),
+                                    _ => format!(", {}", identifier(name)),
+                                })
+                                .collect::<String>()),
+                    ),
+                    CType::Pointer { ty: elem, .. } if has_len => {
+                        iter.next();
+                        locals.push("var retLen C.uintptr_t".to_string());
+                        args.push("&retLen".to_string());
+                        match &**elem {
+                            CType::Primitive(_) => (
+                                format!("[]{}", go_type(elem)),
+                                format!("unsafe.Slice((*{})(unsafe.Pointer(\
+                                    ret)), int(retLen))", go_type(elem)),
+                            ),
+                            _ => (
+                                format!("[]{}", cgo_type(elem)),
+                                "unsafe.Slice(ret, int(retLen))".to_string(),
+                            ),
+                        }
+                    }
+                    ty @ (CType::Primitive(_) | CType::Enum(_)
+                            | CType::Pointer { .. }) =>
+                        (go_type(ty), format!("{}(ret)", go_type(ty))),
+                    ty => (cgo_type(ty), "ret".to_string()),
+                });
+            }
+            (ParamRole::Return(_) | ParamRole::ReturnLen, _) => unreachable!(),
+        }
+    }
+    if ret.is_none() && !is_error_code && !is_next {
+        ret = func.ret.as_ref()
+            .map(|ty| (go_type(ty), format!("{}(C.{}({}))", go_type(ty),
+                func.name, args.join(", "))));
+    }
+
+    let name = match (&receiver, class_name) {
+        (_, _) if is_next => "Next".to_string(),
+        (Some(_), _) | (None, None) =>
+            camel_case(func.short_name(package_name)),
+        (None, Some(class_name)) => format!("{}{}", camel_case(class_name),
+            camel_case(func.short_name(package_name))),
+    };
+    let name = match receiver {
+        None if type_names.contains(&name) => format!("{}Func", name),
+        _ => name,
+    };
+    let results = match (&ret, is_error_code, is_next) {
+        (Some((ty, _)), true, _) => format!(" (result {}, err error)", ty),
+        (None, true, _) => " error".to_string(),
+        (Some((ty, _)), false, true) => format!(" (item {}, ok bool)", ty),
+        (Some((ty, _)), false, false) => format!(" {}", ty),
+        (None, false, _) => String::new(),
+    };
+    let receiver = receiver
+        .map(|receiver| format!("(h *{}) ", receiver))
+        .unwrap_or_default();
+    body.push(format!("// {} calls `{}`.", name, func.name));
+    body.push(format!("func {}{}({}){} {{", receiver, name, params.join(", "),
+        results));
+    for local in locals {
+        body.push(format!("\t{}", local));
+    }
+    let call = format!("C.{}({})", func.name, args.join(", "));
+    match (ret, is_error_code, is_next) {
+        (Some((_, ret_expr)), true, _) => {
+            body.push(format!("\tif code := {}; code != 0 {{", call));
+            body.push("\t\terr = &Error{Code: uint32(code)}".to_string());
+            body.push("\t\treturn".to_string());
+            body.push("\t}".to_string());
+            body.push(format!("\treturn {}, nil", ret_expr));
+        }
+        (None, true, _) => {
+            body.push(format!("\tif code := {}; code != 0 {{", call));
+            body.push("\t\treturn &Error{Code: uint32(code)}".to_string());
+            body.push("\t}".to_string());
+            body.push("\treturn nil".to_string());
+        }
+        (Some((_, ret_expr)), false, true) => {
+            body.push(format!("\tif !{} {{", call));
+            body.push("\t\treturn".to_string());
+            body.push("\t}".to_string());
+            body.push(format!("\treturn {}, true", ret_expr));
+        }
+        (Some((_, ret_expr)), false, false) if func.ret.is_some() =>
+            body.push(format!("\treturn {}", ret_expr)),
+        (Some((_, ret_expr)), false, false) => {
+            body.push(format!("\t{}", call));
+            body.push(format!("\treturn {}", ret_expr));
+        }
+        (None, false, _) => body.push(format!("\t{}", call)),
+    }
+    body.push("}".to_string());
+}
+
+/// The Go type of a C type, as seen by Go callers.
+fn go_type(ty: &CType) -> String {
+    match ty {
+        CType::Primitive(ty) => match ty.as_str() {
+            "usize" => "uintptr",
+            "isize" => "int",
+            "u8" => "uint8",
+            "u16" => "uint16",
+            "u32" => "uint32",
+            "u64" => "uint64",
+            "i8" => "int8",
+            "i16" => "int16",
+            "i32" => "int32",
+            "i64" => "int64",
+            "f32" => "float32",
+            "f64" => "float64",
+            "char" => "rune",
+            ty => ty,
+        }.to_string(),
+        CType::Handle(name) => format!("*{}", camel_case(name)),
+        CType::Enum(name) => name.clone(),
+        // cgo types cannot be named outside the package
+        CType::Pointer { .. } | CType::Void => "unsafe.Pointer".to_string(),
+    }
+}
+
+/// The cgo type of a C type.
+fn cgo_type(ty: &CType) -> String {
+    match ty {
+        CType::Primitive(ty) => match ty.as_str() {
+            "f32" => "C.float".to_string(),
+            "f64" => "C.double".to_string(),
+            ty => format!("C.{}", primitive_name(ty)),
+        },
+        CType::Handle(name) => format!("*C.{}", name),
+        CType::Enum(name) => format!("C.{}", name),
+        CType::Pointer { ty, .. } => format!("*{}", cgo_type(ty)),
+        CType::Void => "unsafe.Pointer".to_string(),
+    }
+}
+
+/// The name as a Go identifier.
+fn identifier(name: &str) -> String {
+    if KEYWORDS.contains(&name) {
+        format!("{}_", name)
+    } else {
+        name.to_string()
+    }
+}
@@ -0,0 +1,468 @@
+//! Writes Java bindings that call every extern function through the Foreign
+//! Function & Memory API, and wrap each handle type in an `AutoCloseable`
+//! class that frees the handle.
+//!
+//! Layouts are derived from the types in the generated Rust signatures, as
+//! written by `Type::to_c_str`, so they match the header.
+
+use super::{
+    camel_case, CType, ExternFunction, FunctionKind, Interface, Ownership,
+    Param, ParamRole,
+};
+use crate::compiler::SerializationCompiler;
+use color_eyre::eyre::Result;
+use std::{fs, path::Path};
+
+/// Names that cannot be used as Java identifiers, and get a trailing `_`.
+const KEYWORDS: &[&str] = &[
+    "abstract", "assert", "boolean", "break", "byte", "case", "catch", "char",
+    "class", "const", "continue", "default", "do", "double", "else", "enum",
+    "extends", "false", "final", "finally", "float", "for", "goto", "if",
+    "implements", "import", "instanceof", "int", "interface", "long",
+    "native", "new", "null", "package", "private", "protected", "public",
+    "return", "short", "static", "strictfp", "super", "switch",
+    "synchronized", "this", "throw", "throws", "transient", "true", "try",
+    "void", "volatile", "while",
+];
+
+/// The source that loads the library and defines the error type, which the
+/// generated classes use. `{lib_property}` and `{lib_name}` are replaced.
+const PRELUDE: &str = r#"private static final Linker LINKER = Linker.nativeLinker();
+private static final SymbolLookup LOOKUP = lookup();
+
+private static SymbolLookup lookup() {
+    String path = System.getProperty("{lib_property}");
+    if (path != null) {
+        return SymbolLookup.libraryLookup(Path.of(path), Arena.global());
+    }
+    return SymbolLookup.libraryLookup(System.mapLibraryName("{lib_name}"),
+        Arena.global());
+}
+
+private static MethodHandle downcall(String name, FunctionDescriptor descriptor) {
+    MemorySegment symbol = LOOKUP.find(name)
+        .orElseThrow(() -> new UnsatisfiedLinkError(name));
+    return LINKER.downcallHandle(symbol, descriptor);
+}
+
+private static RuntimeException rethrow(Throwable t) {
+    if (t instanceof RuntimeException e) {
+        return e;
+    }
+    if (t instanceof Error e) {
+        throw e;
+    }
+    return new RuntimeException(t);
+}
+
+/** A non-zero error code returned by a wrapped function. */
+public static final class NativeException extends RuntimeException {
+    private final int code;
+
+    public NativeException(int code) {
+        super("error code " + code);
+        this.code = code;
+    }
+
+    public int code() {
+        return code;
+    }
+}
+"#;
+
+/// The source of the members shared by every handle type. `{name}` is
+/// replaced by the class name.
+const HANDLE: &str = r#"private MemorySegment ptr;
+private final MethodHandle free;
+
+/** Wraps a handle. A handle with no free function is borrowed. */
+{name}(MemorySegment ptr, MethodHandle free) {
+    this.ptr = ptr;
+    this.free = free;
+}
+
+/** The C handle, which is still owned by this object. */
+public MemorySegment cPtr() {
+    return ptr;
+}
+
+/** Gives up ownership of the C handle. */
+MemorySegment takePtr() {
+    MemorySegment taken = ptr;
+    ptr = MemorySegment.NULL;
+    return taken;
+}
+
+@Override
+public void close() {
+    MemorySegment taken = takePtr();
+    if (free != null && !taken.equals(MemorySegment.NULL)) {
+        try {
+            free.invokeExact(taken);
+        } catch (Throwable t) {
+            throw rethrow(t);
+        }
+    }
+}"#;
+
+/// A line of generated source and its indentation level.
+struct Lines(Vec<(usize, String)>);
+
+impl Lines {
+    fn push(&mut self, indent: usize, line: impl Into<String>) {
+        self.0.push((indent, line.into()));
+    }
+}
+
+/// Writes `java/<package_name>/<PackageName>.java` to the package folder. The
+/// bindings load `<package_name>_c` from the library path, or from the path
+/// in the `<package_name>.library` system property.
+pub fn gen_bindings(
+    package_name: &str,
+    package_folder: &Path,
+    interface: &Interface,
+) -> Result<()> {
+    let outer = camel_case(package_name);
+    let mut lines = Lines(vec![]);
+    lines.push(0, format!("package {};", package_name));
+    lines.push(0, "");
+    lines.push(0, "import java.lang.foreign.Arena;");
+    lines.push(0, "import java.lang.foreign.FunctionDescriptor;");
+    lines.push(0, "import java.lang.foreign.Linker;");
+    lines.push(0, "import java.lang.foreign.MemorySegment;");
+    lines.push(0, "import java.lang.foreign.SymbolLookup;");
+    lines.push(0, "import java.lang.invoke.MethodHandle;");
+    lines.push(0, "import java.nio.file.Path;");
+    lines.push(0, "import java.util.Optional;");
+    lines.push(0, "");
+    lines.push(0, "import static java.lang.foreign.ValueLayout.*;");
+    lines.push(0, "");
+    lines.push(0, format!("/** Bindings for lib{}_c. */", package_name));
+    lines.push(0, format!("public final class {} {{", outer));
+    lines.push(1, format!("private {}() {{}}", outer));
+    lines.push(0, "");
+    let prelude = PRELUDE
+        .replace("{lib_property}", &format!("{}.library", package_name))
+        .replace("{lib_name}", &format!("{}_c", package_name));
+    for line in prelude.lines() {
+        lines.push(1, line);
+    }
+    lines.push(0, "");
+
+    for func in &interface.functions {
+        let args = func.params.iter()
+            .map(|param| layout(&param.c_str))
+            .collect::<Vec<_>>()
+            .join(", ");
+        let descriptor = match &func.ret {
+            Some(CType::Primitive(ret)) => format!("FunctionDescriptor.of({}{}{})",
+                layout(ret), if args.is_empty() { "" } else { ", " }, args),
+            _ => format!("FunctionDescriptor.ofVoid({})", args),
+        };
+        lines.push(1, format!("private static final MethodHandle {} =",
+            func.name));
+        lines.push(2, format!("downcall(\"{}\", {});", func.name, descriptor));
+    }
+
+    for e in &interface.enums {
+        lines.push(0, "");
+        lines.push(1, format!("public enum {} {{", e.name));
+        lines.push(2, format!("{};", e.variants.join(", ")));
+        lines.push(1, "}");
+    }
+
+    // Handle types
+    let mut class_names = interface.handle_names();
+    for func in &interface.functions {
+        if let FunctionKind::Wrapper { struct_name: Some(name), .. } = &func.kind {
+            if !class_names.contains(name) {
+                class_names.push(name.clone());
+            }
+        }
+    }
+    for name in &class_names {
+        let class_name = camel_case(name);
+        lines.push(0, "");
+        lines.push(1, format!("/** Owns a {{@code {}}} handle. */", name));
+        lines.push(1, format!("public static final class {} implements \
+            AutoCloseable {{", class_name));
+        for line in HANDLE.replace("{name}", &class_name).lines() {
+            lines.push(2, line);
+        }
+        for func in &interface.functions {
+            let is_member = match &func.kind {
+                FunctionKind::Wrapper { struct_name, .. } =>
+                    struct_name.as_ref() == Some(name),
+                FunctionKind::IterNext(iter_name) => iter_name == name,
+                _ => false,
+            };
+            if is_member {
+                lines.push(0, "");
+                add_method(&mut lines, 2, func, package_name);
+            }
+        }
+        lines.push(1, "}");
+    }
+
+    for func in &interface.functions {
+        if matches!(func.kind, FunctionKind::Wrapper { struct_name: None, .. }
+                | FunctionKind::DebugLiveHandles) {
+            lines.push(0, "");
+            add_method(&mut lines, 1, func, package_name);
+        }
+    }
+    lines.push(0, "}");
+
+    let java_folder = package_folder.join("java").join(package_name);
+    fs::create_dir_all(&java_folder)?;
+    let mut compiler = SerializationCompiler::new();
+    for (indent, line) in lines.0 {
+        if line.is_empty() {
+            compiler.add_newline()?;
+        } else {
+            compiler.add_line(&format!("{}{}", "    ".repeat(indent), line))?;
+        }
+    }
+    compiler.flush(&java_folder.join(format!("{}.java", outer)))?;
+    Ok(())
+}
+
+/// Adds the Java method that calls an extern function.
+fn add_method(
+    lines: &mut Lines,
+    indent: usize,
+    func: &ExternFunction,
+    package_name: &str,
+) {
+    let is_next = matches!(func.kind, FunctionKind::IterNext(_));
+    let is_error_code = matches!(func.kind,
+        FunctionKind::Wrapper { error_code: true, .. });
+    let mut is_static = true;
+    let mut params = vec![];
+    let mut locals = vec![];
+    let mut args = vec![];
+    let mut ret: Option<(String, String)> = None;
+    let mut iter = func.params.iter().peekable();
+    while let Some(param) = iter.next() {
+        let name = identifier(&param.name);
+        let len_param = iter.peek().copied().filter(|next|
+            matches!(next.role, ParamRole::Len | ParamRole::ReturnLen));
+        match (param.role, &param.ty) {
+            (ParamRole::SelfHandle(ownership), _) => {
+                is_static = false;
+                args.push(if ownership == Ownership::Transfer {
+                    "takePtr()".to_string()
+                } else {
+                    "ptr".to_string()
+                });
+            }
+            (ParamRole::Arg(ownership), CType::Handle(ty)) => {
+                params.push(format!("{} {}", camel_case(ty), name));
+                args.push(if ownership == Ownership::Transfer {
+                    format!("{}.takePtr()", name)
+                } else {
+                    format!("{}.ptr", name)
+                });
+            }
+            (ParamRole::Arg(_), CType::Pointer { ty, .. })
+                    if len_param.is_some() && matches!(**ty, CType::Primitive(_)) => {
+                iter.next();
+                let elem = layout(pointee(&param.c_str));
+                params.push(format!("{}[] {}", carrier(elem), name));
+                locals.push(format!("MemorySegment {}Segment = \
+                    arena.allocateFrom({}, {});", param.name, elem, name));
+                args.push(format!("{}Segment", param.name));
+                args.push(format!("(long) {}.length", name));
+            }
+            (ParamRole::Arg(_), CType::Enum(ty)) => {
+                params.push(format!("{} {}", ty, name));
+                args.push(format!("(long) {}.ordinal()", name));
+            }
+            (ParamRole::Arg(_) | ParamRole::Len, _) => {
+                params.push(format!("{} {}", carrier(layout(&param.c_str)),
+                    name));
+                args.push(name);
+            }
+            (ParamRole::Return(_), ty) => {
+                let ret_layout = layout(pointee(&param.c_str));
+                locals.push(format!("MemorySegment ret = arena.allocate({});",
+                    ret_layout));
+                args.push("ret".to_string());
+                let value = format!("ret.get({}, 0)", ret_layout);
+                ret = Some(match ty {
+                    CType::Pointer { ty, .. } => match &**ty {
+                        CType::Handle(ty) => (
+                            camel_case(ty),
+                            format!("new {}({}, {})", camel_case(ty), value,
+                                param.free_with.as_deref().unwrap_or("null")),
+                        ),
+                        CType::Enum(ty) => (
+                            ty.clone(),
+                            format!("{}.values()[(int) {}]", ty, value),
+                        ),
+                        CType::Pointer { .. } if len_param.is_some() => {
+                            let len_param = iter.next().unwrap();
+                            let len_layout = layout(pointee(&len_param.c_str));
+                            locals.push(format!("MemorySegment retLen = \
+                                arena.allocate({});", len_layout));
+                            args.push("retLen".to_string());
+                            buffer_ret(param, &value,
+                                &format!("retLen.get({}, 0)", len_layout))
+                        }
+                        _ => (carrier(ret_layout).to_string(), value),
+                    },
+                    _ => unreachable!(),
+                });
+            }
+            (ParamRole::ReturnLen, _) => unreachable!(),
+        }
+    }
+    let name = if is_next {
+        "next".to_string()
+    } else {
+        identifier(&lower_camel_case(func.short_name(package_name)))
+    };
+    let ret_ty = match (&ret, is_next) {
+        (Some((ty, _)), true) => format!("Optional<{}>", boxed(ty)),
+        (Some((ty, _)), false) => ty.clone(),
+        (None, _) if !is_error_code && func.ret.is_some() =>
+            carrier(layout(&param_ret(func))).to_string(),
+        (None, _) => "void".to_string(),
+    };
+    let modifiers = if is_static { "public static" } else { "public" };
+    lines.push(indent, format!("/** Calls {{@code {}}}. */", func.name));
+    lines.push(indent, format!("{} {} {}({}) {{", modifiers, ret_ty, name,
+        params.join(", ")));
+    let body_indent = indent + 2;
+    if locals.is_empty() {
+        lines.push(indent + 1, "try {");
+    } else {
+        lines.push(indent + 1, "try (Arena arena = Arena.ofConfined()) {");
+        for local in &locals {
+            lines.push(body_indent, local.clone());
+        }
+    }
+    let call = format!("{}.invokeExact({})", func.name, args.join(", "));
+    match func.ret.as_ref().map(|_| param_ret(func)) {
+        Some(c_str) if is_error_code => {
+            lines.push(body_indent, format!("{} code = ({}) {};",
+                carrier(layout(&c_str)), carrier(layout(&c_str)), call));
+            lines.push(body_indent, "if (code != 0) {");
+            lines.push(body_indent + 1, "throw new NativeException(code);");
+            lines.push(body_indent, "}");
+        }
+        Some(_) if is_next => {
+            lines.push(body_indent, format!("if (!(boolean) {}) {{", call));
+            lines.push(body_indent + 1, "return Optional.empty();");
+            lines.push(body_indent, "}");
+        }
+        Some(c_str) => lines.push(body_indent, format!("return ({}) {};",
+            carrier(layout(&c_str)), call)),
+        None => lines.push(body_indent, format!("{};", call)),
+    }
+    if let Some((_, ret_expr)) = ret {
+        if is_next {
+            lines.push(body_indent, format!("return Optional.of({});",
+                ret_expr));
+        } else {
+            lines.push(body_indent, format!("return {};", ret_expr));
+        }
+    }
+    lines.push(indent + 1, "} catch (Throwable t) {");
+    lines.push(body_indent, "throw rethrow(t);");
+    lines.push(indent + 1, "}");
+    lines.push(indent, "}");
+}
+
+/// The Java type and expression of a returned buffer, copying primitives
+/// into an array.
+fn buffer_ret(param: &Param, value: &str, len: &str) -> (String, String) {
+    let elem_c_str = pointee(pointee(&param.c_str));
+    let elem = layout(elem_c_str);
+    let ptr = format!("{}.reinterpret({} * {}.byteSize())", value, len, elem);
+    if elem == "ADDRESS" {
+        ("MemorySegment".to_string(), ptr)
+    } else {
+        (format!("{}[]", carrier(elem)), format!("{}.toArray({})", ptr, elem))
+    }
+}
+
+/// The Rust type of the value returned by an extern function.
+fn param_ret(func: &ExternFunction) -> String {
+    match &func.ret {
+        Some(CType::Primitive(ty)) => ty.clone(),
+        _ => unreachable!(),
+    }
+}
+
+/// The type behind a pointer type written by `Type::to_c_str`.
+fn pointee(c_str: &str) -> &str {
+    c_str.strip_prefix("*mut ")
+        .or_else(|| c_str.strip_prefix("*const "))
+        .unwrap_or(c_str)
+}
+
+/// The `ValueLayout` of a type written by `Type::to_c_str`.
+fn layout(c_str: &str) -> &'static str {
+    if c_str.starts_with('*') {
+        return "ADDRESS";
+    }
+    match c_str {
+        "usize" | "isize" | "u64" | "i64" => "JAVA_LONG",
+        "u32" | "i32" | "char" => "JAVA_INT",
+        "u16" | "i16" => "JAVA_SHORT",
+        "u8" | "i8" => "JAVA_BYTE",
+        "bool" => "JAVA_BOOLEAN",
+        "f32" => "JAVA_FLOAT",
+        "f64" => "JAVA_DOUBLE",
+        _ => "ADDRESS",
+    }
+}
+
+/// The Java carrier type of a `ValueLayout`.
+fn carrier(layout: &str) -> &'static str {
+    match layout {
+        "JAVA_LONG" => "long",
+        "JAVA_INT" => "int",
+        "JAVA_SHORT" => "short",
+        "JAVA_BYTE" => "byte",
+        "JAVA_BOOLEAN" => "boolean",
+        "JAVA_FLOAT" => "float",
+        "JAVA_DOUBLE" => "double",
+        _ => "MemorySegment",
+    }
+}
+
+/// The boxed type of a Java type, for use as a type argument.
+fn boxed(ty: &str) -> &str {
+    match ty {
+        "long" => "Long",
+        "int" => "Integer",
+        "short" => "Short",
+        "byte" => "Byte",
+        "boolean" => "Boolean",
+        "float" => "Float",
+        "double" => "Double",
+        ty => ty,
+    }
+}
+
+/// Converts a snake case name to lower camel case, e.g., `try_get` to
+/// `tryGet`.
+fn lower_camel_case(name: &str) -> String {
+    let name = camel_case(name);
+    let mut chars = name.chars();
+    match chars.next() {
+        Some(first) => first.to_lowercase().chain(chars).collect(),
+        None => name,
+    }
+}
+
+/// The name as a Java identifier.
+fn identifier(name: &str) -> String {
+    if KEYWORDS.contains(&name) {
+        format!("{}_", name)
+    } else {
+        name.to_string()
+    }
+}
@@ -0,0 +1,304 @@
+//! Writes a LuaJIT module that declares every extern function in an
+//! `ffi.cdef` block, and wraps each handle type in a metatable whose handles
+//! are freed by an `ffi.gc` finalizer.
+
+use super::{
+    c, CType, ConstantValue, ExternFunction, FunctionKind, Interface,
+    Ownership, ParamRole,
+};
+use crate::compiler::SerializationCompiler;
+use color_eyre::eyre::Result;
+use std::path::Path;
+
+/// Names that cannot be used as Lua identifiers, and get a trailing `_`.
+const KEYWORDS: &[&str] = &[
+    "and", "break", "do", "else", "elseif", "end", "false", "for", "function",
+    "goto", "if", "in", "local", "nil", "not", "or", "repeat", "return",
+    "then", "true", "until", "while",
+];
+
+/// The source that defines the error type and the handle helpers, which the
+/// generated classes use.
+const PRELUDE: &str = r#"--- A non-zero error code returned by a wrapped function.
+local Error = {}
+Error.__index = Error
+Error.__tostring = function(err)
+  return "error code " .. err.code
+end
+M.Error = Error
+
+local function check(code)
+  if code ~= 0 then
+    error(setmetatable({ code = code }, Error))
+  end
+end
+
+--- Wraps a handle, freeing it with `free` when garbage collected. A handle
+--- with no `free` function is borrowed and never freed. A handle that
+--- borrows from the handles in `borrows` keeps them alive.
+local function wrap(class, ptr, free, borrows)
+  if free ~= nil then
+    ptr = ffi.gc(ptr, free)
+  end
+  return setmetatable({ ptr = ptr, free = free, borrows = borrows }, class)
+end
+
+--- Gives up ownership of the C handle.
+local function take(handle)
+  local ptr = handle.ptr
+  handle.ptr = nil
+  if handle.free ~= nil then
+    ffi.gc(ptr, nil)
+  end
+  return ptr
+end
+
+--- Frees the handle now rather than when garbage collected.
+local function close(handle)
+  if handle.ptr ~= nil then
+    local free = handle.free
+    local ptr = take(handle)
+    if free ~= nil then
+      free(ptr)
+    end
+  end
+end
+
+--- Copies `len` elements starting at `ptr` into a table.
+local function to_table(ptr, len)
+  local items = {}
+  for i = 0, tonumber(len) - 1 do
+    items[i + 1] = ptr[i]
+  end
+  return items
+end"#;
+
+/// Writes `<package_name>.lua` to the package folder. The module loads
+/// `<package_name>_c` from the library path, or from the path in the
+/// `<PACKAGE_NAME>_C_LIB` environment variable.
+pub fn gen_module(
+    package_name: &str,
+    package_folder: &Path,
+    interface: &Interface,
+) -> Result<()> {
+    let mut compiler = SerializationCompiler::new();
+    compiler.add_line(&format!("--- LuaJIT FFI bindings for lib{}_c.",
+        package_name))?;
+    compiler.add_line("local ffi = require(\"ffi\")")?;
+    compiler.add_newline()?;
+
+    compiler.add_line("ffi.cdef[[")?;
+    for e in &interface.enums {
+        compiler.add_line(&format!("typedef uintptr_t {};", e.name))?;
+    }
+    for name in interface.handle_names() {
+        compiler.add_line(&format!("typedef struct {0} {0};", name))?;
+    }
+    for constant in &interface.constants {
+        match &constant.value {
+            ConstantValue::Literal(_) => continue,
+            ConstantValue::Static => compiler.add_line(&format!(
+                "extern const {};", c::declaration(&constant.ty, &constant.name)))?,
+            ConstantValue::String(_, len) => compiler.add_line(&format!(
+                "extern const uint8_t {}[{}];", constant.name, len))?,
+        }
+    }
+    for func in &interface.functions {
+        compiler.add_line(&format!("{};", c::prototype(func)))?;
+    }
+    compiler.add_line("]]")?;
+    compiler.add_newline()?;
+
+    compiler.add_line(&format!("local lib = ffi.load(os.getenv(\"{}_C_LIB\") \
+        or \"{}_c\")", package_name.to_uppercase(), package_name))?;
+    compiler.add_newline()?;
+    compiler.add_line("local M = { lib = lib }")?;
+    compiler.add_newline()?;
+    for line in PRELUDE.lines() {
+        compiler.add_line(line)?;
+    }
+
+    for constant in &interface.constants {
+        if let ConstantValue::Literal(value) = &constant.value {
+            compiler.add_newline()?;
+            compiler.add_line(&format!("M.{} = {}", constant.name,
+                c::literal(value)))?;
+        }
+    }
+
+    for e in &interface.enums {
+        compiler.add_newline()?;
+        compiler.add_line(&format!("M.{} = {{", e.name))?;
+        for (i, variant) in e.variants.iter().enumerate() {
+            compiler.add_line(&format!("  {} = {},", variant, i))?;
+        }
+        compiler.add_line("}")?;
+    }
+
+    // Classes
+    let mut class_names = interface.handle_names();
+    for func in &interface.functions {
+        if let FunctionKind::Wrapper { struct_name: Some(name), .. } = &func.kind {
+            if !class_names.contains(name) {
+                class_names.push(name.clone());
+            }
+        }
+    }
+    for name in &class_names {
+        compiler.add_newline()?;
+        compiler.add_line(&format!("--- A handle to a `{}`.", name))?;
+        compiler.add_line(&format!("local {} = {{ close = close }}", name))?;
+        compiler.add_line(&format!("{0}.__index = {0}", name))?;
+        compiler.add_line(&format!("M.{0} = {0}", name))?;
+    }
+    for name in &class_names {
+        for func in &interface.functions {
+            let is_member = match &func.kind {
+                FunctionKind::Wrapper { struct_name, .. } =>
+                    struct_name.as_ref() == Some(name),
+                FunctionKind::IterNext(iter_name) => iter_name == name,
+                _ => false,
+            };
+            if is_member {
+                compiler.add_newline()?;
+                add_function(&mut compiler, func, package_name, name)?;
+            }
+        }
+    }
+
+    for func in &interface.functions {
+        if matches!(func.kind, FunctionKind::Wrapper { struct_name: None, .. }
+                | FunctionKind::DebugLiveHandles) {
+            compiler.add_newline()?;
+            add_function(&mut compiler, func, package_name, "M")?;
+        }
+    }
+    compiler.add_newline()?;
+    compiler.add_line("return M")?;
+    compiler.flush(&package_folder.join(format!("{}.lua", package_name)))?;
+    Ok(())
+}
+
+/// Writes the Lua function or method that calls an extern function.
+fn add_function(
+    compiler: &mut SerializationCompiler,
+    func: &ExternFunction,
+    package_name: &str,
+    class_name: &str,
+) -> Result<()> {
+    let is_method = func.params.first()
+        .is_some_and(|param| matches!(param.role, ParamRole::SelfHandle(_)));
+    let is_next = matches!(func.kind, FunctionKind::IterNext(_));
+    let name = if is_next {
+        "next".to_string()
+    } else {
+        identifier(func.short_name(package_name))
+    };
+    let mut params = vec![];
+    let mut locals = vec![];
+    let mut args = vec![];
+    let mut ret_expr = None;
+    let mut iter = func.params.iter().peekable();
+    while let Some(param) = iter.next() {
+        let param_name = identifier(&param.name);
+        let has_len = iter.peek().is_some_and(|next|
+            matches!(next.role, ParamRole::Len | ParamRole::ReturnLen));
+        match (param.role, &param.ty) {
+            (ParamRole::SelfHandle(Ownership::Transfer), _) =>
+                args.push("take(self)".to_string()),
+            (ParamRole::SelfHandle(_), _) => args.push("self.ptr".to_string()),
+            (ParamRole::Arg(Ownership::Transfer), CType::Handle(_)) => {
+                args.push(format!("take({})", param_name));
+                params.push(param_name);
+            }
+            (ParamRole::Arg(_), CType::Handle(_)) => {
+                args.push(format!("{}.ptr", param_name));
+                params.push(param_name);
+            }
+            (ParamRole::Arg(_), CType::Pointer { ty, .. }) if has_len => {
+                iter.next();
+                locals.push(format!("local {0}_buf = ffi.new(\"{1}[?]\", \
+                    #{0}, {0})", param_name, c::type_name(ty)));
+                args.push(format!("{}_buf", param_name));
+                args.push(format!("#{}", param_name));
+                params.push(param_name);
+            }
+            (ParamRole::Arg(_) | ParamRole::Len, _) => {
+                args.push(param_name.clone());
+                params.push(param_name);
+            }
+            (ParamRole::Return(_), CType::Pointer { ty, .. }) => {
+                locals.push(format!("local ret = ffi.new(\"{}[1]\")",
+                    c::type_name(ty)));
+                args.push("ret".to_string());
+                ret_expr = Some(match &**ty {
+                    CType::Handle(class_name) => {
+                        let borrows = param.borrows.iter()
+                            .map(|name| match func.params.iter()
+                                    .find(|p| &p.name == name) {
+                                Some(p) if matches!(p.role,
+                                        ParamRole::SelfHandle(_)) =>
+                                    "self".to_string(),
+                                _ => identifier(name),
+                            })
+                            .collect::<Vec<_>>();
+                        format!("wrap({}, ret[0], {}{})", class_name,
+                            param.free_with.as_ref()
+                                .map(|free| format!("lib.{}", free))
+                                .unwrap_or_else(|| "nil".to_string()),
+                            if borrows.is_empty() {
+                                String::new()
+                            } else {
+                                format!(", {{ {} }}", borrows.join(", "))
+                            })
+                    }
+                    CType::Pointer { .. } if has_len => {
+                        iter.next();
+                        locals.push("local ret_len = ffi.new(\"uintptr_t[1]\")"
+                            .to_string());
+                        args.push("ret_len".to_string());
+                        "to_table(ret[0], ret_len[0])".to_string()
+                    }
+                    _ => "ret[0]".to_string(),
+                });
+            }
+            (ParamRole::Return(_) | ParamRole::ReturnLen, _) => unreachable!(),
+        }
+    }
+
+    let separator = if is_method { ":" } else { "." };
+    compiler.add_line(&format!("--- Calls `{}`.", func.name))?;
+    compiler.add_line(&format!("function {}{}{}({})", class_name, separator,
+        name, params.join(", ")))?;
+    for local in locals {
+        compiler.add_line(&format!("  {}", local))?;
+    }
+    let call = format!("lib.{}({})", func.name, args.join(", "));
+    let is_error_code = matches!(func.kind,
+        FunctionKind::Wrapper { error_code: true, .. });
+    if is_error_code {
+        compiler.add_line(&format!("  check({})", call))?;
+    } else if is_next {
+        compiler.add_line(&format!("  if not {} then", call))?;
+        compiler.add_line("    return nil")?;
+        compiler.add_line("  end")?;
+    } else if func.ret.is_some() && ret_expr.is_none() {
+        ret_expr = Some(call);
+    } else {
+        compiler.add_line(&format!("  {}", call))?;
+    }
+    if let Some(ret_expr) = ret_expr {
+        compiler.add_line(&format!("  return {}", ret_expr))?;
+    }
+    compiler.add_line("end")?;
+    Ok(())
+}
+
+/// The name as a Lua identifier.
+fn identifier(name: &str) -> String {
+    if KEYWORDS.contains(&name) {
+        format!("{}_", name)
+    } else {
+        name.to_string()
+    }
+}
@@ -0,0 +1,263 @@
+//! A language-neutral description of the generated extern C interface, from
+//! which headers and bindings for other languages are written.
+
+pub mod c;
+pub mod cpp;
+pub mod csharp;
+pub mod go;
+pub mod java;
+pub mod lua;
+pub mod python;
+
+use super::types::{Ownership, Type};
+
+/// Every function, constant and enum exposed by the generated crate.
+#[derive(Clone, Debug, Default)]
+pub struct Interface {
+    pub functions: Vec<ExternFunction>,
+    pub constants: Vec<Constant>,
+    pub enums: Vec<Enum>,
+}
+
+/// A generated `#[no_mangle] pub extern "C" fn`.
+#[derive(Clone, Debug)]
+pub struct ExternFunction {
+    pub name: String,
+    pub params: Vec<Param>,
+    /// The return type. Functions without one return nothing.
+    pub ret: Option<CType>,
+    /// The lines of the doc comment on the function.
+    pub docs: Vec<String>,
+    pub kind: FunctionKind,
+}
+
+/// A parameter of a generated extern function.
+#[derive(Clone, Debug)]
+pub struct Param {
+    pub name: String,
+    pub ty: CType,
+    /// The type of the parameter in the generated Rust function, as written
+    /// by `Type::to_c_str`.
+    pub c_str: String,
+    pub role: ParamRole,
+    /// For an out-parameter that receives handles owned by the caller, the
+    /// function that frees them.
+    pub free_with: Option<String>,
+    /// For an out-parameter that receives an iterator or a reference, the
+    /// handle parameters it may borrow from, which must outlive it.
+    pub borrows: Vec<String>,
+}
+
+/// What a parameter of a generated extern function is for.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ParamRole {
+    /// The handle the method is called on, and how it is passed.
+    SelfHandle(Ownership),
+    /// An argument of the wrapped function, and how it is passed.
+    Arg(Ownership),
+    /// The length of the preceding buffer argument.
+    Len,
+    /// The out-parameter that receives the return value, and how it is
+    /// returned.
+    Return(Ownership),
+    /// The out-parameter that receives the length of a returned buffer.
+    ReturnLen,
+}
+
+/// What a generated extern function does.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum FunctionKind {
+    /// Wraps `method`, a standalone function or a function on the handle
+    /// type with the typedef name `struct_name`. Returns an error code if
+    /// `error_code` is set.
+    Wrapper {
+        struct_name: Option<String>,
+        method: String,
+        error_code: bool,
+    },
+    /// Frees a boxed handle to the struct.
+    Free(String),
+    /// Increments the reference count of a shared handle to the struct.
+    Retain(String),
+    /// Decrements the reference count of a shared handle to the struct.
+    Release(String),
+    /// Advances an iterator handle with the given typedef name.
+    IterNext(String),
+    /// Frees an iterator handle with the given typedef name.
+    IterFree(String),
+    /// Dumps the debug handle registry.
+    DebugLiveHandles,
+}
+
+/// A constant exposed to C.
+#[derive(Clone, Debug)]
+pub struct Constant {
+    pub name: String,
+    pub ty: CType,
+    pub value: ConstantValue,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ConstantValue {
+    /// A Rust literal, copied into the bindings.
+    Literal(String),
+    /// A `#[no_mangle]` static of the constant's type.
+    Static,
+    /// A `#[no_mangle]` static nul-terminated string, with its value and its
+    /// length in bytes including the nul terminator.
+    String(String, usize),
+}
+
+/// A Rust enum passed to C by the index of its variant.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Enum {
+    pub name: String,
+    pub variants: Vec<String>,
+}
+
+/// The type of a parameter, return value or constant, as C sees it.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum CType {
+    /// A Rust primitive, by its Rust name.
+    Primitive(String),
+    /// An opaque pointer to a Rust value, by its typedef name.
+    Handle(String),
+    /// An enum, passed as the `usize` index of its variant.
+    Enum(String),
+    /// A pointer to a value of the inner type.
+    Pointer { ty: Box<CType>, is_mut: bool },
+    /// A pointer to an unspecified type.
+    Void,
+}
+
+impl CType {
+    /// The C type of a value of the given Rust type. Iterators have no C
+    /// type of their own, since their typedef is named after the function
+    /// that returns them.
+    pub fn from_type(ty: &Type) -> CType {
+        match ty {
+            Type::Primitive(ty) => CType::Primitive(ty.clone()),
+            Type::Struct{..} | Type::Box(_) | Type::Arc(_) | Type::Rc(_) =>
+                match handle_name(ty) {
+                    Some(name) => CType::Handle(name),
+                    None => unreachable!(),
+                },
+            ty if ty.is_str() => CType::Void,
+            Type::Ref(inner_ty) | Type::RefMut(inner_ty)
+                    if inner_ty.inner_struct().is_some() =>
+                CType::from_type(inner_ty),
+            Type::Ref(inner_ty) => CType::Pointer {
+                ty: Box::new(CType::from_type(inner_ty)),
+                is_mut: false,
+            },
+            Type::RefMut(inner_ty) => CType::Pointer {
+                ty: Box::new(CType::from_type(inner_ty)),
+                is_mut: true,
+            },
+            Type::Buffer(inner_ty) => CType::Pointer {
+                ty: Box::new(CType::from_type(inner_ty)),
+                is_mut: false,
+            },
+            Type::Enum { name, .. } => CType::Enum(name.clone()),
+            Type::RawPtr { ty, is_mut } => match &**ty {
+                Type::Primitive(_) | Type::RawPtr{..} => CType::Pointer {
+                    ty: Box::new(CType::from_type(ty)),
+                    is_mut: *is_mut,
+                },
+                _ => CType::Void,
+            },
+            Type::Iterator(_) => CType::Void,
+        }
+    }
+
+    /// An out-parameter that receives a value of this type.
+    pub fn out_ptr(self) -> CType {
+        CType::Pointer { ty: Box::new(self), is_mut: true }
+    }
+}
+
+impl ExternFunction {
+    /// The name of the function in bindings that group it under a class or
+    /// module. Strips the `<struct>_` prefix from functions on a struct, and
+    /// the `<package_name>_` prefix from other functions. Iterator functions
+    /// are named `next`.
+    pub fn short_name(&self, package_name: &str) -> &str {
+        let prefix = match &self.kind {
+            FunctionKind::Wrapper { struct_name: Some(struct_name), .. } =>
+                format!("{}_", struct_name),
+            FunctionKind::IterNext(_) => return "next",
+            _ => format!("{}_", package_name),
+        };
+        match (&self.kind, self.name.strip_prefix(&prefix)) {
+            (_, Some(name)) => name,
+            (FunctionKind::Wrapper { struct_name: Some(_), method, .. }, None) =>
+                method,
+            (_, None) => &self.name,
+        }
+    }
+}
+
+impl Interface {
+    /// Records an enum, unless it has already been recorded.
+    pub fn add_enum(&mut self, name: &str, variants: &[String]) {
+        if self.enums.iter().all(|e| e.name != name) {
+            self.enums.push(Enum {
+                name: name.to_string(),
+                variants: variants.to_vec(),
+            });
+        }
+    }
+
+    /// The names of every handle typedef used in the interface, in the order
+    /// they first appear.
+    pub fn handle_names(&self) -> Vec<String> {
+        fn visit(ty: &CType, names: &mut Vec<String>) {
+            match ty {
+                CType::Handle(name) if !names.contains(name) =>
+                    names.push(name.clone()),
+                CType::Pointer { ty, .. } => visit(ty, names),
+                _ => {}
+            }
+        }
+        let mut names = vec![];
+        for func in &self.functions {
+            for param in &func.params {
+                visit(&param.ty, &mut names);
+            }
+        }
+        names
+    }
+}
+
+/// The typedef name of C handles of the given type: the name of the struct,
+/// with a `Shared` suffix for `Arc` and `Rc` handles, which are released
+/// rather than freed.
+pub fn handle_name(ty: &Type) -> Option<String> {
+    match ty.inner_struct() {
+        Some(Type::Struct { name, .. }) if ty.is_shared() =>
+            Some(format!("{}Shared", short_struct_name(name))),
+        Some(Type::Struct { name, .. }) =>
+            Some(short_struct_name(name).to_string()),
+        _ => None,
+    }
+}
+
+/// The name of a struct without its path, e.g., `Bump` for `bumpalo::Bump`,
+/// which the names C sees are derived from.
+pub fn short_struct_name(name: &str) -> &str {
+    name.rsplit("::").next().unwrap_or(name)
+}
+
+/// Converts a snake case name to upper camel case, e.g., `try_get` to
+/// `TryGet`.
+pub fn camel_case(name: &str) -> String {
+    name.split('_')
+        .map(|word| {
+            let mut chars = word.chars();
+            match chars.next() {
+                Some(first) => first.to_uppercase().chain(chars).collect(),
+                None => String::new(),
+            }
+        })
+        .collect()
+}
@@ -0,0 +1,316 @@
+//! Writes a Python module that loads the generated library with `ctypes`,
+//! declares the signature of every extern function, and wraps each handle
+//! type in a class that frees the handle when it is garbage collected.
+
+use super::{CType, ExternFunction, FunctionKind, Interface, Ownership, ParamRole};
+use crate::compiler::SerializationCompiler;
+use color_eyre::eyre::Result;
+use std::path::Path;
+
+/// Names that cannot be used as Python identifiers, and get a trailing `_`.
+const KEYWORDS: &[&str] = &[
+    "False", "None", "True", "and", "as", "assert", "async", "await", "break",
+    "class", "continue", "def", "del", "elif", "else", "except", "finally",
+    "for", "from", "global", "if", "import", "in", "is", "lambda", "nonlocal",
+    "not", "or", "pass", "raise", "return", "try", "while", "with", "yield",
+];
+
+/// The source that loads the library and defines the error type, which the
+/// generated classes use. `{lib_env}` and `{lib_name}` are replaced.
+const PRELUDE: &str = r#"import ctypes
+import enum
+import os
+import sys
+
+
+def _load():
+    path = os.environ.get("{lib_env}")
+    if path is None:
+        name = {
+            "darwin": "lib{lib_name}.dylib",
+            "win32": "{lib_name}.dll",
+        }.get(sys.platform, "lib{lib_name}.so")
+        root = os.path.dirname(os.path.abspath(__file__))
+        for profile in ("release", "debug"):
+            path = os.path.join(root, "target", profile, name)
+            if os.path.exists(path):
+                break
+    return ctypes.CDLL(path)
+
+
+_lib = _load()
+
+
+class Error(Exception):
+    """A non-zero error code returned by a wrapped function."""
+
+    def __init__(self, code):
+        super().__init__(f"error code {code}")
+        self.code = code
+
+
+class _Handle:
+    """Owns a C handle, freeing it with `free` when garbage collected. A
+    handle with no `free` function is borrowed and never freed. A handle that
+    borrows from the handles in `borrows` keeps them alive."""
+
+    def __init__(self, ptr, free=None, borrows=()):
+        self._ptr = ptr
+        self._free = free
+        self._borrows = borrows
+
+    def __del__(self):
+        if self._ptr is not None and self._free is not None:
+            self._free(self._ptr)
+        self._ptr = None
+
+    def _take(self):
+        """Gives up ownership of the C handle."""
+        ptr, self._ptr = self._ptr, None
+        return ptr
+"#;
+
+/// Writes `<package_name>.py` to the package folder. The module loads the
+/// library from `target/release` or `target/debug` next to it, or from the
+/// path in the `<PACKAGE_NAME>_C_LIB` environment variable.
+pub fn gen_module(
+    package_name: &str,
+    package_folder: &Path,
+    interface: &Interface,
+) -> Result<()> {
+    let mut compiler = SerializationCompiler::new();
+    compiler.add_line(&format!("\"\"\"ctypes bindings for lib{}_c.\"\"\"",
+        package_name))?;
+    compiler.add_newline()?;
+    let prelude = PRELUDE
+        .replace("{lib_env}", &format!("{}_C_LIB", package_name.to_uppercase()))
+        .replace("{lib_name}", &format!("{}_c", package_name));
+    for line in prelude.lines() {
+        compiler.add_line(line)?;
+    }
+    compiler.add_newline()?;
+
+    for e in &interface.enums {
+        compiler.add_newline()?;
+        compiler.add_line(&format!("class {}(enum.IntEnum):", e.name))?;
+        for (i, variant) in e.variants.iter().enumerate() {
+            compiler.add_line(&format!("    {} = {}", identifier(variant), i))?;
+        }
+        compiler.add_newline()?;
+    }
+
+    // Signatures
+    compiler.add_newline()?;
+    for func in &interface.functions {
+        compiler.add_line(&format!("_lib.{}.argtypes = [{}]", func.name,
+            func.params.iter()
+                .map(|param| ctype(&param.ty))
+                .collect::<Vec<_>>()
+                .join(", ")))?;
+        compiler.add_line(&format!("_lib.{}.restype = {}", func.name,
+            func.ret.as_ref().map(ctype).unwrap_or_else(|| "None".to_string())))?;
+    }
+    compiler.add_newline()?;
+
+    // Classes
+    let mut class_names = interface.handle_names();
+    for func in &interface.functions {
+        if let FunctionKind::Wrapper { struct_name: Some(name), .. } = &func.kind {
+            if !class_names.contains(name) {
+                class_names.push(name.clone());
+            }
+        }
+    }
+    for name in &class_names {
+        compiler.add_newline()?;
+        compiler.add_line(&format!("class {}(_Handle):", name))?;
+        compiler.add_line(&format!("    \"\"\"A handle to a `{}`.\"\"\"", name))?;
+        let is_iter = interface.functions.iter().any(|func|
+            func.kind == FunctionKind::IterNext(name.clone()));
+        if is_iter {
+            compiler.add_newline()?;
+            compiler.add_line("    def __iter__(self):")?;
+            compiler.add_line("        return self")?;
+        }
+        for func in &interface.functions {
+            let is_member = match &func.kind {
+                FunctionKind::Wrapper { struct_name, .. } =>
+                    struct_name.as_ref() == Some(name),
+                FunctionKind::IterNext(iter_name) => iter_name == name,
+                _ => false,
+            };
+            if is_member {
+                compiler.add_newline()?;
+                add_function(&mut compiler, func, package_name, "    ")?;
+            }
+        }
+        compiler.add_newline()?;
+    }
+
+    for func in &interface.functions {
+        if matches!(func.kind, FunctionKind::Wrapper { struct_name: None, .. }
+                | FunctionKind::DebugLiveHandles) {
+            compiler.add_newline()?;
+            add_function(&mut compiler, func, package_name, "")?;
+            compiler.add_newline()?;
+        }
+    }
+    compiler.flush(&package_folder.join(format!("{}.py", package_name)))?;
+    Ok(())
+}
+
+/// The tuple items of the handles that a returned handle keeps alive, with
+/// `self` for the handle the method is called on.
+fn borrowed_handles(func: &ExternFunction, borrows: &[String]) -> String {
+    borrows.iter()
+        .map(|name| match func.params.iter().find(|p| &p.name == name) {
+            Some(param) if matches!(param.role, ParamRole::SelfHandle(_)) =>
+                "self,".to_string(),
+            _ => format!("{},", identifier(name)),
+        })
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Writes the Python function or method that calls an extern function.
+fn add_function(
+    compiler: &mut SerializationCompiler,
+    func: &ExternFunction,
+    package_name: &str,
+    indent: &str,
+) -> Result<()> {
+    let is_method = func.params.first()
+        .is_some_and(|param| matches!(param.role, ParamRole::SelfHandle(_)));
+    let is_next = matches!(func.kind, FunctionKind::IterNext(_));
+    let name = if is_next {
+        "__next__".to_string()
+    } else {
+        identifier(func.short_name(package_name))
+    };
+    let mut params = vec![];
+    let mut locals = vec![];
+    let mut args = vec![];
+    let mut ret_expr = None;
+    let mut iter = func.params.iter().peekable();
+    while let Some(param) = iter.next() {
+        let param_name = identifier(&param.name);
+        let has_len = iter.peek().is_some_and(|next|
+            matches!(next.role, ParamRole::Len | ParamRole::ReturnLen));
+        match (param.role, &param.ty) {
+            (ParamRole::SelfHandle(Ownership::Transfer), _) =>
+                args.push("self._take()".to_string()),
+            (ParamRole::SelfHandle(_), _) => args.push("self._ptr".to_string()),
+            (ParamRole::Arg(Ownership::Transfer), CType::Handle(_)) => {
+                args.push(format!("{}._take()", param_name));
+                params.push(param_name);
+            }
+            (ParamRole::Arg(_), CType::Handle(_)) => {
+                args.push(format!("{}._ptr", param_name));
+                params.push(param_name);
+            }
+            (ParamRole::Arg(_), CType::Pointer { ty, .. }) if has_len => {
+                iter.next();
+                locals.push(format!("{0}_buf = ({1} * len({0}))(*{0})",
+                    param_name, ctype(ty)));
+                args.push(format!("{}_buf", param_name));
+                args.push(format!("len({})", param_name));
+                params.push(param_name);
+            }
+            (ParamRole::Arg(_) | ParamRole::Len, _) => {
+                args.push(param_name.clone());
+                params.push(param_name);
+            }
+            (ParamRole::Return(_), CType::Pointer { ty, .. }) => {
+                locals.push(format!("ret = {}()", ctype(ty)));
+                args.push("ctypes.byref(ret)".to_string());
+                ret_expr = Some(match &**ty {
+                    CType::Handle(class_name) => format!(
+                        "{}(ret.value, {}, ({}))", class_name,
+                        param.free_with.as_ref()
+                            .map(|free| format!("_lib.{}", free))
+                            .unwrap_or_else(|| "None".to_string()),
+                        borrowed_handles(func, &param.borrows)),
+                    CType::Enum(name) => format!("{}(ret.value)", name),
+                    CType::Pointer { .. } if has_len => {
+                        iter.next();
+                        locals.push("ret_len = ctypes.c_size_t()".to_string());
+                        args.push("ctypes.byref(ret_len)".to_string());
+                        "ret[:ret_len.value]".to_string()
+                    }
+                    CType::Pointer { .. } => "ret".to_string(),
+                    _ => "ret.value".to_string(),
+                });
+            }
+            (ParamRole::Return(_) | ParamRole::ReturnLen, _) => unreachable!(),
+        }
+    }
+
+    if is_method {
+        params.insert(0, "self".to_string());
+    } else if !indent.is_empty() {
+        compiler.add_line(&format!("{}@staticmethod", indent))?;
+    }
+    compiler.add_line(&format!("{}def {}({}):", indent, name,
+        params.join(", ")))?;
+    if !is_next {
+        compiler.add_line(&format!("{}    \"\"\"Calls `{}`.\"\"\"", indent,
+            func.name))?;
+    }
+    for local in locals {
+        compiler.add_line(&format!("{}    {}", indent, local))?;
+    }
+    let call = format!("_lib.{}({})", func.name, args.join(", "));
+    let is_error_code = matches!(func.kind,
+        FunctionKind::Wrapper { error_code: true, .. });
+    if is_error_code {
+        compiler.add_line(&format!("{}    code = {}", indent, call))?;
+        compiler.add_line(&format!("{}    if code != 0:", indent))?;
+        compiler.add_line(&format!("{}        raise Error(code)", indent))?;
+    } else if is_next {
+        compiler.add_line(&format!("{}    if not {}:", indent, call))?;
+        compiler.add_line(&format!("{}        raise StopIteration", indent))?;
+    } else if func.ret.is_some() && ret_expr.is_none() {
+        ret_expr = Some(call);
+    } else {
+        compiler.add_line(&format!("{}    {}", indent, call))?;
+    }
+    if let Some(ret_expr) = ret_expr {
+        compiler.add_line(&format!("{}    return {}", indent, ret_expr))?;
+    }
+    Ok(())
+}
+
+/// The `ctypes` type of a C type.
+fn ctype(ty: &CType) -> String {
+    match ty {
+        CType::Primitive(ty) => format!("ctypes.{}", match ty.as_str() {
+            "usize" => "c_size_t",
+            "isize" => "c_ssize_t",
+            "u8" => "c_uint8",
+            "u16" => "c_uint16",
+            "u32" | "char" => "c_uint32",
+            "u64" => "c_uint64",
+            "i8" => "c_int8",
+            "i16" => "c_int16",
+            "i32" => "c_int32",
+            "i64" => "c_int64",
+            "f32" => "c_float",
+            "f64" => "c_double",
+            "bool" => "c_bool",
+            _ => "c_void_p",
+        }),
+        CType::Handle(_) | CType::Void => "ctypes.c_void_p".to_string(),
+        CType::Enum(_) => "ctypes.c_size_t".to_string(),
+        CType::Pointer { ty, .. } =>
+            format!("ctypes.POINTER({})", ctype(ty)),
+    }
+}
+
+/// The name as a Python identifier.
+fn identifier(name: &str) -> String {
+    if KEYWORDS.contains(&name) {
+        format!("{}_", name)
+    } else {
+        name.to_string()
+    }
+}
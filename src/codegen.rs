@@ -1,23 +1,384 @@
 use super::{
+    bindings::{
+        camel_case, handle_name, short_struct_name, CType, Constant,
+        ConstantValue, ExternFunction, FunctionKind, Interface, Param,
+        ParamRole,
+    },
     compiler::{
         Context, FunctionArg, FunctionContext, SerializationCompiler, CArgInfo,
-        MatchContext,
+        MatchContext, LoopContext, LoopBranch,
+    },
+    manifest::{toml_key, toml_string, CargoManifestConfig, Dependency},
+    types::{
+        self, Type, SelfType, NullPolicy, Ownership, HandleLock, CrateType,
     },
-    types::{Type, SelfType},
 };
 use color_eyre::eyre::{bail, Result};
-use std::{str, path::Path};
+use std::{collections::HashMap, str, path::Path};
+
+/// The error code returned for a null pointer argument under
+/// `NullPolicy::ReturnError`. Other errors return 1.
+pub const NULL_POINTER_ERROR: u32 = 2;
+
+/// Options that apply to every function generated by a compiler.
+#[derive(Clone, Debug, Default)]
+pub struct Options {
+    pub null_policy: NullPolicy,
+    /// The lock around boxed handles to each struct, by struct name.
+    pub handle_locks: HashMap<String, HandleLock>,
+    /// Whether functions that take pointers are declared `unsafe`, with a
+    /// `# Safety` section describing what each pointer must satisfy.
+    pub unsafe_functions: bool,
+}
+
+/// The module of the generated crate that tracks handles given to C.
+const DEBUG_HANDLES_MOD: &str = "debug_handles";
+
+/// The source of the debug handle registry. Every handle crossing the FFI
+/// boundary is reported to it, but it only keeps track of them when the
+/// generated crate is built with the `debug-handles` feature.
+const DEBUG_HANDLES_SRC: &str = r#"//! Tracks every handle given to C when the `debug-handles` feature is enabled,
+//! so that use-after-free, double-free and type confusion abort with a
+//! description instead of causing undefined behavior. Without the feature
+//! every function is a no-op.
+#![allow(dead_code, unused_variables)]
+
+use std::os::raw::c_void;
+
+#[cfg(feature = "debug-handles")]
+mod registry {
+    use std::{collections::HashMap, sync::Mutex};
+
+    /// A handle owned by C.
+    pub struct Owned {
+        pub kind: &'static str,
+        pub ty: &'static str,
+        pub count: usize,
+    }
+
+    /// A reference to a Rust value lent to C.
+    pub struct Borrowed {
+        pub ty: &'static str,
+        /// The handle it was borrowed from, or 0 if it outlives every handle.
+        pub owner: usize,
+    }
+
+    #[derive(Default)]
+    pub struct Registry {
+        /// Handles owned by C, by address.
+        pub owned: HashMap<usize, Owned>,
+        /// References to Rust values lent to C, by address.
+        pub borrowed: HashMap<usize, Vec<Borrowed>>,
+        /// The types of handles that have been freed, by address.
+        pub freed: HashMap<usize, &'static str>,
+    }
+
+    static REGISTRY: Mutex<Option<Registry>> = Mutex::new(None);
+
+    pub fn with<R>(f: impl FnOnce(&mut Registry) -> R) -> R {
+        let mut registry = REGISTRY.lock().unwrap_or_else(|e| e.into_inner());
+        f(registry.get_or_insert_with(Registry::default))
+    }
+
+    pub fn fail(func: &str, ptr: usize, msg: &str) -> ! {
+        eprintln!("{}: handle {:#x} {}", func, ptr, msg);
+        std::process::abort()
+    }
+
+    /// Aborts unless the handle points to a live `ty`.
+    pub fn check(registry: &Registry, ptr: usize, ty: &str, func: &str) {
+        let owned = registry.owned.get(&ptr).map(|owned| owned.ty);
+        let borrowed = registry.borrowed.get(&ptr);
+        if owned == Some(ty) || borrowed.map_or(false, |b| b.iter().any(|b| b.ty == ty)) {
+            return;
+        }
+        let tys = owned.into_iter()
+            .chain(borrowed.into_iter().flatten().map(|b| b.ty))
+            .collect::<Vec<_>>();
+        if !tys.is_empty() {
+            fail(func, ptr, &format!("has type {}, expected {}", tys.join(" or "), ty));
+        }
+        match registry.freed.get(&ptr) {
+            Some(freed_ty) => fail(func, ptr, &format!("to a {} was already freed", freed_ty)),
+            None => fail(func, ptr, "was never issued"),
+        }
+    }
+
+    /// Aborts unless the handle is owned by C as a `kind<ty>`.
+    pub fn check_owned(registry: &Registry, ptr: usize, kind: &str, ty: &str, func: &str) {
+        check(registry, ptr, ty, func);
+        match registry.owned.get(&ptr) {
+            Some(owned) if owned.ty == ty && owned.kind != kind => fail(func, ptr, &format!(
+                "is a {}<{}>, expected a {}<{}>", owned.kind, ty, kind, ty)),
+            Some(owned) if owned.ty == ty => {}
+            _ => fail(func, ptr, "is borrowed and not owned by the caller"),
+        }
+    }
+
+    /// Forgets the references lent to C from the freed handle at `owner`,
+    /// and the references lent from those in turn.
+    pub fn drop_borrowed(registry: &mut Registry, owner: usize) {
+        let mut owners = vec![owner];
+        while let Some(owner) = owners.pop() {
+            let mut dropped = vec![];
+            registry.borrowed.retain(|&ptr, borrowed| {
+                borrowed.retain(|b| b.owner != owner || {
+                    dropped.push((ptr, b.ty));
+                    false
+                });
+                !borrowed.is_empty()
+            });
+            for (ptr, ty) in dropped {
+                if !registry.owned.contains_key(&ptr) && !registry.borrowed.contains_key(&ptr) {
+                    registry.freed.insert(ptr, ty);
+                    owners.push(ptr);
+                }
+            }
+        }
+    }
+}
+
+/// Records a handle to a `kind<ty>` given to C.
+pub fn register(ptr: *const c_void, kind: &'static str, ty: &'static str) {
+    #[cfg(feature = "debug-handles")]
+    registry::with(|registry| {
+        let ptr = ptr as usize;
+        registry.freed.remove(&ptr);
+        registry.owned.entry(ptr)
+            .and_modify(|owned| owned.count += 1)
+            .or_insert(registry::Owned { kind, ty, count: 1 });
+    });
+}
+
+/// Records a reference to a `ty` lent to C, which is valid until the handle
+/// at `owner` is freed. A null `owner` is never freed.
+pub fn register_borrowed(ptr: *const c_void, ty: &'static str, owner: *const c_void) {
+    #[cfg(feature = "debug-handles")]
+    registry::with(|registry| {
+        let (ptr, owner) = (ptr as usize, owner as usize);
+        registry.freed.remove(&ptr);
+        let borrowed = registry.borrowed.entry(ptr).or_default();
+        if !borrowed.iter().any(|b| b.ty == ty && b.owner == owner) {
+            borrowed.push(registry::Borrowed { ty, owner });
+        }
+    });
+}
+
+/// Aborts unless a handle passed to `func` points to a live `ty`.
+pub fn check(ptr: *const c_void, ty: &'static str, func: &'static str) {
+    #[cfg(feature = "debug-handles")]
+    registry::with(|registry| registry::check(registry, ptr as usize, ty, func));
+}
+
+/// Records another strong reference to a shared handle passed to `func`.
+pub fn retain(ptr: *const c_void, kind: &'static str, ty: &'static str, func: &'static str) {
+    #[cfg(feature = "debug-handles")]
+    registry::with(|registry| {
+        let ptr = ptr as usize;
+        registry::check_owned(registry, ptr, kind, ty, func);
+        registry.owned.get_mut(&ptr).unwrap().count += 1;
+    });
+}
+
+/// Records that C gave up a handle passed to `func`, freeing it once no
+/// references remain.
+pub fn release(ptr: *const c_void, kind: &'static str, ty: &'static str, func: &'static str) {
+    #[cfg(feature = "debug-handles")]
+    registry::with(|registry| {
+        let ptr = ptr as usize;
+        registry::check_owned(registry, ptr, kind, ty, func);
+        let owned = registry.owned.get_mut(&ptr).unwrap();
+        owned.count -= 1;
+        if owned.count == 0 {
+            registry.owned.remove(&ptr);
+            registry.freed.insert(ptr, ty);
+            registry::drop_borrowed(registry, ptr);
+        }
+    });
+}
 
+/// Prints every handle owned by C to stderr and returns how many there are.
+pub fn live_handles() -> usize {
+    #[cfg(feature = "debug-handles")]
+    let count = registry::with(|registry| {
+        for (ptr, owned) in &registry.owned {
+            eprintln!("{:#x}: {}<{}> ({} references)", ptr, owned.kind, owned.ty, owned.count);
+        }
+        registry.owned.len()
+    });
+    #[cfg(not(feature = "debug-handles"))]
+    let count = 0;
+    count
+}"#;
+
+/// Writes a Makefile whose `install` target copies the release libraries, the
+/// headers, and the pkg-config and CMake package files under
+/// `$(DESTDIR)$(PREFIX)`. A static build also records the native libraries
+/// that consumers must link in `native-static-libs`, and fills them in to the
+/// package files.
 pub fn gen_makefile(
+    package_name: &str,
     package_folder: &Path,
+    crate_type: CrateType,
 ) -> Result<()> {
+    let cmake_name = camel_case(package_name);
     let mut compiler = SerializationCompiler::new();
+    compiler.add_line("PREFIX ?= /usr/local")?;
+    compiler.add_line("DESTDIR ?=")?;
+    if crate_type.is_static() {
+        compiler.add_line("SUBST = sed -e 's|@PREFIX@|$(PREFIX)|g' \
+            -e \"s|@NATIVE_STATIC_LIBS@|$$(cat native-static-libs)|g\"")?;
+    } else {
+        compiler.add_line("SUBST = sed -e 's|@PREFIX@|$(PREFIX)|g' \
+            -e 's|@NATIVE_STATIC_LIBS@||g'")?;
+    }
+    compiler.add_newline()?;
     compiler.add_line("build:")?;
     compiler.add_line(&format!("\tCDYLIB=1 cargo b --release"))?;
+    if crate_type.is_static() {
+        // rustc only prints the libraries when it rebuilds the library
+        compiler.add_line("\ttouch src/lib.rs")?;
+        compiler.add_line("\tCDYLIB=1 cargo rustc --release --lib --quiet -- \
+            --print native-static-libs 2>&1 \
+            | sed -n 's/^note: native-static-libs: //p' > native-static-libs")?;
+        compiler.add_line(&format!("\t$(SUBST) {0}.pc.in > {0}.pc",
+            package_name))?;
+    }
+    compiler.add_newline()?;
+    compiler.add_line("debug:")?;
+    compiler.add_line("\tCDYLIB=1 cargo b --features debug-handles")?;
+    compiler.add_newline()?;
+    compiler.add_line("install: build")?;
+    compiler.add_line(&format!("\tinstall -d $(DESTDIR)$(PREFIX)/include \
+        $(DESTDIR)$(PREFIX)/lib/pkgconfig $(DESTDIR)$(PREFIX)/lib/cmake/{}",
+        cmake_name))?;
+    if crate_type.is_shared() {
+        compiler.add_line(&format!("\tinstall -m 755 target/release/lib{}_c.so \
+            $(DESTDIR)$(PREFIX)/lib", package_name))?;
+    }
+    if crate_type.is_static() {
+        compiler.add_line(&format!("\tinstall -m 644 target/release/lib{}_c.a \
+            $(DESTDIR)$(PREFIX)/lib", package_name))?;
+    }
+    compiler.add_line(&format!("\tinstall -m 644 {}.h $(DESTDIR)$(PREFIX)/include",
+        package_name))?;
+    compiler.add_line(&format!("\tif [ -f {0}.hpp ]; then install -m 644 {0}.hpp \
+        $(DESTDIR)$(PREFIX)/include; fi", package_name))?;
+    compiler.add_line(&format!("\t$(SUBST) {0}.pc.in \
+        > $(DESTDIR)$(PREFIX)/lib/pkgconfig/{0}.pc", package_name))?;
+    compiler.add_line(&format!("\t$(SUBST) {0}Config.cmake \
+        > $(DESTDIR)$(PREFIX)/lib/cmake/{0}/{0}Config.cmake", cmake_name))?;
+    compiler.add_newline()?;
+    compiler.add_line(".PHONY: build debug install")?;
     compiler.flush(&package_folder.join("Makefile"))?;
     Ok(())
 }
 
+/// Writes `<package_name>.pc.in`, which `make install` fills in with
+/// `$(PREFIX)` and the native libraries of a static build, and
+/// `<package_name>.pc` for the default prefix, `/usr/local`, which a static
+/// `make build` fills in.
+pub fn gen_pkg_config(
+    package_name: &str,
+    package_folder: &Path,
+    crate_type: CrateType,
+    version: &str,
+) -> Result<()> {
+    let mut template = format!("prefix=@PREFIX@
+exec_prefix=${{prefix}}
+libdir=${{exec_prefix}}/lib
+includedir=${{prefix}}/include
+
+Name: {0}
+Description: C bindings for {0}
+Version: {1}
+Libs: -L${{libdir}} -l{0}_c
+Cflags: -I${{includedir}}
+", package_name, version);
+    if crate_type.is_static() {
+        template.push_str("Libs.private: @NATIVE_STATIC_LIBS@\n");
+    }
+    for (file_name, contents) in [
+        (format!("{}.pc.in", package_name), template.clone()),
+        (format!("{}.pc", package_name), template
+            .replace("@PREFIX@", "/usr/local")
+            .replace("@NATIVE_STATIC_LIBS@", "")),
+    ] {
+        let mut compiler = SerializationCompiler::new();
+        for line in contents.lines() {
+            compiler.add_line(line)?;
+        }
+        compiler.flush(&package_folder.join(file_name))?;
+    }
+    Ok(())
+}
+
+/// Writes `<PackageName>Config.cmake`, which defines an imported target for
+/// each library relative to where it is installed, in
+/// `<prefix>/lib/cmake/<PackageName>`. The library is
+/// `<PackageName>::<package_name>_c`, and with `CrateType::Both` the static
+/// library is `<PackageName>::<package_name>_c_static`.
+pub fn gen_cmake_config(
+    package_name: &str,
+    package_folder: &Path,
+    crate_type: CrateType,
+) -> Result<()> {
+    let cmake_name = camel_case(package_name);
+    let prefix_var = format!("_{}_prefix", package_name);
+    let libs_var = format!("_{}_libs", package_name);
+    let shared_target = format!("{}::{}_c", cmake_name, package_name);
+    let static_target = if crate_type.is_shared() {
+        format!("{}_static", shared_target)
+    } else {
+        shared_target.clone()
+    };
+    let mut targets = vec![];
+    if crate_type.is_shared() {
+        targets.push((&shared_target, "SHARED"));
+    }
+    if crate_type.is_static() {
+        targets.push((&static_target, "STATIC"));
+    }
+
+    let mut compiler = SerializationCompiler::new();
+    compiler.add_line(&format!("# Found by find_package({}), which defines the \
+        imported targets {}.", cmake_name, targets.iter()
+            .map(|(target, _)| target.as_str())
+            .collect::<Vec<_>>()
+            .join(" and ")))?;
+    compiler.add_line(&format!("get_filename_component({} \
+        \"${{CMAKE_CURRENT_LIST_DIR}}/../../..\" ABSOLUTE)", prefix_var))?;
+    if crate_type.is_static() {
+        compiler.add_line(&format!("separate_arguments({} UNIX_COMMAND \
+            \"@NATIVE_STATIC_LIBS@\")", libs_var))?;
+    }
+    for (target, kind) in &targets {
+        compiler.add_newline()?;
+        compiler.add_line(&format!("if(NOT TARGET {})", target))?;
+        compiler.add_line(&format!("  add_library({} {} IMPORTED)", target,
+            kind))?;
+        compiler.add_line(&format!("  set_target_properties({} PROPERTIES",
+            target))?;
+        compiler.add_line(&format!("    IMPORTED_LOCATION \"${{{0}}}/lib/\
+            ${{CMAKE_{2}_LIBRARY_PREFIX}}{1}_c${{CMAKE_{2}_LIBRARY_SUFFIX}}\"",
+            prefix_var, package_name, kind))?;
+        if *kind == "STATIC" {
+            compiler.add_line(&format!("    INTERFACE_LINK_LIBRARIES \
+                \"${{{}}}\"", libs_var))?;
+        }
+        compiler.add_line(&format!("    INTERFACE_INCLUDE_DIRECTORIES \
+            \"${{{}}}/include\")", prefix_var))?;
+        compiler.add_line("endif()")?;
+    }
+    compiler.add_newline()?;
+    compiler.add_line(&format!("unset({})", prefix_var))?;
+    if crate_type.is_static() {
+        compiler.add_line(&format!("unset({})", libs_var))?;
+    }
+    compiler.flush(&package_folder.join(format!("{}Config.cmake", cmake_name)))?;
+    Ok(())
+}
+
 pub fn gen_build_rs(package_name: &str, package_folder: &Path) -> Result<()> {
     let mut compiler = SerializationCompiler::new();
     compiler.add_extern_crate("cbindgen")?;
@@ -51,7 +412,10 @@ pub fn gen_build_rs(package_name: &str, package_folder: &Path) -> Result<()> {
 pub fn gen_cargo_toml(
     package_name: &str,
     package_folder: &Path,
-    crates: &Vec<String>,
+    crates: &[Dependency],
+    use_cbindgen: bool,
+    crate_type: CrateType,
+    config: &CargoManifestConfig,
 ) -> Result<()> {
     let package_name_c = format!("{}-c", str::replace(package_name, "_", "-"));
     let package_name_rust = format!("{}_c", package_name);
@@ -59,28 +423,93 @@ pub fn gen_cargo_toml(
     let mut compiler = SerializationCompiler::new();
     compiler.add_line("[package]")?;
     compiler.add_line(&format!("name = \"{}\"", package_name_c))?;
-    compiler.add_line("version = \"0.1.0\"")?;
-    compiler.add_line("edition = \"2021\"")?;
+    compiler.add_line(&format!("version = {}", toml_string(&config.version)))?;
+    compiler.add_line(&format!("edition = {}", toml_string(&config.edition)))?;
+    if !config.authors.is_empty() {
+        compiler.add_line(&format!("authors = [{}]", config.authors.iter()
+            .map(|author| toml_string(author))
+            .collect::<Vec<_>>()
+            .join(", ")))?;
+    }
+    for (key, value) in [
+        ("description", &config.description),
+        ("license", &config.license),
+        ("repository", &config.repository),
+    ] {
+        if let Some(value) = value {
+            compiler.add_line(&format!("{} = {}", key, toml_string(value)))?;
+        }
+    }
     compiler.add_newline()?;
 
     compiler.add_line("[lib]")?;
     compiler.add_line(&format!("name = \"{}\"", package_name_rust))?;
     compiler.add_line("path = \"src/lib.rs\"")?;
-    compiler.add_line("crate-type = [\"cdylib\"]")?;
+    compiler.add_line(&format!("crate-type = [{}]", crate_type
+        .cargo_crate_types()
+        .iter()
+        .map(|crate_type| format!("\"{}\"", crate_type))
+        .collect::<Vec<_>>()
+        .join(", ")))?;
     compiler.add_newline()?;
 
     // Dependencies
     compiler.add_line("[dependencies]")?;
-    for line in crates {
-        compiler.add_line(line)?;
+    for dependency in crates {
+        compiler.add_line(&dependency.to_toml())?;
     }
     compiler.add_newline()?;
+    for (target, dependencies) in &config.target_dependencies {
+        compiler.add_line(&format!("[target.{}.dependencies]",
+            toml_key(target)))?;
+        for dependency in dependencies {
+            compiler.add_line(&dependency.to_toml())?;
+        }
+        compiler.add_newline()?;
+    }
 
-    // Build dependencies
-    compiler.add_line("[build-dependencies]")?;
-    compiler.add_line("cbindgen = \"0.23.0\"")?;
+    compiler.add_line("[features]")?;
+    compiler.add_line("debug-handles = []")?;
+    for (feature, enables) in &config.features {
+        compiler.add_line(&format!("{} = [{}]", toml_key(feature), enables
+            .iter()
+            .map(|enable| toml_string(enable))
+            .collect::<Vec<_>>()
+            .join(", ")))?;
+    }
     compiler.add_newline()?;
 
+    // Build dependencies
+    if use_cbindgen {
+        compiler.add_line("[build-dependencies]")?;
+        compiler.add_line(&format!("cbindgen = {}",
+            toml_string(&config.cbindgen_version)))?;
+        compiler.add_newline()?;
+    }
+
+    let profile = &config.release_profile;
+    if !profile.is_empty() {
+        compiler.add_line("[profile.release]")?;
+        if let Some(lto) = profile.lto {
+            compiler.add_line(&format!("lto = \"{}\"", lto.as_str()))?;
+        }
+        if let Some(codegen_units) = profile.codegen_units {
+            compiler.add_line(&format!("codegen-units = {}", codegen_units))?;
+        }
+        if let Some(panic) = profile.panic {
+            compiler.add_line(&format!("panic = \"{}\"", panic.as_str()))?;
+        }
+        compiler.add_newline()?;
+    }
+
+    for (source, dependencies) in &config.patches {
+        compiler.add_line(&format!("[patch.{}]", toml_key(source)))?;
+        for dependency in dependencies {
+            compiler.add_line(&dependency.to_toml())?;
+        }
+        compiler.add_newline()?;
+    }
+
     // Exclude the generated package from the workspace
     compiler.add_line("[workspace]")?;
 
@@ -88,54 +517,267 @@ pub fn gen_cargo_toml(
     Ok(())
 }
 
+/// Writes the debug handle registry module to src/.
+pub fn gen_debug_handles(package_folder: &Path) -> Result<()> {
+    let mut compiler = SerializationCompiler::new();
+    compiler.add_block(DEBUG_HANDLES_SRC)?;
+    compiler.flush(&package_folder.join("src")
+        .join(format!("{}.rs", DEBUG_HANDLES_MOD)))?;
+    Ok(())
+}
+
+/// Declares the debug handle registry module and adds
+/// `<package_name>_debug_live_handles`, which dumps it for leak hunting.
+pub fn add_debug_handles_functions(
+    compiler: &mut SerializationCompiler,
+    interface: &mut Interface,
+    package_name: &str,
+) -> Result<()> {
+    compiler.add_mod_declaration(DEBUG_HANDLES_MOD)?;
+    compiler.add_newline()?;
+    let extern_name = format!("{}_debug_live_handles", package_name);
+    let mut func_context = FunctionContext::new_extern_c_with_ret(
+        &extern_name, true, vec![], "usize",
+    );
+    func_context.add_doc("Prints every handle owned by C to stderr and \
+        returns how many there are. Handles are only tracked when the crate is \
+        built with the `debug-handles` feature, and this returns 0 otherwise.");
+    interface.functions.push(ExternFunction {
+        name: extern_name,
+        params: vec![],
+        ret: Some(CType::Primitive("usize".to_string())),
+        docs: func_context.docs().to_vec(),
+        kind: FunctionKind::DebugLiveHandles,
+    });
+    compiler.add_context(Context::Function(func_context))?;
+    compiler.add_return_val(
+        &format!("{}::live_handles()", DEBUG_HANDLES_MOD), false)?;
+    compiler.pop_context()?;
+    compiler.add_newline()?;
+    Ok(())
+}
+
+#[allow(clippy::too_many_arguments)]
 pub fn add_extern_c_function(
     compiler: &mut SerializationCompiler,
+    options: &Options,
+    interface: &mut Interface,
     extern_name: &str,
     struct_ty: Option<(Type, SelfType)>,
     func_name: &str,
     raw_args: Vec<(&str, Type)>,
+    arg_ownership: Vec<Ownership>,
     raw_ret: Option<Type>,
+    ret_ownership: Ownership,
     use_error_code: bool,
+    doc: Option<&str>,
 ) -> Result<()> {
+    // Check the signature before emitting anything. A reference or iterator
+    // that borrows from a locked value would outlive the lock guard.
+    let self_locked = struct_ty.as_ref().is_some_and(|(struct_ty, self_ty)|
+        self_ty.is_ref() && handle_lock(options, struct_ty) != HandleLock::None);
+    match &raw_ret {
+        Some(Type::Ref(ty) | Type::RefMut(ty)) if ret_ownership == Ownership::Borrow
+                && (self_locked || handle_lock(options, ty) != HandleLock::None) =>
+            bail!("{} cannot borrow from a locked handle, so its return value \
+                must be cloned or copied", extern_name),
+        Some(Type::Iterator(item_ty)) => {
+            if let Type::Iterator(_) = &**item_ty {
+                bail!("Nested iterators are not supported: {}", extern_name);
+            }
+            if self_locked {
+                bail!("{} cannot return an iterator that borrows from a locked \
+                    handle", extern_name);
+            }
+            if let Type::Ref(ty) | Type::RefMut(ty) = &**item_ty {
+                if handle_lock(options, ty) != HandleLock::None {
+                    bail!("{} cannot yield references to locked handles",
+                        extern_name);
+                }
+            }
+        }
+        _ => {}
+    }
+    for (arg_name, arg_ty) in &raw_args {
+        if let Type::Iterator(_) = arg_ty {
+            bail!("Iterator arguments are not supported: {}", arg_name);
+        }
+    }
+
+    // The callee could replace the value behind a shared handle, and other
+    // references keep it from being moved out
+    if let Some((struct_ty, self_ty)) = &struct_ty {
+        if struct_ty.is_shared() {
+            match self_ty {
+                SelfType::RefMut =>
+                    bail!("Cannot mutably borrow a shared handle: {}",
+                        struct_ty.to_rust_str()),
+                SelfType::Value | SelfType::ValueMut =>
+                    bail!("Cannot consume a shared handle: {}",
+                        struct_ty.to_rust_str()),
+                _ => {}
+            }
+        }
+    }
+    for (arg_name, arg_ty) in &raw_args {
+        if matches!(arg_ty, Type::RefMut(ty) if ty.is_shared()) {
+            bail!("Cannot mutably borrow a shared handle: {}", arg_name);
+        }
+    }
+
+    let mut ptr_args = vec![];
+    let mut buffer_args = vec![];
     let args = {
         let mut args = vec![];
         if struct_ty.as_ref().map(|(_, self_ty)| self_ty.is_some()).unwrap_or(false) {
             args.push(FunctionArg::CSelfArg);
+            ptr_args.push("self_".to_string());
         }
         for (arg_name, arg_ty) in &raw_args {
             args.push(FunctionArg::CArg(CArgInfo::arg(arg_name, &arg_ty.to_c_str())));
             if arg_ty.is_buffer() {
                 args.push(FunctionArg::CArg(CArgInfo::len_arg(arg_name)));
+                buffer_args.push(arg_name.to_string());
+            }
+            if !matches!(arg_ty, Type::Primitive(_) | Type::Enum{..} | Type::RawPtr{..}) {
+                ptr_args.push(arg_name.to_string());
             }
         }
         if let Some(ret_ty) = &raw_ret {
             args.push(FunctionArg::CArg(CArgInfo::ret_arg(&ret_ty.to_c_str())));
+            ptr_args.push("return_ptr".to_string());
             if ret_ty.is_buffer() {
                 args.push(FunctionArg::CArg(CArgInfo::ret_len_arg()));
+                ptr_args.push("return_len_ptr".to_string());
             }
         }
         args
     };
 
-    let func_context = FunctionContext::new_extern_c(
+    let borrows = borrowed_handles(&struct_ty, &raw_args);
+    let mut func_context = FunctionContext::new_extern_c(
         extern_name, true, args, use_error_code,
     );
+    for line in doc.into_iter().flat_map(str::lines) {
+        func_context.add_doc(line);
+    }
+    let mut notes = vec![];
+    if let Some((_, SelfType::Value | SelfType::ValueMut)) = struct_ty {
+        notes.push(
+            "Consumes `self_`. The handle is invalid after this call.".to_string());
+    }
+    match &raw_ret {
+        Some(Type::Iterator(_)) => {
+            notes.push(format!(
+                "Writes an iterator handle to `return_ptr`. Advance it with \
+                `{0}_iter_next` and free it with `{0}_iter_free`.",
+                extern_name,
+            ));
+            if !borrows.is_empty() {
+                notes.push(format!("The iterator may borrow from {}, so free \
+                    it first.", describe_params(&borrows)));
+            }
+        }
+        Some(ret_ty) if ret_ty.is_buffer() => notes.push(
+            "Writes a pointer to the returned buffer to `return_ptr` and its \
+            length to `return_len_ptr`.".to_string()),
+        Some(_) => notes.push(
+            "Writes the return value to `return_ptr`.".to_string()),
+        None => {}
+    }
+    if use_error_code {
+        notes.push(format!("Returns 0 on success, or 1 if `{}` returned an \
+            error.", func_name));
+        if options.null_policy == NullPolicy::ReturnError && !ptr_args.is_empty() {
+            notes.push(format!("Returns {} if a pointer argument is null.",
+                NULL_POINTER_ERROR));
+        }
+    }
+    if !notes.is_empty() {
+        if func_context.has_docs() {
+            func_context.add_doc("");
+        }
+        for note in notes {
+            func_context.add_doc(&note);
+        }
+    }
+    let mut ownership_docs = raw_args.iter()
+        .zip(&arg_ownership)
+        .filter_map(|((arg_name, arg_ty), ownership)| {
+            arg_ownership_doc(arg_ty, *ownership)
+                .map(|doc| format!("- `{}`: {}", arg_name, doc))
+        })
+        .collect::<Vec<_>>();
+    if let Some(doc) = raw_ret.as_ref()
+            .and_then(|ret_ty| ret_ownership_doc(ret_ty, ret_ownership)) {
+        ownership_docs.push(format!("- `return_ptr`: {}", doc));
+    }
+    if !ownership_docs.is_empty() {
+        if func_context.has_docs() {
+            func_context.add_doc("");
+        }
+        func_context.add_doc("Ownership:");
+        for doc in ownership_docs {
+            func_context.add_doc(&doc);
+        }
+    }
+    let mut safety_docs = vec![];
+    if let Some((struct_ty, self_ty)) = &struct_ty {
+        let self_arg = match self_ty {
+            SelfType::None => None,
+            SelfType::Value | SelfType::ValueMut =>
+                Some((struct_ty.clone(), Ownership::Transfer)),
+            SelfType::Ref =>
+                Some((Type::Ref(Box::new(struct_ty.clone())), Ownership::Borrow)),
+            SelfType::RefMut =>
+                Some((Type::RefMut(Box::new(struct_ty.clone())), Ownership::Borrow)),
+        };
+        safety_docs.extend(self_arg.and_then(|(self_ty, ownership)|
+            arg_safety_doc(options, "self_", &self_ty, ownership)));
+    }
+    for ((arg_name, arg_ty), ownership) in raw_args.iter().zip(&arg_ownership) {
+        safety_docs.extend(arg_safety_doc(options, arg_name, arg_ty, *ownership));
+    }
+    match &raw_ret {
+        Some(ret_ty) if ret_ty.is_buffer() => safety_docs.push(
+            "`return_ptr` and `return_len_ptr` must be valid for writes."
+                .to_string()),
+        Some(_) => safety_docs.push(
+            "`return_ptr` must be valid for writes.".to_string()),
+        None => {}
+    }
+    add_safety_docs(&mut func_context, options, safety_docs);
+    record_function(interface, extern_name, &func_context, &struct_ty,
+        func_name, &raw_args, &arg_ownership, raw_ret.as_ref(), ret_ownership,
+        &borrows, use_error_code);
     compiler.add_context(Context::Function(func_context))?;
+    add_null_checks(compiler, options, extern_name, &ptr_args, &buffer_args,
+        use_error_code, false)?;
 
     // Format self argument
     if let Some((ref struct_ty, ref self_ty)) = struct_ty {
-        let struct_name = struct_ty.to_rust_str();
+        let is_locked = handle_lock(options, struct_ty) != HandleLock::None;
         match self_ty {
             SelfType::None => {}
-            SelfType::Value => unimplemented!(),
-            SelfType::ValueMut => unimplemented!(),
-            SelfType::Ref => {
+            SelfType::Value | SelfType::ValueMut => {
+                add_debug_handle_call(compiler, "release", "self_", struct_ty,
+                    Some(extern_name))?;
                 compiler.add_unsafe_def_with_let(false, None, "self_",
-                    &format!("Box::from_raw(self_ as *mut {})", struct_name))?;
+                    &handle_from_raw(options, struct_ty, "self_", extern_name))?;
+            }
+            // Handles point directly at the inner value, so borrowing it
+            // leaves ownership with the caller
+            SelfType::Ref => {
+                add_debug_handle_call(compiler, "check", "self_", struct_ty,
+                    Some(extern_name))?;
+                compiler.add_def_with_let(false, None, "self_",
+                    &handle_borrow(options, struct_ty, "self_", false, extern_name))?;
             }
             SelfType::RefMut => {
-                compiler.add_unsafe_def_with_let(true, None, "self_",
-                    &format!("Box::from_raw(self_ as *mut {})", struct_name))?;
+                add_debug_handle_call(compiler, "check", "self_", struct_ty,
+                    Some(extern_name))?;
+                compiler.add_def_with_let(is_locked, None, "self_",
+                    &handle_borrow(options, struct_ty, "self_", true, extern_name))?;
             }
         }
     }
@@ -143,23 +785,57 @@ pub fn add_extern_c_function(
     // Format arguments
     for (i, (arg_name, arg_ty)) in raw_args.iter().enumerate() {
         let left = format!("arg{}", i);
+        let ownership = arg_ownership[i];
+        match arg_ty {
+            Type::Struct{..} | Type::Box(_) | Type::Arc(_) | Type::Rc(_) => {
+                let hook = if ownership == Ownership::Transfer {
+                    "release"
+                } else {
+                    "check"
+                };
+                add_debug_handle_call(compiler, hook, arg_name, arg_ty,
+                    Some(extern_name))?;
+            }
+            Type::Ref(ty) | Type::RefMut(ty) if is_borrowed_handle(arg_ty) => {
+                add_debug_handle_call(compiler, "check", arg_name, ty,
+                    Some(extern_name))?;
+            }
+            _ => {}
+        }
         let right = match arg_ty {
-            Type::Primitive(_) => arg_name.to_string(),
-            Type::Struct{..} => format!(
-                "unsafe {{ *Box::from_raw({} as *mut {}) }}",
-                arg_name, arg_ty.to_rust_str(),
-            ),
-            Type::Ref(ty) => format!(
-                "unsafe {{ Box::from_raw({} as *mut {}) }}",
-                arg_name, ty.to_rust_str(),
+            Type::Primitive(_) | Type::RawPtr{..} => arg_name.to_string(),
+            // Leave the caller's handle intact
+            Type::Struct{..} if ownership == Ownership::Copy => format!(
+                "*{}", handle_borrow(options, arg_ty, arg_name, false, extern_name)),
+            Type::Struct{..} if ownership == Ownership::BorrowClone => format!(
+                "{}.clone()", handle_borrow(options, arg_ty, arg_name, false, extern_name)),
+            Type::Box(_) if ownership == Ownership::BorrowClone => format!(
+                "Box::new({}.clone())",
+                handle_borrow(options, arg_ty, arg_name, false, extern_name),
             ),
-            Type::RefMut(ty) => format!(
-                "{} as *mut {}",
-                arg_name, ty.to_rust_str(),
+            Type::Arc(ty) | Type::Rc(ty) if ownership == Ownership::BorrowClone =>
+                format!(
+                    "unsafe {{ {0}::increment_strong_count({1} as *const {2}); \
+                    {0}::from_raw({1} as *const {2}) }}",
+                    shared_path(arg_ty), arg_name, ty.to_rust_str(),
+                ),
+            Type::Struct{..} | Type::Box(_) | Type::Arc(_) | Type::Rc(_) =>
+                format!("unsafe {{ {} }}",
+                    handle_from_raw(options, arg_ty, arg_name, extern_name)),
+            // Borrow the shared value without giving up the caller's count
+            Type::Ref(ty) if ty.is_shared() => format!(
+                "unsafe {{ std::mem::ManuallyDrop::new({}) }}",
+                handle_from_raw(options, ty, arg_name, extern_name),
             ),
+            Type::Ref(ty) =>
+                handle_borrow(options, ty, arg_name, false, extern_name),
+            Type::RefMut(ty) =>
+                handle_borrow(options, ty, arg_name, true, extern_name),
+            // An empty buffer may be null, which from_raw_parts does not allow
             Type::Buffer(_) => format!(
-                "unsafe {{ std::slice::from_raw_parts({} as {}, {}_len) }}",
-                arg_name, arg_ty.to_rust_str(), arg_name,
+                "if {0}_len == 0 {{ &[] }} else {{ \
+                unsafe {{ std::slice::from_raw_parts({0} as {1}, {0}_len) }} }}",
+                arg_name, arg_ty.to_rust_str(),
             ),
             Type::Enum { name, variants } => {
                 let mut variant_nums = (0..variants.len())
@@ -177,21 +853,32 @@ pub fn add_extern_c_function(
                 }
                 continue;
             }
+            // Rejected above
+            Type::Iterator(_) => unreachable!(),
         };
-        compiler.add_def_with_let(false, None, &left, &right)?;
+        let is_mut = matches!(arg_ty,
+            Type::RefMut(ty) if handle_lock(options, ty) != HandleLock::None);
+        compiler.add_def_with_let(is_mut, None, &left, &right)?;
     }
 
     // Generate function arguments and return type
     let args = raw_args.iter()
         .enumerate()
         .map(|(i, (_, arg_ty))| match arg_ty {
-            Type::Ref(_) => format!("&arg{}", i),
-            Type::RefMut(_) => format!("unsafe {{ &mut *arg{} }}", i),
+            Type::Ref(ty) if ty.is_shared() => format!("&arg{}", i),
+            // Borrow through the lock guard
+            Type::Ref(ty) if handle_lock(options, ty) != HandleLock::None =>
+                format!("&*arg{}", i),
+            Type::RefMut(ty) if handle_lock(options, ty) != HandleLock::None =>
+                format!("&mut *arg{}", i),
             _ => format!("arg{}", i),
         })
         .collect::<Vec<_>>();
-    let ret_ty = if let Some(ref ret_ty) = raw_ret {
+    let ret_ty = if let (Some(ref ret_ty), Ownership::Borrow) =
+            (&raw_ret, ret_ownership) {
         match ret_ty {
+            // Marshalled with `as_ptr`, which takes the reference
+            Type::Ref(ty) | Type::RefMut(ty) if ty.is_shared() => None,
             Type::Ref(ty) => Some(format!("*const {}", &ty.to_rust_str())),
             Type::RefMut(ty) => Some(format!("*mut {}", &ty.to_rust_str())),
             _ => None,
@@ -205,8 +892,8 @@ pub fn add_extern_c_function(
         if self_ty.is_some() {
             (Some("self_".to_string()), func_name.to_string())
         } else {
-            let (struct_name, struct_params) = match struct_ty {
-                Type::Struct { ref name, ref args } => (name, args),
+            let (struct_name, struct_params) = match struct_ty.inner_struct() {
+                Some(Type::Struct { ref name, ref args }) => (name, args),
                 _ => bail!("Expecting Struct argument type as struct_ty"),
             };
             (None, format!(
@@ -252,63 +939,1019 @@ pub fn add_extern_c_function(
     }
 
     // Marshall return value into C type
+    let owner = match borrows.first() {
+        // Shadowed by the borrowed value, which the handle points to
+        Some(name) if name == "self_" => "self_ as *const _ as _".to_string(),
+        Some(name) => format!("{} as _", name),
+        None => "std::ptr::null()".to_string(),
+    };
     if let Some(ret_ty) = &raw_ret {
-        match ret_ty {
-            Type::Primitive(_) => {
-                compiler.add_unsafe_set("return_ptr", "value")?;
+        match (ret_ty, ret_ownership) {
+            (Type::Ref(ty) | Type::RefMut(ty), Ownership::BorrowClone) => {
+                compiler.add_def_with_let(false, None, "value", "value.clone()")?;
+                marshal_return(compiler, options, ty, &owner)?;
             }
-            Type::Struct{..} => {
-                compiler.add_func_call_with_let("value", None, None,
-                   "Box::into_raw", vec!["Box::new(value)".to_string()],
-                   false)?;
-                compiler.add_unsafe_set("return_ptr", "value as _")?;
+            (Type::Ref(ty) | Type::RefMut(ty), Ownership::Copy) => {
+                compiler.add_def_with_let(false, None, "value", "*value")?;
+                marshal_return(compiler, options, ty, &owner)?;
             }
-            Type::Ref(_) | Type::RefMut(_) => {
-                compiler.add_unsafe_set("return_ptr", "value as _")?;
+            _ => marshal_return(compiler, options, ret_ty, &owner)?,
+        }
+    }
+
+    if use_error_code {
+        compiler.add_line("0")?;
+    }
+
+    compiler.pop_context()?; // end of function
+    compiler.add_newline()?;
+
+    if let Some(Type::Iterator(item_ty)) = &raw_ret {
+        add_iterator_functions(compiler, options, interface, extern_name,
+            item_ty)?;
+    }
+    Ok(())
+}
+
+/// The ownership of each argument and of the return value: the annotated
+/// ownership if there is one, or the ownership implied by the type. Fails if
+/// an annotation names no argument, or a value cannot be passed with its
+/// ownership.
+pub fn resolve_ownership(
+    raw_args: &[(&str, Type)],
+    raw_ret: Option<&Type>,
+    mut arg_annotations: HashMap<String, Ownership>,
+    ret_annotation: Option<Ownership>,
+) -> Result<(Vec<Ownership>, Ownership)> {
+    let arg_ownership = raw_args.iter()
+        .map(|(arg_name, arg_ty)| {
+            let ownership = arg_annotations.remove(*arg_name)
+                .unwrap_or_else(|| arg_ty.ownership());
+            check_ownership(arg_name, arg_ty, ownership, false)
+        })
+        .collect::<Result<Vec<_>>>()?;
+    if let Some(arg_name) = arg_annotations.keys().next() {
+        bail!("Ownership is set for {}, which is not an argument", arg_name);
+    }
+    let ret_ownership = match raw_ret {
+        Some(ret_ty) => {
+            let ownership = ret_annotation.unwrap_or(match ret_ty {
+                // Returned buffers are handed over to the caller
+                Type::Buffer(_) => Ownership::Transfer,
+                ty => ty.ownership(),
+            });
+            check_ownership("return value", ret_ty, ownership, true)?
+        }
+        None if ret_annotation.is_some() =>
+            bail!("Ownership is set for the return value, but there is none"),
+        None => Ownership::Copy,
+    };
+    Ok((arg_ownership, ret_ownership))
+}
+
+/// Checks that an argument or return type can be passed with the given
+/// ownership.
+fn check_ownership(
+    name: &str,
+    ty: &Type,
+    ownership: Ownership,
+    is_return: bool,
+) -> Result<Ownership> {
+    let valid = match (ty, ownership) {
+        (Type::Primitive(_) | Type::Enum{..} | Type::RawPtr{..}, ownership) =>
+            ownership == Ownership::Copy,
+        (Type::Struct{..}, Ownership::BorrowClone | Ownership::Copy) =>
+            !is_return,
+        (Type::Box(_) | Type::Arc(_) | Type::Rc(_), Ownership::BorrowClone) =>
+            !is_return,
+        (Type::Struct{..} | Type::Box(_) | Type::Arc(_) | Type::Rc(_)
+            | Type::Iterator(_), ownership) => ownership == Ownership::Transfer,
+        (Type::Ref(_) | Type::RefMut(_), Ownership::Borrow) => true,
+        (Type::Ref(ty) | Type::RefMut(ty), Ownership::BorrowClone) =>
+            is_return && ty.inner_struct().is_some(),
+        (Type::Ref(ty) | Type::RefMut(ty), Ownership::Copy) =>
+            is_return && ty.is_struct(),
+        (Type::Ref(_) | Type::RefMut(_), Ownership::Transfer) => false,
+        (Type::Buffer(_), Ownership::Transfer) => is_return,
+        (Type::Buffer(_), Ownership::Borrow) => !is_return,
+        (Type::Buffer(_), _) => false,
+    };
+    if !valid {
+        bail!("{} of type {} cannot be passed with {:?} ownership", name,
+            ty.to_rust_str(), ownership);
+    }
+    Ok(ownership)
+}
+
+/// Describes who owns an argument after the call, for the doc comment.
+fn arg_ownership_doc(ty: &Type, ownership: Ownership) -> Option<&'static str> {
+    if matches!(ty, Type::Primitive(_) | Type::Enum{..} | Type::RawPtr{..}) {
+        return None;
+    }
+    Some(match ownership {
+        Ownership::Transfer =>
+            "consumed. The handle is invalid after this call.",
+        Ownership::Borrow => "borrowed for the duration of the call.",
+        Ownership::BorrowClone =>
+            "cloned. The caller still owns the handle.",
+        Ownership::Copy => "copied. The caller still owns the handle.",
+    })
+}
+
+/// Describes who owns a return value, for the doc comment.
+fn ret_ownership_doc(ty: &Type, ownership: Ownership) -> Option<String> {
+    let free_name = handle_free_name(ty)?;
+    Some(match (ty, ownership) {
+        (Type::Buffer(_), _) => format!("receives new handles owned by the \
+            caller, each of which must be freed with `{}`.", free_name),
+        (_, Ownership::Transfer) => format!("receives a new handle owned by \
+            the caller, which must be freed with `{}`.", free_name),
+        (_, Ownership::Borrow) => "receives a reference that is valid while \
+            the value it was borrowed from is alive. Do not free it."
+            .to_string(),
+        (_, Ownership::BorrowClone) => format!("receives a new handle to a \
+            clone of the reference, which must be freed with `{}`.", free_name),
+        (_, Ownership::Copy) => format!("receives a new handle to a copy of \
+            the reference, which must be freed with `{}`.", free_name),
+    })
+}
+
+/// The function that frees handles of the given type, looking through
+/// references and buffers.
+fn handle_free_name(ty: &Type) -> Option<String> {
+    let inner_ty = match ty {
+        Type::Ref(ty) | Type::RefMut(ty) | Type::Buffer(ty) => ty,
+        ty => ty,
+    };
+    match (inner_ty, inner_ty.inner_struct()) {
+        (Type::Arc(_) | Type::Rc(_), Some(Type::Struct { name, .. })) =>
+            Some(format!("{}_release", short_struct_name(name))),
+        (_, Some(Type::Struct { name, .. })) =>
+            Some(format!("{}_free", short_struct_name(name))),
+        _ => None,
+    }
+}
+
+/// Records the C signature of a wrapper function.
+#[allow(clippy::too_many_arguments)]
+fn record_function(
+    interface: &mut Interface,
+    extern_name: &str,
+    func_context: &FunctionContext,
+    struct_ty: &Option<(Type, SelfType)>,
+    func_name: &str,
+    raw_args: &[(&str, Type)],
+    arg_ownership: &[Ownership],
+    raw_ret: Option<&Type>,
+    ret_ownership: Ownership,
+    borrows: &[String],
+    use_error_code: bool,
+) {
+    let mut params = vec![];
+    if let Some((struct_ty, self_ty)) = struct_ty {
+        if self_ty.is_some() {
+            params.push(Param {
+                name: "self_".to_string(),
+                ty: CType::from_type(struct_ty),
+                c_str: struct_ty.to_c_str(),
+                role: ParamRole::SelfHandle(if self_ty.is_ref() {
+                    Ownership::Borrow
+                } else {
+                    Ownership::Transfer
+                }),
+                free_with: None,
+                borrows: vec![],
+            });
+        }
+    }
+    for ((arg_name, arg_ty), ownership) in raw_args.iter().zip(arg_ownership) {
+        if let Type::Enum { name, variants } = arg_ty {
+            interface.add_enum(name, variants);
+        }
+        params.push(Param {
+            name: arg_name.to_string(),
+            ty: CType::from_type(arg_ty),
+            c_str: arg_ty.to_c_str(),
+            role: ParamRole::Arg(*ownership),
+            free_with: None,
+            borrows: vec![],
+        });
+        if arg_ty.is_buffer() {
+            params.push(Param {
+                name: format!("{}_len", arg_name),
+                ty: CType::Primitive("usize".to_string()),
+                c_str: "usize".to_string(),
+                role: ParamRole::Len,
+                free_with: None,
+                borrows: vec![],
+            });
+        }
+    }
+    if let Some(ret_ty) = raw_ret {
+        let ty = match ret_ty {
+            Type::Iterator(_) => CType::Handle(format!("{}_iter", extern_name)),
+            ty => CType::from_type(ty),
+        };
+        params.push(Param {
+            name: "return_ptr".to_string(),
+            ty: ty.out_ptr(),
+            c_str: format!("*mut {}", ret_ty.to_c_str()),
+            role: ParamRole::Return(ret_ownership),
+            free_with: match ret_ty {
+                Type::Iterator(_) => Some(format!("{}_iter_free", extern_name)),
+                _ if ret_ownership == Ownership::Borrow => None,
+                ty => handle_free_name(ty),
             },
-            Type::Buffer(ty) => {
-                compiler.add_def_with_let(false,
-                    Some(format!("Vec<*mut {}>", ty.to_rust_str())),
-                    "value",
-                    "value.into_iter().map(|x| Box::into_raw(Box::new(x))).collect()")?;
-                compiler.add_func_call_with_let("value", None, None,
-                   "Box::into_raw", vec!["Box::new(value)".to_string()],
-                   false)?;
-                compiler.add_unsafe_set("return_ptr", "(*value).as_ptr() as _")?;
-                compiler.add_unsafe_set("return_len_ptr", "(*value).len()")?;
+            borrows: match ret_ty {
+                Type::Iterator(_) => borrows.to_vec(),
+                _ if ret_ownership == Ownership::Borrow => borrows.to_vec(),
+                _ => vec![],
             },
-            Type::Enum{..} => unimplemented!(),
+        });
+        if ret_ty.is_buffer() {
+            params.push(Param {
+                name: "return_len_ptr".to_string(),
+                ty: CType::Primitive("usize".to_string()).out_ptr(),
+                c_str: "*mut usize".to_string(),
+                role: ParamRole::ReturnLen,
+                free_with: None,
+                borrows: vec![],
+            });
         }
     }
+    let struct_name = struct_ty.as_ref()
+        .and_then(|(struct_ty, _)| handle_name(struct_ty));
+    interface.functions.push(ExternFunction {
+        name: extern_name.to_string(),
+        params,
+        ret: use_error_code.then(|| CType::Primitive("u32".to_string())),
+        docs: func_context.docs().to_vec(),
+        kind: FunctionKind::Wrapper {
+            struct_name,
+            method: func_name.to_string(),
+            error_code: use_error_code,
+        },
+    });
+}
 
-    // Unformat arguments
-    if let Some((_, ref self_ty)) = struct_ty {
-        if self_ty.is_some() {
-            if !self_ty.is_ref() {
-                unimplemented!()
+/// The handle parameters that a returned iterator or reference may borrow
+/// from: `self_` when it is borrowed, and borrowed handle arguments.
+fn borrowed_handles(
+    struct_ty: &Option<(Type, SelfType)>,
+    raw_args: &[(&str, Type)],
+) -> Vec<String> {
+    let self_arg = struct_ty.as_ref()
+        .filter(|(_, self_ty)| self_ty.is_ref())
+        .map(|_| "self_".to_string());
+    self_arg.into_iter()
+        .chain(raw_args.iter()
+            .filter(|(_, arg_ty)| is_borrowed_handle(arg_ty))
+            .map(|(arg_name, _)| arg_name.to_string()))
+        .collect()
+}
+
+/// Lists parameter names for a doc comment, e.g., "`self_` and `other`".
+fn describe_params(names: &[String]) -> String {
+    let names = names.iter()
+        .map(|name| format!("`{}`", name))
+        .collect::<Vec<_>>();
+    match names.split_last() {
+        Some((last, rest)) if !rest.is_empty() =>
+            format!("{} and {}", rest.join(", "), last),
+        _ => names.join(""),
+    }
+}
+
+/// Describes what a pointer argument must satisfy for the `# Safety` section,
+/// if it is a pointer.
+fn arg_safety_doc(
+    options: &Options,
+    name: &str,
+    ty: &Type,
+    ownership: Ownership,
+) -> Option<String> {
+    let handle_name = |ty: &Type| match debug_handle_type(ty) {
+        ("Box", ty_str) => format!("`{}`", ty_str),
+        (kind, ty_str) => format!("`{}<{}>`", kind, ty_str),
+    };
+    Some(match ty {
+        Type::Primitive(_) | Type::Enum{..} | Type::Iterator(_) => return None,
+        Type::RawPtr{..} =>
+            format!("`{}` is passed to the wrapped function unchecked.", name),
+        Type::Struct{..} | Type::Box(_) | Type::Arc(_) | Type::Rc(_)
+                if ownership == Ownership::Transfer => format!(
+            "`{}` must be a live {} handle owned by the caller. It is \
+            consumed, so it must not be used again, including by another \
+            thread.",
+            name, handle_name(ty),
+        ),
+        Type::Struct{..} | Type::Box(_) | Type::Arc(_) | Type::Rc(_) => format!(
+            "`{}` must be a live {} handle that is not mutated during the call.",
+            name, handle_name(ty),
+        ),
+        Type::Ref(inner_ty) | Type::RefMut(inner_ty)
+                if inner_ty.inner_struct().is_none() || ty.is_str() => format!(
+            "`{}` must point to a valid `{}` that is not mutated during the call.",
+            name, inner_ty.to_rust_str(),
+        ),
+        Type::Ref(inner_ty) | Type::RefMut(inner_ty)
+                if handle_lock(options, inner_ty) != HandleLock::None => format!(
+            "`{}` must be a live {} handle. It is locked for the duration of \
+            the call.",
+            name, handle_name(inner_ty),
+        ),
+        Type::Ref(inner_ty) => format!(
+            "`{}` must be a live {} handle or borrowed reference that is not \
+            mutated during the call.",
+            name, handle_name(inner_ty),
+        ),
+        Type::RefMut(inner_ty) => format!(
+            "`{}` must be a live {} handle or borrowed reference that is not \
+            used by another thread or argument during the call.",
+            name, handle_name(inner_ty),
+        ),
+        Type::Buffer(inner_ty) => format!(
+            "`{0}` must point to `{0}_len` consecutive valid `{1}` values that \
+            are not mutated during the call. It may be null if `{0}_len` is 0.",
+            name, inner_ty.to_rust_str(),
+        ),
+    })
+}
+
+/// Declares the function `unsafe` and documents what its pointer arguments
+/// must satisfy, if the options ask for unsafe functions and it takes any.
+fn add_safety_docs(
+    func_context: &mut FunctionContext,
+    options: &Options,
+    safety_docs: Vec<String>,
+) {
+    if !options.unsafe_functions || safety_docs.is_empty() {
+        return;
+    }
+    func_context.set_unsafe(true);
+    if func_context.has_docs() {
+        func_context.add_doc("");
+    }
+    func_context.add_doc("# Safety");
+    func_context.add_doc("");
+    for doc in safety_docs {
+        func_context.add_doc(&format!("- {}", doc));
+    }
+    if options.null_policy == NullPolicy::Unchecked {
+        func_context.add_doc("- None of the pointers may be null.");
+    }
+}
+
+/// Writes a return value named `value` to `return_ptr`, and `return_len_ptr`
+/// for buffers, converting it into its C type. `owner` is an expression for
+/// the handle that a returned reference borrows from.
+fn marshal_return(
+    compiler: &mut SerializationCompiler,
+    options: &Options,
+    ret_ty: &Type,
+    owner: &str,
+) -> Result<()> {
+    match ret_ty {
+        Type::Primitive(_) | Type::RawPtr{..} => {
+            compiler.add_unsafe_set("return_ptr", "value")?;
+        }
+        Type::Struct{..} | Type::Box(_) | Type::Arc(_) | Type::Rc(_) => {
+            compiler.add_def_with_let(false, None, "value",
+                &handle_into_raw(options, ret_ty, "value"))?;
+            add_debug_handle_call(compiler, "register", "value", ret_ty, None)?;
+            compiler.add_unsafe_set("return_ptr", "value as _")?;
+        }
+        // Shared handles point at the inner value, not at the `Arc` or `Rc`
+        Type::Ref(ty) | Type::RefMut(ty) if ty.is_shared() => {
+            let value = format!("{}::as_ptr(value)", shared_path(ty));
+            add_borrowed_handle_call(compiler, &value, ty, owner)?;
+            compiler.add_unsafe_set("return_ptr", &format!("{} as _", value))?;
+        }
+        Type::Ref(ty) => {
+            let value = format!("value as *const {}", ty.to_rust_str());
+            if is_borrowed_handle(ret_ty) {
+                add_borrowed_handle_call(compiler, &value, ty, owner)?;
             }
-            let args = vec!["self_".to_string()];
-            compiler.add_func_call(None, "Box::into_raw", args, false)?;
+            compiler.add_unsafe_set("return_ptr", &format!("{} as _", value))?;
+        },
+        Type::RefMut(ty) => {
+            let value = format!("value as *mut {}", ty.to_rust_str());
+            if is_borrowed_handle(ret_ty) {
+                add_borrowed_handle_call(compiler, &value, ty, owner)?;
+            }
+            compiler.add_unsafe_set("return_ptr", &format!("{} as _", value))?;
+        },
+        Type::Buffer(ty) => {
+            compiler.add_def_with_let(false,
+                Some("Vec<*mut _>".to_string()),
+                "value",
+                &format!("value.into_iter().map(|x| {}).collect()",
+                    handle_into_raw(options, ty, "x")))?;
+            let (kind, ty_str) = debug_handle_type(ty);
+            compiler.add_line(&format!(
+                "value.iter().for_each(|x| {}::register(*x as _, {:?}, {:?}));",
+                DEBUG_HANDLES_MOD, kind, ty_str))?;
+            compiler.add_func_call_with_let("value", None, None,
+               "Box::into_raw", vec!["Box::new(value)".to_string()],
+               false)?;
+            compiler.add_unsafe_set("return_ptr", "(*value).as_ptr() as _")?;
+            compiler.add_unsafe_set("return_len_ptr", "(*value).len()")?;
+        },
+        Type::Iterator(_) => {
+            compiler.add_def_with_let(false, Some(ret_ty.to_rust_str()),
+                "value", "Box::new(value.into_iter())")?;
+            compiler.add_def_with_let(false, None, "value",
+                &handle_into_raw(options, ret_ty, "value"))?;
+            add_debug_handle_call(compiler, "register", "value", ret_ty, None)?;
+            compiler.add_unsafe_set("return_ptr", "value as _")?;
+        }
+        Type::Enum{..} => unimplemented!(),
+    }
+    Ok(())
+}
+
+/// Adds `<extern_name>_iter_next` and `<extern_name>_iter_free` for the
+/// iterator handle returned by the extern function with the given name. The
+/// items must not be iterators themselves.
+fn add_iterator_functions(
+    compiler: &mut SerializationCompiler,
+    options: &Options,
+    interface: &mut Interface,
+    extern_name: &str,
+    item_ty: &Type,
+) -> Result<()> {
+    let iter_ty = Type::Iterator(Box::new(item_ty.clone()));
+
+    let mut args = vec![
+        FunctionArg::CArg(CArgInfo::arg("it", &iter_ty.to_c_str())),
+        FunctionArg::CArg(CArgInfo::ret_arg(&item_ty.to_c_str())),
+    ];
+    let mut ptr_args = vec!["it".to_string(), "return_ptr".to_string()];
+    if item_ty.is_buffer() {
+        args.push(FunctionArg::CArg(CArgInfo::ret_len_arg()));
+        ptr_args.push("return_len_ptr".to_string());
+    }
+    let next_name = format!("{}_iter_next", extern_name);
+    let mut func_context = FunctionContext::new_extern_c_with_ret(
+        &next_name, true, args, "bool",
+    );
+    func_context.add_doc("Advances the iterator, writing the next item to \
+        `return_ptr`. Returns false once the iterator is exhausted.");
+    add_safety_docs(&mut func_context, options, vec![
+        format!("`it` must be a live iterator handle returned by `{}` that is \
+            not used by another thread during the call.", extern_name),
+        if item_ty.is_buffer() {
+            "`return_ptr` and `return_len_ptr` must be valid for writes."
+                .to_string()
+        } else {
+            "`return_ptr` must be valid for writes.".to_string()
+        },
+    ]);
+    let iter_name = format!("{}_iter", extern_name);
+    let mut params = vec![
+        Param {
+            name: "it".to_string(),
+            ty: CType::Handle(iter_name.clone()),
+            c_str: iter_ty.to_c_str(),
+            role: ParamRole::SelfHandle(Ownership::Borrow),
+            free_with: None,
+            borrows: vec![],
+        },
+        Param {
+            name: "return_ptr".to_string(),
+            ty: CType::from_type(item_ty).out_ptr(),
+            c_str: format!("*mut {}", item_ty.to_c_str()),
+            role: ParamRole::Return(item_ty.ownership()),
+            free_with: match item_ty.ownership() {
+                Ownership::Borrow => None,
+                _ => handle_free_name(item_ty),
+            },
+            // Borrowed items come from whatever the iterator borrows from
+            borrows: match item_ty.ownership() {
+                Ownership::Borrow => vec!["it".to_string()],
+                _ => vec![],
+            },
+        },
+    ];
+    if item_ty.is_buffer() {
+        params.push(Param {
+            name: "return_len_ptr".to_string(),
+            ty: CType::Primitive("usize".to_string()).out_ptr(),
+            c_str: "*mut usize".to_string(),
+            role: ParamRole::ReturnLen,
+            free_with: None,
+            borrows: vec![],
+        });
+    }
+    interface.functions.push(ExternFunction {
+        name: next_name.clone(),
+        params,
+        ret: Some(CType::Primitive("bool".to_string())),
+        docs: func_context.docs().to_vec(),
+        kind: FunctionKind::IterNext(iter_name.clone()),
+    });
+    compiler.add_context(Context::Function(func_context))?;
+    add_null_checks(compiler, options, &next_name, &ptr_args, &[], false,
+        false)?;
+    add_debug_handle_call(compiler, "check", "it", &iter_ty, Some(&next_name))?;
+    compiler.add_unsafe_def_with_let(false, None, "it",
+        &format!("&mut *(it as *mut {})", iter_ty.to_rust_str()))?;
+    let match_context = MatchContext::new(
+        "it.next()",
+        vec!["Some(value)".to_string(), "None".to_string()],
+    );
+    compiler.add_context(Context::Match(match_context))?;
+    marshal_return(compiler, options, item_ty, "it as *const _ as _")?;
+    compiler.add_return_val("true", false)?;
+    compiler.pop_context()?;
+    compiler.add_return_val("false", false)?;
+    compiler.pop_context()?;
+    compiler.pop_context()?; // end of function
+    compiler.add_newline()?;
+
+    let args = vec![
+        FunctionArg::CArg(CArgInfo::arg("it", &iter_ty.to_c_str())),
+    ];
+    let free_name = format!("{}_iter_free", extern_name);
+    let mut func_context = FunctionContext::new_extern_c(
+        &free_name, true, args, false,
+    );
+    func_context.add_doc("Frees the iterator. The handle is invalid after \
+        this call.");
+    add_safety_docs(&mut func_context, options, vec![format!(
+        "`it` must be a live iterator handle returned by `{}`. It is consumed, \
+        so it must not be used again, including by another thread.",
+        extern_name,
+    )]);
+    interface.functions.push(ExternFunction {
+        name: free_name.clone(),
+        params: vec![Param {
+            name: "it".to_string(),
+            ty: CType::Handle(iter_name.clone()),
+            c_str: iter_ty.to_c_str(),
+            role: ParamRole::SelfHandle(Ownership::Transfer),
+            free_with: None,
+            borrows: vec![],
+        }],
+        ret: None,
+        docs: func_context.docs().to_vec(),
+        kind: FunctionKind::IterFree(iter_name),
+    });
+    compiler.add_context(Context::Function(func_context))?;
+    add_null_checks(compiler, options, &free_name, &["it".to_string()], &[],
+        false, true)?;
+    add_debug_handle_call(compiler, "release", "it", &iter_ty, Some(&free_name))?;
+    compiler.add_line(&format!("drop(unsafe {{ {} }});",
+        handle_from_raw(options, &Type::Box(Box::new(iter_ty.clone())), "it",
+            &free_name)))?;
+    compiler.pop_context()?;
+    compiler.add_newline()?;
+    Ok(())
+}
+
+/// Adds a constant of primitive or `&str` type. A literal primitive becomes
+/// a `pub const`, which cbindgen turns into a `#define`. A string becomes a
+/// `#[no_mangle] pub static` nul-terminated byte array. Any other value, such
+/// as a path to a constant in the wrapped crate, becomes a `#[no_mangle] pub
+/// static` so the C value always matches the Rust definition.
+pub fn add_constant(
+    compiler: &mut SerializationCompiler,
+    interface: &mut Interface,
+    name: &str,
+    ty: &Type,
+    value: &str,
+) -> Result<()> {
+    let expr = syn::parse_str::<syn::Expr>(value).ok();
+    let is_literal = expr.as_ref()
+        .and_then(types::literal_to_string)
+        .is_some();
+    match ty {
+        Type::Primitive(_) if is_literal => {
+            compiler.add_constant_def(true, name, &ty.to_rust_str(), value)?;
+            interface.constants.push(Constant {
+                name: name.to_string(),
+                ty: CType::from_type(ty),
+                value: ConstantValue::Literal(value.to_string()),
+            });
+        }
+        Type::Primitive(_) => {
+            compiler.add_line("#[no_mangle]")?;
+            compiler.add_line(&format!(
+                "pub static {}: {} = {};", name, ty.to_rust_str(), value))?;
+            interface.constants.push(Constant {
+                name: name.to_string(),
+                ty: CType::from_type(ty),
+                value: ConstantValue::Static,
+            });
+        }
+        ty if ty.is_str() => {
+            let value = match expr {
+                Some(syn::Expr::Lit(syn::ExprLit {
+                    lit: syn::Lit::Str(lit), ..
+                })) => lit.value(),
+                _ => bail!("String constant {} must have a literal value", name),
+            };
+            if value.contains('\0') {
+                bail!("String constant {} contains a nul byte", name);
+            }
+            let bytes = value.bytes()
+                .flat_map(std::ascii::escape_default)
+                .map(char::from)
+                .collect::<String>();
+            compiler.add_line("#[no_mangle]")?;
+            compiler.add_line(&format!(
+                "pub static {}: [u8; {}] = *b\"{}\\0\";",
+                name, value.len() + 1, bytes))?;
+            interface.constants.push(Constant {
+                name: name.to_string(),
+                ty: CType::Primitive("u8".to_string()),
+                value: ConstantValue::String(value.clone(), value.len() + 1),
+            });
+        }
+        _ => bail!("Constant {} must have a primitive or &str type", name),
+    }
+    compiler.add_newline()?;
+    Ok(())
+}
+
+/// Asserts at compile time that handles of the given type can be shared
+/// between C threads, i.e., that what they point to is `Send` and `Sync`.
+pub fn add_shareable_assertion(
+    compiler: &mut SerializationCompiler,
+    options: &Options,
+    ty: &Type,
+) -> Result<()> {
+    let ty_str = match ty {
+        Type::Arc(_) | Type::Rc(_) => ty.to_rust_str(),
+        _ => handle_pointee(options, ty),
+    };
+    compiler.add_line(&format!(
+        "const _: fn() = || {{ \
+            fn assert_shareable<T: ?Sized + Send + Sync>() {{}} \
+            assert_shareable::<{}>(); \
+        }};",
+        ty_str,
+    ))?;
+    compiler.add_newline()?;
+    Ok(())
+}
+
+/// The handle types in a function signature that need lifetime management
+/// functions: shared handles anywhere in the signature, and owned handles that
+/// are returned to C.
+pub fn handle_types(
+    struct_ty: Option<&Type>,
+    raw_args: &[(&str, Type)],
+    raw_ret: Option<&Type>,
+    ret_ownership: Ownership,
+) -> Vec<Type> {
+    let mut tys = vec![];
+    if let Some(ty) = struct_ty {
+        if ty.is_shared() {
+            tys.push(ty.clone());
         }
     }
-    for (i, (_, arg_ty)) in raw_args.iter().enumerate() {
+    for (_, arg_ty) in raw_args {
         match arg_ty {
-            Type::Primitive(_) => { continue; },
-            Type::Struct{..} => { continue; },
-            Type::Ref(_) => {
-                compiler.add_func_call(None, "Box::into_raw", vec![format!("arg{}", i)], false)?;
+            Type::Ref(ty) | Type::RefMut(ty) if ty.is_shared() => {
+                tys.push(*ty.clone());
+            }
+            ty if ty.is_shared() => tys.push(ty.clone()),
+            _ => {}
+        }
+    }
+    match raw_ret.map(|ty| (ty, ret_ownership)) {
+        Some((Type::Buffer(ty) | Type::Iterator(ty), _))
+                if ty.inner_struct().is_some() => {
+            tys.push(*ty.clone());
+        }
+        Some((Type::Ref(ty) | Type::RefMut(ty),
+                Ownership::BorrowClone | Ownership::Copy)) => {
+            tys.push(*ty.clone());
+        }
+        Some((ty, _)) if ty.inner_struct().is_some() => tys.push(ty.clone()),
+        _ => {}
+    }
+    tys
+}
+
+/// Adds the functions C needs to manage the lifetime of a handle type, if
+/// there are any: `<T>_retain` and `<T>_release` for an `Arc<T>` or `Rc<T>`,
+/// and `<T>_free` for a boxed `T`. `generated` tracks the functions that have
+/// already been added so each is only added once.
+pub fn add_handle_functions(
+    compiler: &mut SerializationCompiler,
+    options: &Options,
+    interface: &mut Interface,
+    generated: &mut HashMap<String, Type>,
+    ty: &Type,
+) -> Result<()> {
+    let (struct_name, inner_ty) = match ty.inner_struct() {
+        Some(Type::Struct { name, .. }) => (name, ty.inner_struct().unwrap()),
+        _ => return Ok(()),
+    };
+    let ty = match ty {
+        Type::Box(_) => inner_ty.clone(),
+        ty => ty.clone(),
+    };
+    let suffixes: &[&str] = if ty.is_shared() {
+        &["retain", "release"]
+    } else {
+        &["free"]
+    };
+    for suffix in suffixes {
+        let extern_name = format!("{}_{}", short_struct_name(struct_name),
+            suffix);
+        if let Some(existing_ty) = generated.get(&extern_name) {
+            if existing_ty != &ty {
+                bail!("{} is already defined for {}", extern_name,
+                    existing_ty.to_rust_str());
+            }
+            continue;
+        }
+        let mut func_context = FunctionContext::new_extern_c(
+            &extern_name, true, vec![FunctionArg::CSelfArg], false,
+        );
+        let (doc, line) = match *suffix {
+            "retain" => (
+                "Increments the strong reference count of the handle.".to_string(),
+                format!("unsafe {{ {}::increment_strong_count(self_ as *const {}) }};",
+                    shared_path(&ty), inner_ty.to_rust_str()),
+            ),
+            "release" => (
+                "Decrements the strong reference count of the handle. The \
+                handle is invalid after this call.".to_string(),
+                format!("drop(unsafe {{ {} }});",
+                    handle_from_raw(options, &ty, "self_", &extern_name)),
+            ),
+            _ => (
+                "Frees the handle. The handle is invalid after this call."
+                    .to_string(),
+                format!("drop(unsafe {{ {} }});",
+                    handle_from_raw(options, &Type::Box(Box::new(ty.clone())),
+                        "self_", &extern_name)),
+            ),
+        };
+        func_context.add_doc(&doc);
+        let safety_doc = if *suffix == "retain" {
+            arg_safety_doc(options, "self_", &ty, Ownership::BorrowClone)
+        } else {
+            arg_safety_doc(options, "self_", &ty, Ownership::Transfer)
+        };
+        add_safety_docs(&mut func_context, options,
+            safety_doc.into_iter().collect());
+        interface.functions.push(ExternFunction {
+            name: extern_name.clone(),
+            params: vec![Param {
+                name: "self_".to_string(),
+                ty: CType::from_type(&ty),
+                c_str: ty.to_c_str(),
+                role: ParamRole::SelfHandle(if *suffix == "retain" {
+                    Ownership::Borrow
+                } else {
+                    Ownership::Transfer
+                }),
+                free_with: None,
+                borrows: vec![],
+            }],
+            ret: None,
+            docs: func_context.docs().to_vec(),
+            kind: match *suffix {
+                "retain" => FunctionKind::Retain(struct_name.clone()),
+                "release" => FunctionKind::Release(struct_name.clone()),
+                _ => FunctionKind::Free(struct_name.clone()),
             },
-            Type::RefMut(_) => { continue; },
-            Type::Buffer(_) => { continue; },
-            Type::Enum{..} => { continue; },
+        });
+        compiler.add_context(Context::Function(func_context))?;
+        add_null_checks(compiler, options, &extern_name, &["self_".to_string()],
+            &[],
+            false, *suffix != "retain")?;
+        let hook = if *suffix == "retain" { "retain" } else { "release" };
+        add_debug_handle_call(compiler, hook, "self_", &ty, Some(&extern_name))?;
+        compiler.add_line(&line)?;
+        compiler.pop_context()?;
+        compiler.add_newline()?;
+        generated.insert(extern_name, ty.clone());
+    }
+    Ok(())
+}
+
+/// Checks that each of the given pointer arguments is not null, according to
+/// the null policy. Buffer arguments may be null when their length is 0.
+/// Functions that free a handle instead return early.
+fn add_null_checks(
+    compiler: &mut SerializationCompiler,
+    options: &Options,
+    extern_name: &str,
+    ptr_args: &[String],
+    buffer_args: &[String],
+    use_error_code: bool,
+    is_free: bool,
+) -> Result<()> {
+    if options.null_policy == NullPolicy::Unchecked {
+        return Ok(());
+    }
+    for ptr_arg in ptr_args {
+        let condition = if buffer_args.contains(ptr_arg) {
+            format!("{0}.is_null() && {0}_len != 0", ptr_arg)
+        } else {
+            format!("{}.is_null()", ptr_arg)
         };
+        let loop_context = LoopContext::new(vec![
+            LoopBranch::ifbranch(&condition),
+        ]);
+        compiler.add_context(Context::Loop(loop_context))?;
+        if is_free {
+            compiler.add_return_val("", true)?;
+        } else if use_error_code
+                && options.null_policy == NullPolicy::ReturnError {
+            compiler.add_return_val(&NULL_POINTER_ERROR.to_string(), true)?;
+        } else {
+            compiler.add_macro_call("eprintln", vec![format!(
+                "\"{}: `{}` is null\"", extern_name, ptr_arg)])?;
+            compiler.add_func_call(None, "std::process::abort", vec![], false)?;
+        }
+        compiler.pop_context()?;
     }
+    Ok(())
+}
 
-    if use_error_code {
-        compiler.add_line("0")?;
+/// Whether a reference type points to a struct that C holds a handle to, and
+/// so can be checked against the debug handle registry.
+fn is_borrowed_handle(ty: &Type) -> bool {
+    match ty {
+        Type::Ref(inner_ty) | Type::RefMut(inner_ty) =>
+            !ty.is_str() && inner_ty.inner_struct().is_some(),
+        _ => false,
     }
+}
 
-    compiler.pop_context()?; // end of function
-    compiler.add_newline()?;
+/// How the debug handle registry describes a handle of the given type: the
+/// pointer that owns it and the type it points to.
+fn debug_handle_type(ty: &Type) -> (&'static str, String) {
+    match ty {
+        Type::Box(inner_ty) => ("Box", inner_ty.to_rust_str()),
+        Type::Arc(inner_ty) => ("Arc", inner_ty.to_rust_str()),
+        Type::Rc(inner_ty) => ("Rc", inner_ty.to_rust_str()),
+        _ => ("Box", ty.to_rust_str()),
+    }
+}
+
+/// Reports a handle of the given type to the debug handle registry. `hook`
+/// is one of the registry functions, and `extern_name` is the function the
+/// handle was passed to, if it is an argument.
+fn add_debug_handle_call(
+    compiler: &mut SerializationCompiler,
+    hook: &str,
+    ptr: &str,
+    ty: &Type,
+    extern_name: Option<&str>,
+) -> Result<()> {
+    let (kind, ty_str) = debug_handle_type(ty);
+    let mut args = vec![format!("{} as _", ptr)];
+    if matches!(hook, "register" | "retain" | "release") {
+        args.push(format!("{:?}", kind));
+    }
+    args.push(format!("{:?}", ty_str));
+    if let Some(extern_name) = extern_name {
+        args.push(format!("{:?}", extern_name));
+    }
+    compiler.add_func_call(None, &format!("{}::{}", DEBUG_HANDLES_MOD, hook),
+        args, false)?;
     Ok(())
 }
+
+/// Reports a reference of the given type lent to C to the debug handle
+/// registry, tied to the handle that `owner` points to.
+fn add_borrowed_handle_call(
+    compiler: &mut SerializationCompiler,
+    ptr: &str,
+    ty: &Type,
+    owner: &str,
+) -> Result<()> {
+    let (_, ty_str) = debug_handle_type(ty);
+    compiler.add_func_call(None,
+        &format!("{}::register_borrowed", DEBUG_HANDLES_MOD),
+        vec![format!("{} as _", ptr), format!("{:?}", ty_str), owner.to_string()],
+        false)?;
+    Ok(())
+}
+
+fn shared_path(ty: &Type) -> &'static str {
+    match ty {
+        Type::Arc(_) => "std::sync::Arc",
+        Type::Rc(_) => "std::rc::Rc",
+        _ => unreachable!(),
+    }
+}
+
+/// The lock around the value behind a C handle of the given type.
+fn handle_lock(options: &Options, ty: &Type) -> HandleLock {
+    match ty {
+        Type::Struct { name, .. } => options.handle_locks
+            .get(short_struct_name(name))
+            .copied()
+            .unwrap_or_default(),
+        Type::Box(ty) => handle_lock(options, ty),
+        _ => HandleLock::None,
+    }
+}
+
+/// The type a C handle of the given type points to.
+fn handle_pointee(options: &Options, ty: &Type) -> String {
+    let inner_ty = match ty {
+        Type::Box(ty) | Type::Arc(ty) | Type::Rc(ty) => ty,
+        ty => ty,
+    };
+    match handle_lock(options, ty) {
+        HandleLock::None => inner_ty.to_rust_str(),
+        HandleLock::Mutex =>
+            format!("std::sync::Mutex<{}>", inner_ty.to_rust_str()),
+        HandleLock::RwLock =>
+            format!("std::sync::RwLock<{}>", inner_ty.to_rust_str()),
+    }
+}
+
+/// Borrows the value behind a C handle of the given type. A locked handle is
+/// locked, and the expression evaluates to the guard. `func` is the extern
+/// function the handle was passed to.
+fn handle_borrow(
+    options: &Options,
+    ty: &Type,
+    ptr: &str,
+    is_mut: bool,
+    func: &str,
+) -> String {
+    let pointee = handle_pointee(options, ty);
+    match (handle_lock(options, ty), is_mut) {
+        (HandleLock::None, false) =>
+            format!("unsafe {{ &*({} as *const {}) }}", ptr, pointee),
+        (HandleLock::None, true) =>
+            format!("unsafe {{ &mut *({} as *mut {}) }}", ptr, pointee),
+        (lock, is_mut) => format!(
+            "unsafe {{ &*({} as *const {}) }}.{}().{}",
+            ptr, pointee,
+            match (lock, is_mut) {
+                (HandleLock::RwLock, false) => "read",
+                (HandleLock::RwLock, true) => "write",
+                _ => "lock",
+            },
+            unwrap_lock(ptr, func),
+        ),
+    }
+}
+
+/// Takes ownership of the value behind a C handle of the given type. `func`
+/// is the extern function the handle was passed to.
+fn handle_from_raw(options: &Options, ty: &Type, ptr: &str, func: &str) -> String {
+    let pointee = handle_pointee(options, ty);
+    match (ty, handle_lock(options, ty)) {
+        (Type::Box(_), HandleLock::None) =>
+            format!("Box::from_raw({} as *mut {})", ptr, pointee),
+        (Type::Box(_), _) => format!(
+            "Box::new(Box::from_raw({} as *mut {}).into_inner().{})",
+            ptr, pointee, unwrap_lock(ptr, func),
+        ),
+        (Type::Arc(_) | Type::Rc(_), _) => format!(
+            "{}::from_raw({} as *const {})",
+            shared_path(ty), ptr, pointee,
+        ),
+        (_, HandleLock::None) =>
+            format!("*Box::from_raw({} as *mut {})", ptr, pointee),
+        (_, _) => format!("Box::from_raw({} as *mut {}).into_inner().{}",
+            ptr, pointee, unwrap_lock(ptr, func)),
+    }
+}
+
+/// Unwraps the result of locking the handle `ptr` passed to `func`. A lock is
+/// poisoned by a panic while it is held, which cannot unwind out of an extern
+/// function, so abort rather than panic.
+fn unwrap_lock(ptr: &str, func: &str) -> String {
+    format!(
+        "unwrap_or_else(|_| {{ \
+        eprintln!(\"{}: the lock of `{}` is poisoned\"); \
+        std::process::abort() }})",
+        func, ptr,
+    )
+}
+
+/// Leaks a value of the given type into a C handle.
+fn handle_into_raw(options: &Options, ty: &Type, value: &str) -> String {
+    let lock = match handle_lock(options, ty) {
+        HandleLock::None => None,
+        HandleLock::Mutex => Some("std::sync::Mutex"),
+        HandleLock::RwLock => Some("std::sync::RwLock"),
+    };
+    match (ty, lock) {
+        (Type::Box(_), None) => format!("Box::into_raw({})", value),
+        (Type::Box(_), Some(lock)) =>
+            format!("Box::into_raw(Box::new({}::new(*{})))", lock, value),
+        (Type::Arc(inner_ty) | Type::Rc(inner_ty), _) => format!(
+            "{}::into_raw({}) as *mut {}",
+            shared_path(ty), value, inner_ty.to_rust_str(),
+        ),
+        (_, None) => format!("Box::into_raw(Box::new({}))", value),
+        (_, Some(lock)) =>
+            format!("Box::into_raw(Box::new({}::new({})))", lock, value),
+    }
+}
@@ -153,6 +153,8 @@ pub struct FunctionContext {
     started: bool,
     func_lifetime: Option<String>,
     where_clause: Option<String>,
+    docs: Vec<String>,
+    is_unsafe: bool,
 }
 
 impl FunctionContext {
@@ -170,6 +172,8 @@ impl FunctionContext {
             ret_type: ret_type,
             func_lifetime: None,
             where_clause: None,
+            docs: vec![],
+            is_unsafe: false,
         }
     }
 
@@ -185,6 +189,17 @@ impl FunctionContext {
         func_context
     }
 
+    pub fn new_extern_c_with_ret(
+        name: &str,
+        is_pub: bool,
+        args: Vec<FunctionArg>,
+        ret: &str,
+    ) -> Self {
+        let mut func_context = Self::new(name, is_pub, args, ret);
+        func_context.is_extern_c = true;
+        func_context
+    }
+
     pub fn new_with_lifetime(
         name: &str,
         is_pub: bool,
@@ -209,19 +224,52 @@ impl FunctionContext {
             ret_type: ret_type,
             func_lifetime: lifetime,
             where_clause: Some(where_clause.to_string()),
+            docs: vec![],
+            is_unsafe: false,
         }
     }
+
+    /// Adds a line to the doc comment emitted above the function signature.
+    pub fn add_doc(&mut self, line: &str) {
+        self.docs.push(line.to_string());
+    }
+
+    /// The lines of the doc comment.
+    pub fn docs(&self) -> &[String] {
+        &self.docs
+    }
+
+    /// Whether any doc comment lines have been added.
+    pub fn has_docs(&self) -> bool {
+        !self.docs.is_empty()
+    }
+
+    /// Sets whether the function is declared `unsafe`.
+    pub fn set_unsafe(&mut self, is_unsafe: bool) {
+        self.is_unsafe = is_unsafe;
+    }
 }
 
 impl ContextPop for FunctionContext {
     fn pop(&mut self) -> Result<(String, bool)> {
         if !self.started {
             self.started = true;
+            let doc_str = self.docs.iter()
+                .map(|line| if line.is_empty() {
+                    "///\n".to_string()
+                } else {
+                    format!("/// {}\n", line)
+                })
+                .collect::<String>();
             let inline_str = "#[inline]";
             let is_pub_str = match self.is_pub {
                 true => "pub ".to_string(),
                 false => "".to_string(),
             };
+            let unsafe_str = match self.is_unsafe {
+                true => "unsafe ",
+                false => "",
+            };
             let (no_mangle_str, extern_c_str) = match self.is_extern_c {
                 true => ("\n#[no_mangle]".to_string(), "extern \"C\" ".to_string()),
                 false => ("".to_string(), "".to_string()),
@@ -244,10 +292,12 @@ impl ContextPop for FunctionContext {
             };
             Ok((
                 format!(
-                    "{}{}\n{}{}fn {}{}({}) {} {} {{",
+                    "{}{}{}\n{}{}{}fn {}{}({}) {} {} {{",
+                    doc_str,
                     inline_str,
                     no_mangle_str,
                     is_pub_str,
+                    unsafe_str,
                     extern_c_str,
                     self.name,
                     lifetime_str,
@@ -682,6 +732,7 @@ impl ContextPop for Context {
     }
 }
 
+#[derive(Clone, Default)]
 pub struct SerializationCompiler {
     current_string: String,
     current_context: Vec<Context>,
@@ -714,6 +765,20 @@ impl SerializationCompiler {
         Ok(())
     }
 
+    /// Adds `use <dependency>` at the top of the file, after any inner
+    /// attributes.
+    pub fn prepend_dependency(&mut self, dependency: &str) -> Result<()> {
+        let mut offset = 0;
+        for line in self.current_string.split_inclusive('\n') {
+            if !line.trim_start().starts_with("#![") {
+                break;
+            }
+            offset += line.len();
+        }
+        self.current_string.insert_str(offset, &format!("use {};\n", dependency));
+        Ok(())
+    }
+
     pub fn add_macro_call(&mut self, macro_name: &str, args: Vec<String>) -> Result<()> {
         let line = format!("{}!({});", macro_name, args.join(", "));
         self.add_line(&line)?;
@@ -1,17 +1,43 @@
+pub mod bindings;
 pub mod codegen;
 pub mod compiler;
+pub mod manifest;
 pub mod types;
 
-use std::{fs, path::{Path, PathBuf}};
-use color_eyre::eyre::{Result, ErrReport};
-use types::{Type, SelfType, DerivedTrait};
+use std::{collections::HashMap, fs, path::{Path, PathBuf}};
+use color_eyre::eyre::{bail, Result, WrapErr};
+use manifest::{CargoManifestConfig, Dependency};
+use types::{
+    Type, SelfType, DerivedTrait, NullPolicy, HandleLock, Language, CrateType,
+    Ownership,
+};
 
+#[derive(Default)]
 pub struct CDylibCompiler {
     pub inner: compiler::SerializationCompiler,
     pub package_name: String,
     pub package_name_c: String,
     pub package_folder: PathBuf,
-    crates: Vec<String>,
+    crates: Vec<Dependency>,
+    source_dependencies: Vec<Dependency>,
+    imports: Vec<String>,
+    item_paths: HashMap<String, String>,
+    method_traits: HashMap<String, String>,
+    used_items: Vec<String>,
+    /// The crates that the paths in bound types and calls start with.
+    used_crates: Vec<String>,
+    handle_functions: HashMap<String, Type>,
+    /// The typedef names of the handles asserted to be shareable.
+    shareable_handles: Vec<String>,
+    options: codegen::Options,
+    interface: bindings::Interface,
+    languages: Vec<Language>,
+    crate_type: CrateType,
+    manifest_config: CargoManifestConfig,
+    docs: HashMap<String, String>,
+    next_doc: Option<String>,
+    next_arg_ownership: HashMap<String, Ownership>,
+    next_ret_ownership: Option<Ownership>,
     dummy: bool,
 }
 
@@ -25,14 +51,7 @@ impl CDylibCompiler {
     /// "<CDYLIB_ROOT>/<CDYLIB_NAME>-c".
     pub fn new() -> Self {
         if std::env::var("CDYLIB").is_ok() {
-            return Self {
-                inner: compiler::SerializationCompiler::new(),
-                package_name: "".to_string(),
-                package_name_c: "".to_string(),
-                package_folder: Path::new("").to_path_buf(),
-                crates: vec![],
-                dummy: true,
-            };
+            return Self { dummy: true, ..Default::default() };
         }
         let package_name = std::env::var("CDYLIB_NAME").expect("set \
             CYDLIB_NAME envvar to the name of the original Rust crate");
@@ -43,7 +62,7 @@ impl CDylibCompiler {
 
     /// Initialize a compiler for generating a cdylib crate at the given output
     /// folder. The compiler will also generate a build.rs file that uses
-    /// cbindgen to output a header file.
+    /// cbindgen to output a header file, unless `Language::C` is added.
     pub fn new_with_output_folder(
         package_name: &str,
         output_folder: &str,
@@ -52,13 +71,11 @@ impl CDylibCompiler {
         let package_name_c =
             format!("{}-c", str::replace(&package_name, "_", "-"));
         CDylibCompiler {
-            inner: compiler::SerializationCompiler::new(),
             package_folder:
                 Path::new(output_folder).join(&package_name_c).to_path_buf(),
             package_name,
             package_name_c,
-            crates: vec![],
-            dummy: false,
+            ..Default::default()
         }
     }
 
@@ -71,51 +88,414 @@ impl CDylibCompiler {
         if self.dummy {
             return;
         }
-        self.crates.push(format!("{} = \"{}\"", crate_name, version));
+        self.crates.push(Dependency::new(crate_name).version(version));
+    }
+
+    /// Add a crate to the Cargo.toml. Fails if the dependency has no source
+    /// or conflicting sources, e.g., both a path and a git repository.
+    pub fn add_dependency(&mut self, dependency: Dependency) -> Result<()> {
+        if self.dummy {
+            return Ok(());
+        }
+        dependency.validate()?;
+        self.crates.push(dependency);
+        Ok(())
     }
 
-    /// Add a crate to the Cargo.toml.
-    pub fn add_dependency(
+    /// Adds the crate whose Cargo.toml is at `manifest_path` as a path
+    /// dependency, and reads its `[dependencies]`. `flush` adds each of them
+    /// that the generated code uses, by importing from it with `import` or
+    /// by a path in a bound type or call, e.g., `bumpalo::Bump`, with the
+    /// same spec as in the crate, unless a dependency of the same name was
+    /// added directly.
+    pub fn add_dependencies_from_manifest(
         &mut self,
-        crate_name: &str,
-        kv: Vec<(&str, &str)>,
+        manifest_path: &Path,
     ) -> Result<()> {
         if self.dummy {
             return Ok(());
         }
-        if !kv.is_empty() {
-            self.crates.push(format!(
-                "{} = {{ {} }}",
-                crate_name,
-                kv.into_iter().map(|(k, v)| format!("{} = {}", k, v))
-                    .collect::<Vec<_>>().join(","),
-            ));
-            Ok(())
-        } else {
-            Err(ErrReport::msg("Cargo.toml crate requires at least one key."))
+        let (package, dependencies) =
+            manifest::read_manifest_dependencies(manifest_path)?;
+        self.crates.push(package);
+        self.source_dependencies.extend(dependencies);
+        Ok(())
+    }
+
+    /// Sets the kinds of library the generated crate builds. For a static
+    /// library, `make build` records the native libraries that consumers
+    /// must also link, and `make install` adds them to the pkg-config and
+    /// CMake package files. Defaults to `CrateType::Cdylib`.
+    pub fn set_crate_type(&mut self, crate_type: CrateType) {
+        self.crate_type = crate_type;
+    }
+
+    /// Sets the package metadata, release profile, features, patches and
+    /// target-specific dependencies of the generated Cargo.toml. Fails if any
+    /// of its dependencies is invalid.
+    pub fn set_manifest_config(
+        &mut self,
+        manifest_config: CargoManifestConfig,
+    ) -> Result<()> {
+        manifest_config.validate()?;
+        self.manifest_config = manifest_config;
+        Ok(())
+    }
+
+    /// Sets how functions added after this call treat null pointer
+    /// arguments. Defaults to `NullPolicy::Unchecked`.
+    pub fn set_null_policy(&mut self, null_policy: NullPolicy) {
+        self.options.null_policy = null_policy;
+    }
+
+    /// Sets whether the generated functions that take pointers are declared
+    /// `unsafe`, with a `# Safety` section in their doc comments describing
+    /// what each pointer must satisfy. Defaults to false. All functions must
+    /// agree, so this must be called before any function is added, and fails
+    /// otherwise.
+    pub fn set_unsafe_functions(
+        &mut self,
+        unsafe_functions: bool,
+    ) -> Result<()> {
+        if !self.interface.functions.is_empty() {
+            bail!("Cannot set whether functions are unsafe after adding \
+                functions");
+        }
+        self.options.unsafe_functions = unsafe_functions;
+        Ok(())
+    }
+
+    /// Has `flush` write a header or bindings in the given language. Adding
+    /// C++ again replaces its error style. None are written by default.
+    pub fn add_language(&mut self, language: Language) {
+        let kind = std::mem::discriminant(&language);
+        self.languages.retain(|l| std::mem::discriminant(l) != kind);
+        self.languages.push(language);
+    }
+
+    /// The extern C interface of the functions and constants added so far.
+    pub fn interface(&self) -> &bindings::Interface {
+        &self.interface
+    }
+
+    /// Sets the lock around boxed handles to the given struct, by its name
+    /// without a path, which lets C share them between threads. Every
+    /// function that passes the handles must agree on the lock, so this fails
+    /// if a function or assertion using boxed handles to the struct has
+    /// already been added.
+    pub fn set_handle_lock(
+        &mut self,
+        struct_name: &str,
+        lock: HandleLock,
+    ) -> Result<()> {
+        if self.interface.handle_names().iter()
+                .chain(&self.shareable_handles)
+                .any(|name| name == struct_name) {
+            bail!("Cannot set the lock around {} after adding functions that \
+                use it", struct_name);
+        }
+        self.options.handle_locks.insert(struct_name.to_string(), lock);
+        Ok(())
+    }
+
+    /// Asserts at compile time that handles of the given type can be shared
+    /// between threads, taking into account any lock set for the struct.
+    pub fn assert_shareable(&mut self, ty: Type) -> Result<()> {
+        if self.dummy {
+            return Ok(());
         }
+        codegen::add_shareable_assertion(&mut self.inner, &self.options, &ty)?;
+        self.shareable_handles.extend(bindings::handle_name(&ty));
+        Ok(())
     }
 
     /// Add a dependency to the generated .rs file e.g., `use <dependency>`.
+    /// Names it imports take precedence over those imported automatically.
     pub fn import(&mut self, dependency: &str) -> Result<()> {
         if self.dummy {
             return Ok(());
         }
         self.inner.add_dependency(dependency)?;
+        self.imports.push(dependency.to_string());
+        Ok(())
+    }
+
+    /// Sets the doc comment of the next extern function wrapper added,
+    /// overriding any doc comment loaded from source. Notes on the out-pointer,
+    /// error code and ownership are appended to it.
+    pub fn set_doc(&mut self, doc: &str) {
+        self.next_doc = Some(doc.to_string());
+    }
+
+    /// Sets how the argument with the given name of the next extern function
+    /// wrapper added is passed, overriding the ownership implied by its type,
+    /// e.g., to clone a handle argument rather than consume it.
+    pub fn set_arg_ownership(&mut self, arg_name: &str, ownership: Ownership) {
+        self.next_arg_ownership.insert(arg_name.to_string(), ownership);
+    }
+
+    /// Sets how the return value of the next extern function wrapper added is
+    /// passed, e.g., to clone or copy a returned reference into a new handle.
+    pub fn set_ret_ownership(&mut self, ownership: Ownership) {
+        self.next_ret_ownership = Some(ownership);
+    }
+
+    /// Records the paths of the public structs, enums, traits and functions
+    /// in the given source file and the public modules it declares, under
+    /// `module_path`, e.g., the crate name for its lib.rs, along with the
+    /// trait that defines each method of a trait impl. `flush` imports those
+    /// that the wrappers refer to: the types in their signatures, the
+    /// standalone functions they call, and the traits of the methods they
+    /// call.
+    pub fn add_paths_from_source(
+        &mut self,
+        module_path: &str,
+        source_file: &Path,
+    ) -> Result<()> {
+        if self.dummy {
+            return Ok(());
+        }
+        let file = syn::parse_file(&fs::read_to_string(source_file)?)?;
+        let is_mod_root = matches!(
+            source_file.file_name().and_then(|name| name.to_str()),
+            Some("lib.rs" | "main.rs" | "mod.rs"));
+        let source_dir = source_file.parent().unwrap_or(Path::new(""));
+        let mod_dir = if is_mod_root {
+            source_dir.to_path_buf()
+        } else {
+            source_file.with_extension("")
+        };
+        self.add_paths_from_items(module_path, &file.items, &mod_dir)
+    }
+
+    /// Records the paths of the items of a module whose submodule files are in
+    /// `mod_dir`.
+    fn add_paths_from_items(
+        &mut self,
+        module_path: &str,
+        items: &[syn::Item],
+        mod_dir: &Path,
+    ) -> Result<()> {
+        for item in items {
+            let (vis, ident) = match item {
+                syn::Item::Struct(item) => (&item.vis, &item.ident),
+                syn::Item::Enum(item) => (&item.vis, &item.ident),
+                syn::Item::Trait(item) => (&item.vis, &item.ident),
+                syn::Item::Fn(item) => (&item.vis, &item.sig.ident),
+                syn::Item::Type(item) => (&item.vis, &item.ident),
+                syn::Item::Impl(item) => {
+                    self.add_method_traits(item);
+                    continue;
+                }
+                syn::Item::Mod(item) => {
+                    if !matches!(item.vis, syn::Visibility::Public(_)) {
+                        continue;
+                    }
+                    let path = format!("{}::{}", module_path, item.ident);
+                    let dir = mod_dir.join(item.ident.to_string());
+                    match &item.content {
+                        Some((_, items)) =>
+                            self.add_paths_from_items(&path, items, &dir)?,
+                        None => {
+                            let file = mod_dir.join(format!("{}.rs", item.ident));
+                            let file = if file.exists() {
+                                file
+                            } else {
+                                dir.join("mod.rs")
+                            };
+                            let file = syn::parse_file(&fs::read_to_string(&file)?)?;
+                            self.add_paths_from_items(&path, &file.items, &dir)?;
+                        }
+                    }
+                    continue;
+                }
+                _ => continue,
+            };
+            if matches!(vis, syn::Visibility::Public(_)) {
+                self.item_paths.insert(ident.to_string(),
+                    format!("{}::{}", module_path, ident));
+            }
+        }
         Ok(())
     }
 
+    /// Records the trait that defines each method of a trait impl.
+    fn add_method_traits(&mut self, item: &syn::ItemImpl) {
+        let trait_name = match item.trait_.as_ref()
+                .and_then(|(_, path, _)| path.segments.last()) {
+            Some(segment) => segment.ident.to_string(),
+            None => return,
+        };
+        let struct_name = match &*item.self_ty {
+            syn::Type::Path(ty) => match ty.path.segments.last() {
+                Some(segment) => segment.ident.to_string(),
+                None => return,
+            },
+            _ => return,
+        };
+        for impl_item in &item.items {
+            if let syn::ImplItem::Method(method) = impl_item {
+                self.method_traits.insert(
+                    format!("{}::{}", struct_name, method.sig.ident),
+                    trait_name.clone());
+            }
+        }
+    }
+
+    /// Records the items that a wrapper refers to, to import them at `flush`.
+    fn add_used_items(
+        &mut self,
+        struct_ty: Option<&Type>,
+        func_call: &str,
+        raw_args: &[(&str, Type)],
+        raw_ret: Option<&Type>,
+    ) {
+        let mut names = struct_ty.into_iter()
+            .chain(raw_args.iter().map(|(_, arg_ty)| arg_ty))
+            .chain(raw_ret)
+            .flat_map(Type::named_types)
+            .map(str::to_string)
+            .collect::<Vec<_>>();
+        match struct_ty.and_then(Type::inner_struct) {
+            Some(Type::Struct { name, .. }) => names.extend(self.method_traits
+                .get(&format!("{}::{}", bindings::short_struct_name(name),
+                    func_call))
+                .cloned()),
+            _ => names.push(func_call.to_string()),
+        }
+        for name in names {
+            // A path needs no import, but its crate must be a dependency
+            if let Some((root, _)) = name.trim_start_matches("::")
+                    .split_once("::") {
+                if !self.used_crates.iter().any(|used| used == root) {
+                    self.used_crates.push(root.to_string());
+                }
+            } else if !self.used_items.contains(&name) {
+                self.used_items.push(name);
+            }
+        }
+    }
+
+    /// The paths of the used items found by `add_paths_from_source`, except
+    /// those already imported and those whose names an import already
+    /// brings into scope.
+    fn auto_imports(&self) -> Result<Vec<String>> {
+        let mut imported_names = vec![];
+        for import in &self.imports {
+            let item: syn::ItemUse =
+                syn::parse_str(&format!("use {};", import)).wrap_err(
+                    format!("Failed to parse import {:?}", import))?;
+            imported_names.extend(types::use_tree_names(&item.tree, None));
+        }
+        Ok(self.used_items.iter()
+            .filter(|name| !imported_names.contains(name))
+            .filter_map(|name| self.item_paths.get(name))
+            .filter(|path| !self.imports.contains(path))
+            .cloned()
+            .collect())
+    }
+
+    /// Loads the doc comments of every `pub fn` and every method in an `impl`
+    /// block of the given source file. Extern function wrappers added later
+    /// for these functions copy the doc comment.
+    pub fn add_docs_from_source(&mut self, source_file: &Path) -> Result<()> {
+        if self.dummy {
+            return Ok(());
+        }
+        let file = syn::parse_file(&fs::read_to_string(source_file)?)?;
+        for item in file.items {
+            match item {
+                syn::Item::Fn(item) => {
+                    if !matches!(item.vis, syn::Visibility::Public(_)) {
+                        continue;
+                    }
+                    if let Some(doc) = types::doc_from_attrs(&item.attrs) {
+                        self.docs.insert(item.sig.ident.to_string(), doc);
+                    }
+                }
+                syn::Item::Impl(item) => {
+                    let struct_name = match &*item.self_ty {
+                        syn::Type::Path(ty) => match ty.path.segments.last() {
+                            Some(segment) => segment.ident.to_string(),
+                            None => continue,
+                        },
+                        _ => continue,
+                    };
+                    for impl_item in item.items {
+                        let method = match impl_item {
+                            syn::ImplItem::Method(method) => method,
+                            _ => continue,
+                        };
+                        if let Some(doc) = types::doc_from_attrs(&method.attrs) {
+                            self.docs.insert(format!("{}::{}", struct_name,
+                                method.sig.ident), doc);
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+
+    /// The dependencies of the generated crate: those added directly, and
+    /// those read from a manifest that an import, or a path in a bound type
+    /// or call, refers to.
+    fn dependencies(&self) -> Vec<Dependency> {
+        let imported_crates = self.imports.iter()
+            .filter_map(|import| import.trim_start_matches("::")
+                .split("::")
+                .next())
+            .chain(self.used_crates.iter().map(String::as_str))
+            .collect::<Vec<_>>();
+        let mut dependencies = self.crates.clone();
+        for dependency in &self.source_dependencies {
+            let crate_name = dependency.name().replace('-', "_");
+            if imported_crates.contains(&crate_name.as_str())
+                    && !dependencies.iter()
+                        .any(|added| added.name() == dependency.name()) {
+                dependencies.push(dependency.clone());
+            }
+        }
+        dependencies
+    }
+
+    /// The doc comment of the next extern function wrapper, given the
+    /// function it calls, e.g., `Struct::method`.
+    fn take_doc(&mut self, func_path: &str) -> Option<String> {
+        self.next_doc.take().or_else(|| self.docs.get(func_path).cloned())
+    }
+
+    /// The ownership of each argument and the return value of the next extern
+    /// function wrapper, with any ownership set for it.
+    fn take_ownership(
+        &mut self,
+        raw_args: &[(&str, Type)],
+        raw_ret: Option<&Type>,
+    ) -> Result<(Vec<Ownership>, Ownership)> {
+        codegen::resolve_ownership(raw_args, raw_ret,
+            std::mem::take(&mut self.next_arg_ownership),
+            self.next_ret_ownership.take())
+    }
+
     /// Adds an extern C function wrapper around a method on a struct.
     /// The default name is <struct_name>_<func_call>, where the name of the
-    /// struct omits the parameterized types.
+    /// struct omits the parameterized types, and has a `Shared` suffix for a
+    /// shared handle.
     ///
     /// Params:
-    /// - `struct_ty`: The struct on which the function is defined.
+    /// - `struct_ty`: The struct on which the function is defined. A
+    ///   `Type::Arc` or `Type::Rc` struct is called through a shared handle,
+    ///   whose C typedef is `<struct_name>Shared`.
     /// - `func_call`: The name of the function call on the struct.
     /// - `self_ty`: If the function call has a self argument, whether it is
     ///    mutable and/or a reference.
     /// - `raw_args`: The names and types of the function call arguments.
+    ///   Call `set_arg_ownership` first to override whether an argument is
+    ///   consumed, borrowed, cloned or copied.
     /// - `raw_ret`: The function call return value type, if there is one.
+    ///   Call `set_ret_ownership` first to clone or copy a returned reference
+    ///   into a new handle.
     /// - `use_error_code`: Whether the function call returns a Result.
     ///
     /// TODO: Parse these options directly from the function specification.
@@ -131,20 +511,35 @@ impl CDylibCompiler {
         if self.dummy {
             return Ok(());
         }
-        assert!(struct_ty.is_struct());
-        let struct_name = match struct_ty {
-            Type::Struct { ref name, .. } => name,
+        assert!(struct_ty.inner_struct().is_some());
+        let struct_name = match struct_ty.inner_struct() {
+            Some(Type::Struct { name, .. }) =>
+                bindings::short_struct_name(name).to_string(),
             _ => unreachable!(),
         };
+        let doc = self.take_doc(&format!("{}::{}", struct_name, func_call));
+        let (arg_ownership, ret_ownership) =
+            self.take_ownership(&raw_args, raw_ret.as_ref())?;
+        self.add_used_items(Some(&struct_ty), func_call, &raw_args,
+            raw_ret.as_ref());
+        let handle_tys = codegen::handle_types(
+            Some(&struct_ty), &raw_args, raw_ret.as_ref(), ret_ownership);
+        let handle_name = bindings::handle_name(&struct_ty).unwrap();
         codegen::add_extern_c_function(
             &mut self.inner,
-            &format!("{}_{}", struct_name, func_call),
+            &self.options,
+            &mut self.interface,
+            &format!("{}_{}", handle_name, func_call),
             Some((struct_ty, self_ty)),
             func_call,
             raw_args,
+            arg_ownership,
             raw_ret,
+            ret_ownership,
             use_error_code,
+            doc.as_deref(),
         )?;
+        self.add_handle_functions(handle_tys)?;
         Ok(())
     }
 
@@ -162,16 +557,33 @@ impl CDylibCompiler {
         if self.dummy {
             return Ok(());
         }
-        assert!(struct_ty.is_struct());
+        let struct_name = match struct_ty.inner_struct() {
+            Some(Type::Struct { name, .. }) =>
+                bindings::short_struct_name(name).to_string(),
+            _ => unreachable!(),
+        };
+        let doc = self.take_doc(&format!("{}::{}", struct_name, func_call));
+        let (arg_ownership, ret_ownership) =
+            self.take_ownership(&raw_args, raw_ret.as_ref())?;
+        self.add_used_items(Some(&struct_ty), func_call, &raw_args,
+            raw_ret.as_ref());
+        let handle_tys = codegen::handle_types(
+            Some(&struct_ty), &raw_args, raw_ret.as_ref(), ret_ownership);
         codegen::add_extern_c_function(
             &mut self.inner,
+            &self.options,
+            &mut self.interface,
             extern_name,
             Some((struct_ty, self_ty)),
             func_call,
             raw_args,
+            arg_ownership,
             raw_ret,
+            ret_ownership,
             use_error_code,
+            doc.as_deref(),
         )?;
+        self.add_handle_functions(handle_tys)?;
         Ok(())
     }
 
@@ -188,15 +600,109 @@ impl CDylibCompiler {
         if self.dummy {
             return Ok(());
         }
+        let doc = self.take_doc(
+            func_call.rsplit("::").next().unwrap_or(func_call));
+        let (arg_ownership, ret_ownership) =
+            self.take_ownership(&raw_args, raw_ret.as_ref())?;
+        self.add_used_items(None, func_call, &raw_args, raw_ret.as_ref());
+        let handle_tys = codegen::handle_types(
+            None, &raw_args, raw_ret.as_ref(), ret_ownership);
         codegen::add_extern_c_function(
             &mut self.inner,
+            &self.options,
+            &mut self.interface,
             extern_name,
             None,
             func_call,
             raw_args,
+            arg_ownership,
             raw_ret,
+            ret_ownership,
             use_error_code,
+            doc.as_deref(),
         )?;
+        self.add_handle_functions(handle_tys)?;
+        Ok(())
+    }
+
+    /// Exposes a constant to C.
+    ///
+    /// Params:
+    /// - `name`: The name of the constant in C.
+    /// - `ty`: The type of the constant, either a primitive or `&str`.
+    /// - `value`: The value of the constant as a Rust expression. A literal
+    ///   is emitted as a `pub const` (a `#define` in the header), and
+    ///   anything else, e.g., `mlx5_datapath::MTU`, as a `#[no_mangle] pub
+    ///   static` that tracks the Rust definition. Strings must be literals,
+    ///   and are emitted as static nul-terminated byte arrays.
+    pub fn add_constant(
+        &mut self,
+        name: &str,
+        ty: Type,
+        value: &str,
+    ) -> Result<()> {
+        if self.dummy {
+            return Ok(());
+        }
+        codegen::add_constant(&mut self.inner, &mut self.interface, name, &ty,
+            value)?;
+        Ok(())
+    }
+
+    /// Exposes every `pub const` of primitive or `&str` type in the given
+    /// source file to C, under the same name. Literal values are copied, and
+    /// other values reference the constant at `<module_path>::<name>`.
+    ///
+    /// Constants of other types, and `&str` constants whose value is not a
+    /// string literal (e.g., `env!("CARGO_PKG_VERSION")`), are skipped.
+    /// Returns the names of the skipped constants.
+    pub fn add_constants_from_source(
+        &mut self,
+        module_path: &str,
+        source_file: &Path,
+    ) -> Result<Vec<String>> {
+        if self.dummy {
+            return Ok(vec![]);
+        }
+        let file = syn::parse_file(&fs::read_to_string(source_file)?)?;
+        let mut skipped = vec![];
+        for item in file.items {
+            let item = match item {
+                syn::Item::Const(item) => item,
+                _ => continue,
+            };
+            if !matches!(item.vis, syn::Visibility::Public(_)) {
+                continue;
+            }
+            let name = item.ident.to_string();
+            let ty = match types::constant_type(&item.ty) {
+                Some(ty) => ty,
+                None => {
+                    skipped.push(name);
+                    continue;
+                }
+            };
+            let is_str_literal = matches!(*item.expr, syn::Expr::Lit(
+                syn::ExprLit { lit: syn::Lit::Str(_), .. }));
+            if ty.is_str() && !is_str_literal {
+                skipped.push(name);
+                continue;
+            }
+            let value = types::literal_to_string(&item.expr)
+                .unwrap_or_else(|| format!("{}::{}", module_path, name));
+            codegen::add_constant(&mut self.inner, &mut self.interface, &name,
+                &ty, &value)?;
+        }
+        Ok(skipped)
+    }
+
+    /// Adds the lifetime management functions for the given handle types
+    /// that have not already been added.
+    fn add_handle_functions(&mut self, tys: Vec<Type>) -> Result<()> {
+        for ty in tys {
+            codegen::add_handle_functions(&mut self.inner, &self.options,
+                &mut self.interface, &mut self.handle_functions, &ty)?;
+        }
         Ok(())
     }
 
@@ -222,7 +728,21 @@ impl CDylibCompiler {
     /// package-name-c/
     ///     src/
     ///         lib.rs
-    ///     build.rs
+    ///         debug_handles.rs
+    ///     build.rs            (unless the C header is written)
+    ///     package_name.h      (for C, C++ and Go)
+    ///     package_name.hpp    (for C++)
+    ///     package_name.py     (for Python)
+    ///     package_name.cs     (for C#)
+    ///     package_name.lua    (for Lua)
+    ///     go/
+    ///         package_name.go (for Go)
+    ///     java/package_name/
+    ///         PackageName.java (for Java)
+    ///     package_name.pc.in
+    ///     package_name.pc
+    ///     PackageNameConfig.cmake
+    ///     Makefile
     ///     Cargo.toml
     pub fn flush(&mut self) -> Result<()> {
         if self.dummy {
@@ -231,14 +751,70 @@ impl CDylibCompiler {
         let src_folder = self.package_folder.join("src");
         fs::create_dir_all(&src_folder)?;
 
-        codegen::gen_makefile(&self.package_folder)?;
-        codegen::gen_build_rs(&self.package_name, &self.package_folder)?;
+        let native_header = self.languages.iter()
+            .any(Language::includes_c_header);
+        codegen::gen_makefile(&self.package_name, &self.package_folder,
+            self.crate_type)?;
+        codegen::gen_pkg_config(&self.package_name, &self.package_folder,
+            self.crate_type, &self.manifest_config.version)?;
+        codegen::gen_cmake_config(&self.package_name, &self.package_folder,
+            self.crate_type)?;
+        if native_header {
+            // A build.rs left by an earlier flush would overwrite the header
+            let build_rs = self.package_folder.join("build.rs");
+            if build_rs.exists() {
+                fs::remove_file(build_rs)?;
+            }
+        } else {
+            codegen::gen_build_rs(&self.package_name, &self.package_folder)?;
+        }
         codegen::gen_cargo_toml(&self.package_name, &self.package_folder,
-            &self.crates)?;
+            &self.dependencies(), !native_header, self.crate_type,
+            &self.manifest_config)?;
+
+        codegen::gen_debug_handles(&self.package_folder)?;
 
         let lib_file = src_folder.join("lib.rs");
-        self.inner.flush(&lib_file)?;
+        // Imports are prepended to a copy, so flushing again adds them once
+        let mut inner = self.inner.clone();
+        for import in self.auto_imports()?.into_iter().rev() {
+            inner.prepend_dependency(&import)?;
+        }
+        inner.flush(&lib_file)?;
+        let mut debug_compiler = compiler::SerializationCompiler::new();
+        let mut interface = self.interface.clone();
+        codegen::add_debug_handles_functions(&mut debug_compiler,
+            &mut interface, &self.package_name)?;
+        debug_compiler.append_flush(&lib_file)?;
         compiler::run_rustfmt(&lib_file)?;
+
+        if native_header {
+            bindings::c::gen_header(&self.package_name, &self.package_folder,
+                &interface)?;
+        }
+        for language in &self.languages {
+            match *language {
+                Language::C => {}
+                Language::Cpp(error_style) =>
+                    bindings::cpp::gen_header(&self.package_name,
+                        &self.package_folder, &interface, error_style)?,
+                Language::Python =>
+                    bindings::python::gen_module(&self.package_name,
+                        &self.package_folder, &interface)?,
+                Language::Go =>
+                    bindings::go::gen_package(&self.package_name,
+                        &self.package_folder, &interface)?,
+                Language::CSharp =>
+                    bindings::csharp::gen_bindings(&self.package_name,
+                        &self.package_folder, &interface)?,
+                Language::Java =>
+                    bindings::java::gen_bindings(&self.package_name,
+                        &self.package_folder, &interface)?,
+                Language::Lua =>
+                    bindings::lua::gen_module(&self.package_name,
+                        &self.package_folder, &interface)?,
+            }
+        }
         Ok(())
     }
 }
@@ -0,0 +1,437 @@
+//! Settings for the Cargo.toml of the generated crate.
+
+use color_eyre::eyre::{bail, eyre, Result};
+use std::{fs, path::Path};
+
+/// The contents of the generated Cargo.toml beyond its library target and
+/// dependencies. The default reproduces the manifest ffiber generates
+/// without a config.
+#[derive(Clone, Debug)]
+pub struct CargoManifestConfig {
+    /// `package.version`, also used by the pkg-config file.
+    pub version: String,
+    /// `package.edition`.
+    pub edition: String,
+    /// `package.authors`.
+    pub authors: Vec<String>,
+    /// `package.description`.
+    pub description: Option<String>,
+    /// `package.license`, as an SPDX expression.
+    pub license: Option<String>,
+    /// `package.repository`.
+    pub repository: Option<String>,
+    /// The version of cbindgen to build the header with, unless the header is
+    /// written natively.
+    pub cbindgen_version: String,
+    /// `[profile.release]` settings.
+    pub release_profile: ReleaseProfile,
+    /// Features of the generated crate and the features of its dependencies
+    /// that each enables, e.g., `("mlx5", vec!["mlx5-datapath/mlx5"])` to
+    /// pass the `mlx5` feature through to the wrapped crate.
+    pub features: Vec<(String, Vec<String>)>,
+    /// `[patch.<source>]` sections, by source, e.g., `crates-io`, each with
+    /// the dependencies that replace those from the source.
+    pub patches: Vec<(String, Vec<Dependency>)>,
+    /// `[target.<target>.dependencies]` tables, by target, e.g.,
+    /// `cfg(target_os = "linux")`.
+    pub target_dependencies: Vec<(String, Vec<Dependency>)>,
+}
+
+impl Default for CargoManifestConfig {
+    fn default() -> Self {
+        CargoManifestConfig {
+            version: "0.1.0".to_string(),
+            edition: "2021".to_string(),
+            authors: vec![],
+            description: None,
+            license: None,
+            repository: None,
+            cbindgen_version: "0.23.0".to_string(),
+            release_profile: ReleaseProfile::default(),
+            features: vec![],
+            patches: vec![],
+            target_dependencies: vec![],
+        }
+    }
+}
+
+impl CargoManifestConfig {
+    /// Checks every dependency in the patches and target-specific tables.
+    pub fn validate(&self) -> Result<()> {
+        for (_, dependencies) in self.patches.iter()
+                .chain(&self.target_dependencies) {
+            for dependency in dependencies {
+                dependency.validate()?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// `[profile.release]` settings. Unset settings keep Cargo's defaults, and the
+/// section is omitted if none are set.
+#[derive(Clone, Debug, Default)]
+pub struct ReleaseProfile {
+    pub lto: Option<Lto>,
+    pub codegen_units: Option<u32>,
+    pub panic: Option<PanicStrategy>,
+}
+
+impl ReleaseProfile {
+    /// Whether any setting is set.
+    pub fn is_empty(&self) -> bool {
+        self.lto.is_none() && self.codegen_units.is_none()
+            && self.panic.is_none()
+    }
+}
+
+/// The `lto` setting of a profile.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum Lto {
+    Off,
+    Thin,
+    Fat,
+}
+
+impl Lto {
+    /// The value of the setting in Cargo.toml.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Lto::Off => "off",
+            Lto::Thin => "thin",
+            Lto::Fat => "fat",
+        }
+    }
+}
+
+/// The `panic` setting of a profile.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum PanicStrategy {
+    Unwind,
+    Abort,
+}
+
+impl PanicStrategy {
+    /// The value of the setting in Cargo.toml.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            PanicStrategy::Unwind => "unwind",
+            PanicStrategy::Abort => "abort",
+        }
+    }
+}
+
+/// A dependency of the generated crate, written as one line of Cargo.toml,
+/// e.g., `Dependency::new("bumpalo").git("https://...").features(&["collections"])`.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Dependency {
+    name: String,
+    version: Option<String>,
+    path: Option<String>,
+    git: Option<String>,
+    rev: Option<String>,
+    branch: Option<String>,
+    tag: Option<String>,
+    features: Vec<String>,
+    default_features: Option<bool>,
+    optional: bool,
+    package: Option<String>,
+}
+
+impl Dependency {
+    /// A dependency named `name` in the generated crate, which is also the
+    /// name of the package unless it is renamed with `package`.
+    pub fn new(name: &str) -> Self {
+        Dependency { name: name.to_string(), ..Default::default() }
+    }
+
+    /// The version requirement, e.g., `1.0`.
+    pub fn version(mut self, version: &str) -> Self {
+        self.version = Some(version.to_string());
+        self
+    }
+
+    /// A local path to the package.
+    pub fn path(mut self, path: &str) -> Self {
+        self.path = Some(path.to_string());
+        self
+    }
+
+    /// The URL of a git repository containing the package.
+    pub fn git(mut self, url: &str) -> Self {
+        self.git = Some(url.to_string());
+        self
+    }
+
+    /// The git commit to use.
+    pub fn rev(mut self, rev: &str) -> Self {
+        self.rev = Some(rev.to_string());
+        self
+    }
+
+    /// The git branch to use.
+    pub fn branch(mut self, branch: &str) -> Self {
+        self.branch = Some(branch.to_string());
+        self
+    }
+
+    /// The git tag to use.
+    pub fn tag(mut self, tag: &str) -> Self {
+        self.tag = Some(tag.to_string());
+        self
+    }
+
+    /// Features of the package to enable.
+    pub fn features(mut self, features: &[&str]) -> Self {
+        self.features.extend(features.iter().map(|feature| feature.to_string()));
+        self
+    }
+
+    /// Whether to enable the default features of the package.
+    pub fn default_features(mut self, default_features: bool) -> Self {
+        self.default_features = Some(default_features);
+        self
+    }
+
+    /// Whether the dependency is only built when a feature enables it.
+    pub fn optional(mut self, optional: bool) -> Self {
+        self.optional = optional;
+        self
+    }
+
+    /// The name of the package, if it differs from the dependency name.
+    pub fn package(mut self, package: &str) -> Self {
+        self.package = Some(package.to_string());
+        self
+    }
+
+    /// The name of the dependency.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Checks that the dependency has exactly one source and at most one git
+    /// reference, which requires a git source.
+    pub fn validate(&self) -> Result<()> {
+        if self.name.is_empty() {
+            bail!("dependency has an empty name");
+        }
+        if self.path.is_some() && self.git.is_some() {
+            bail!("dependency {} cannot have both a path and a git source",
+                self.name);
+        }
+        if self.version.is_none() && self.path.is_none() && self.git.is_none() {
+            bail!("dependency {} needs a version, path or git source",
+                self.name);
+        }
+        let git_refs = [&self.rev, &self.branch, &self.tag].iter()
+            .filter(|git_ref| git_ref.is_some())
+            .count();
+        if git_refs > 0 && self.git.is_none() {
+            bail!("dependency {} has a rev, branch or tag but no git source",
+                self.name);
+        }
+        if git_refs > 1 {
+            bail!("dependency {} can only have one of a rev, branch or tag",
+                self.name);
+        }
+        if let Some(feature) = self.features.iter()
+                .find(|feature| feature.is_empty() || feature.contains(' ')) {
+            bail!("dependency {} has an invalid feature {:?}", self.name,
+                feature);
+        }
+        Ok(())
+    }
+
+    /// The line declaring the dependency in Cargo.toml. Call `validate` first.
+    pub fn to_toml(&self) -> String {
+        let mut kv = vec![];
+        for (key, value) in [
+            ("package", &self.package),
+            ("version", &self.version),
+            ("path", &self.path),
+            ("git", &self.git),
+            ("rev", &self.rev),
+            ("branch", &self.branch),
+            ("tag", &self.tag),
+        ] {
+            if let Some(value) = value {
+                kv.push(format!("{} = {}", key, toml_string(value)));
+            }
+        }
+        if !self.features.is_empty() {
+            kv.push(format!("features = [{}]", self.features.iter()
+                .map(|feature| toml_string(feature))
+                .collect::<Vec<_>>()
+                .join(", ")));
+        }
+        if let Some(default_features) = self.default_features {
+            kv.push(format!("default-features = {}", default_features));
+        }
+        if self.optional {
+            kv.push("optional = true".to_string());
+        }
+        let name = toml_key(&self.name);
+        match (kv.len(), &self.version) {
+            (1, Some(version)) => format!("{} = {}", name, toml_string(version)),
+            _ => format!("{} = {{ {} }}", name, kv.join(", ")),
+        }
+    }
+}
+
+/// Reads the Cargo.toml at `manifest_path`, and returns its package as a path
+/// dependency along with its `[dependencies]`, with relative paths resolved
+/// and `workspace = true` dependencies taken from the enclosing workspace.
+/// Optional dependencies are made required, since the generated crate uses
+/// them unconditionally.
+pub fn read_manifest_dependencies(
+    manifest_path: &Path,
+) -> Result<(Dependency, Vec<Dependency>)> {
+    let manifest = read_toml(manifest_path)?;
+    let crate_dir = fs::canonicalize(manifest_path)?
+        .parent()
+        .map(Path::to_path_buf)
+        .ok_or_else(|| eyre!("{} has no parent", manifest_path.display()))?;
+    let name = manifest.get("package")
+        .and_then(|package| package.get("name"))
+        .and_then(toml::Value::as_str)
+        .ok_or_else(|| eyre!("{} has no package name",
+            manifest_path.display()))?;
+    let package = Dependency::new(name)
+        .path(&crate_dir.display().to_string());
+
+    let mut dependencies = vec![];
+    let table = match manifest.get("dependencies") {
+        Some(toml::Value::Table(table)) => table.clone(),
+        Some(_) => bail!("{} has an invalid [dependencies] table",
+            manifest_path.display()),
+        None => toml::value::Table::new(),
+    };
+    for (name, spec) in &table {
+        let inherits = spec.get("workspace")
+            .and_then(toml::Value::as_bool)
+            .unwrap_or(false);
+        let mut dependency = if inherits {
+            let (workspace_dir, workspace_spec) =
+                workspace_dependency(&crate_dir, name)?;
+            merge_dependency_toml(Dependency::new(name), &workspace_spec,
+                &workspace_dir)?
+        } else {
+            Dependency::new(name)
+        };
+        dependency = merge_dependency_toml(dependency, spec, &crate_dir)?;
+        dependency.validate()?;
+        dependencies.push(dependency);
+    }
+    Ok((package, dependencies))
+}
+
+/// Finds the spec of a dependency in `[workspace.dependencies]` of the
+/// nearest enclosing workspace, and the workspace directory.
+fn workspace_dependency(
+    crate_dir: &Path,
+    name: &str,
+) -> Result<(std::path::PathBuf, toml::Value)> {
+    for dir in crate_dir.ancestors() {
+        let manifest_path = dir.join("Cargo.toml");
+        if !manifest_path.exists() {
+            continue;
+        }
+        let manifest = read_toml(&manifest_path)?;
+        let workspace = match manifest.get("workspace") {
+            Some(workspace) => workspace,
+            None => continue,
+        };
+        return match workspace.get("dependencies")
+                .and_then(|dependencies| dependencies.get(name)) {
+            Some(spec) => Ok((dir.to_path_buf(), spec.clone())),
+            None => bail!("{} is not in [workspace.dependencies] of {}", name,
+                manifest_path.display()),
+        };
+    }
+    bail!("{} inherits from a workspace, but {} is not in one", name,
+        crate_dir.display())
+}
+
+fn read_toml(path: &Path) -> Result<toml::Value> {
+    Ok(toml::from_str(&fs::read_to_string(path)?)?)
+}
+
+/// Sets the keys of a dependency spec in Cargo.toml on a `Dependency`,
+/// resolving a relative path against `base_dir`. Features are added to those
+/// already set.
+fn merge_dependency_toml(
+    mut dependency: Dependency,
+    spec: &toml::Value,
+    base_dir: &Path,
+) -> Result<Dependency> {
+    let table = match spec {
+        toml::Value::String(version) => return Ok(dependency.version(version)),
+        toml::Value::Table(table) => table,
+        _ => bail!("dependency {} has an invalid spec", dependency.name),
+    };
+    for (key, value) in table {
+        let invalid = || eyre!("dependency {} has an invalid {}",
+            dependency.name, key);
+        match key.as_str() {
+            "version" | "git" | "rev" | "branch" | "tag" | "package" => {
+                let value = value.as_str().ok_or_else(invalid)?.to_string();
+                let field = match key.as_str() {
+                    "version" => &mut dependency.version,
+                    "git" => &mut dependency.git,
+                    "rev" => &mut dependency.rev,
+                    "branch" => &mut dependency.branch,
+                    "tag" => &mut dependency.tag,
+                    _ => &mut dependency.package,
+                };
+                *field = Some(value);
+            }
+            "path" => {
+                let path = base_dir.join(value.as_str().ok_or_else(invalid)?);
+                dependency.path = Some(path.display().to_string());
+            }
+            "features" => {
+                for feature in value.as_array().ok_or_else(invalid)? {
+                    let feature = feature.as_str().ok_or_else(invalid)?;
+                    if !dependency.features.iter().any(|f| f == feature) {
+                        dependency.features.push(feature.to_string());
+                    }
+                }
+            }
+            "default-features" | "default_features" => dependency
+                .default_features = Some(value.as_bool().ok_or_else(invalid)?),
+            "workspace" | "optional" => {}
+            _ => bail!("dependency {} has unsupported key {}", dependency.name,
+                key),
+        }
+    }
+    Ok(dependency)
+}
+
+/// Quotes a string as a TOML basic string.
+pub fn toml_string(value: &str) -> String {
+    let mut quoted = String::from("\"");
+    for c in value.chars() {
+        match c {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            '\n' => quoted.push_str("\\n"),
+            '\t' => quoted.push_str("\\t"),
+            c if c.is_control() =>
+                quoted.push_str(&format!("\\u{:04X}", c as u32)),
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+/// Writes a TOML key, quoting it unless it is a bare key.
+pub fn toml_key(key: &str) -> String {
+    if !key.is_empty() && key.chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-') {
+        key.to_string()
+    } else {
+        toml_string(key)
+    }
+}
@@ -6,6 +6,14 @@ pub enum Type {
     RefMut(Box<Type>),
     Buffer(Box<Type>),
     Enum { name: String, variants: Vec<String> },
+    RawPtr { ty: Box<Type>, is_mut: bool },
+    Box(Box<Type>),
+    Arc(Box<Type>),
+    Rc(Box<Type>),
+    /// An iterator over items of the inner type, returned from methods with
+    /// an `impl Iterator` or `impl IntoIterator` return type (or any other
+    /// `IntoIterator` whose iterator is `'static`).
+    Iterator(Box<Type>),
 }
 
 #[derive(Clone, PartialEq, Eq, Hash, Debug)]
@@ -17,6 +25,148 @@ pub enum SelfType {
     ValueMut,
 }
 
+/// How generated functions treat null pointer arguments, including handles,
+/// buffers, and the `return_ptr` and `return_len_ptr` out-parameters. Raw
+/// pointer arguments are passed through unchecked, and a buffer argument may
+/// be null under every policy when its length is 0. Under a checking policy,
+/// functions that free a handle do nothing when given null, like `free()`.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug, Default)]
+pub enum NullPolicy {
+    /// Functions that return an error code return
+    /// `codegen::NULL_POINTER_ERROR`. Other functions abort.
+    ReturnError,
+    /// Print the name of the function and the null argument, and abort.
+    Abort,
+    /// Do not check for null pointers.
+    #[default]
+    Unchecked,
+}
+
+/// How the generated C++ wrappers report a non-zero error code.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug, Default)]
+pub enum CppErrorStyle {
+    /// Throw the error code as an exception.
+    #[default]
+    Exceptions,
+    /// Return a C++23 `std::expected` holding the result or the error code.
+    Expected,
+}
+
+/// A language that `flush` writes a header or bindings for, alongside the
+/// generated crate.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum Language {
+    /// A C header, `<package_name>.h`, written by ffiber itself instead of a
+    /// build.rs that runs cbindgen. The header exists as soon as the crate
+    /// is generated, and the crate has no build dependencies.
+    C,
+    /// A C++ header, `<package_name>.hpp`, that wraps each handle type in a
+    /// move-only class that frees the handle on destruction. Error codes are
+    /// reported in the given style.
+    Cpp(CppErrorStyle),
+    /// A Python module, `<package_name>.py`, that loads the library with
+    /// `ctypes`, declares the signature of every function, and wraps each
+    /// handle type in a class that frees the handle when garbage collected.
+    /// Non-zero error codes raise `Error`.
+    Python,
+    /// A Go package, `go/<package_name>.go`, that calls the library through
+    /// cgo and wraps each handle type in a struct with a finalizer that frees
+    /// the handle. Functions that use an error code return a Go `error`.
+    Go,
+    /// C# bindings, `<package_name>.cs`, that declare every function with
+    /// `[DllImport]` and wrap each handle type in a `SafeHandle` that frees
+    /// the handle. Non-zero error codes throw a `NativeException`.
+    CSharp,
+    /// Java bindings, `java/<package_name>/<PackageName>.java`, that call
+    /// every function through the Foreign Function & Memory API and wrap each
+    /// handle type in an `AutoCloseable` class that frees the handle.
+    /// Non-zero error codes throw a `NativeException`.
+    Java,
+    /// A LuaJIT module, `<package_name>.lua`, that declares every function in
+    /// an `ffi.cdef` block and wraps each handle type in a metatable whose
+    /// handles are freed by an `ffi.gc` finalizer. Non-zero error codes raise
+    /// an `Error`.
+    Lua,
+}
+
+impl Language {
+    /// Whether the output includes the C header, so that adding the language
+    /// also writes it.
+    pub fn includes_c_header(&self) -> bool {
+        matches!(self, Language::C | Language::Cpp(_) | Language::Go)
+    }
+}
+
+/// The kinds of library the generated crate builds.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug, Default)]
+pub enum CrateType {
+    /// A shared library, e.g., `lib<package_name>_c.so`.
+    #[default]
+    Cdylib,
+    /// A static library, `lib<package_name>_c.a`. Consumers must also link
+    /// the native libraries it depends on.
+    Staticlib,
+    /// Both a shared and a static library.
+    Both,
+}
+
+impl CrateType {
+    /// The `crate-type` values in the generated Cargo.toml.
+    pub fn cargo_crate_types(&self) -> &'static [&'static str] {
+        match self {
+            CrateType::Cdylib => &["cdylib"],
+            CrateType::Staticlib => &["staticlib"],
+            CrateType::Both => &["cdylib", "staticlib"],
+        }
+    }
+
+    /// Whether the crate builds a shared library.
+    pub fn is_shared(&self) -> bool {
+        *self != CrateType::Staticlib
+    }
+
+    /// Whether the crate builds a static library.
+    pub fn is_static(&self) -> bool {
+        *self != CrateType::Cdylib
+    }
+}
+
+/// Who owns a handle passed to or returned from a generated function. Without
+/// an annotation, this is implied by the type: values are transferred,
+/// references are borrowed, and primitives are copied.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum Ownership {
+    /// Ownership moves across the call. An argument handle is consumed, and a
+    /// returned handle must be freed by the caller.
+    Transfer,
+    /// The value is lent to the callee for the duration of the call, or for a
+    /// returned reference, to the caller for as long as the value it was
+    /// borrowed from.
+    Borrow,
+    /// The value behind the handle is cloned. An argument handle stays owned
+    /// by the caller, and a returned reference becomes a new handle that the
+    /// caller must free.
+    BorrowClone,
+    /// Like `BorrowClone`, but copies the value of a `Copy` type.
+    Copy,
+}
+
+/// The lock around the value behind a boxed handle, which lets C share the
+/// handle between threads. Methods taking `&self` lock it for reading, and
+/// methods taking `&mut self` for writing. `Arc` and `Rc` handles are never
+/// locked.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug, Default)]
+pub enum HandleLock {
+    /// The handle points directly at the value.
+    #[default]
+    None,
+    /// The handle points at a `std::sync::Mutex`, so every call is exclusive.
+    Mutex,
+    /// The handle points at a `std::sync::RwLock`, so calls taking `&self`
+    /// may run concurrently.
+    RwLock,
+}
+
 #[derive(Clone, PartialEq, Eq, Hash, Debug)]
 pub enum DerivedTrait {
     Default,
@@ -64,6 +214,36 @@ impl Type {
         Type::RefMut(Box::new(Type::new_struct(struct_name)))
     }
 
+    pub fn new_ptr(ty: Type) -> Self {
+        Type::RawPtr { ty: Box::new(ty), is_mut: false }
+    }
+
+    pub fn new_ptr_mut(ty: Type) -> Self {
+        Type::RawPtr { ty: Box::new(ty), is_mut: true }
+    }
+
+    pub fn new_box(struct_name: &str) -> Self {
+        Type::Box(Box::new(Type::new_struct(struct_name)))
+    }
+
+    pub fn new_arc(struct_name: &str) -> Self {
+        Type::Arc(Box::new(Type::new_struct(struct_name)))
+    }
+
+    pub fn new_rc(struct_name: &str) -> Self {
+        Type::Rc(Box::new(Type::new_struct(struct_name)))
+    }
+
+    /// The ownership implied by the type as an argument.
+    pub fn ownership(&self) -> Ownership {
+        match self {
+            Type::Primitive(_) | Type::Enum{..} | Type::RawPtr{..} =>
+                Ownership::Copy,
+            Type::Ref(_) | Type::RefMut(_) | Type::Buffer(_) => Ownership::Borrow,
+            _ => Ownership::Transfer,
+        }
+    }
+
     pub fn new_u8_buffer() -> Self {
         Type::Buffer(Box::new(Type::Primitive("u8".to_string())))
     }
@@ -82,6 +262,45 @@ impl Type {
         }
     }
 
+    /// Whether the type is a string slice, `&str`.
+    pub fn is_str(&self) -> bool {
+        match self {
+            Type::Ref(ty) => matches!(&**ty,
+                Type::Struct { name, .. } if name == "str"),
+            _ => false,
+        }
+    }
+
+    /// Whether the type is a reference-counted handle i.e., an `Arc<T>` or
+    /// `Rc<T>`, whose C pointer points directly at the inner `T`.
+    pub fn is_shared(&self) -> bool {
+        matches!(self, Type::Arc(_) | Type::Rc(_))
+    }
+
+    /// The struct behind a C handle, unwrapping any `Box`, `Arc` or `Rc`.
+    pub fn inner_struct(&self) -> Option<&Type> {
+        match self {
+            Type::Struct{..} => Some(self),
+            Type::Box(ty) | Type::Arc(ty) | Type::Rc(ty) => ty.inner_struct(),
+            _ => None,
+        }
+    }
+
+    /// The names of the structs and enums in the type, including its generic
+    /// arguments, which the generated code refers to.
+    pub fn named_types(&self) -> Vec<&str> {
+        match self {
+            Type::Primitive(_) => vec![],
+            Type::Struct { name, args } => std::iter::once(name.as_str())
+                .chain(args.iter().flat_map(|arg| arg.named_types()))
+                .collect(),
+            Type::Enum { name, .. } => vec![name.as_str()],
+            Type::Ref(ty) | Type::RefMut(ty) | Type::Buffer(ty)
+                | Type::RawPtr { ty, .. } | Type::Box(ty) | Type::Arc(ty)
+                | Type::Rc(ty) | Type::Iterator(ty) => ty.named_types(),
+        }
+    }
+
     pub fn to_c_str(&self) -> String {
         match self {
             Type::Primitive(ty) => ty.clone(),
@@ -90,6 +309,11 @@ impl Type {
             Type::RefMut(_) => "*mut ::std::os::raw::c_void".to_string(),
             Type::Buffer(ty) => format!("*const {}", ty.to_c_str()),
             Type::Enum{..} => "usize".to_string(),
+            Type::RawPtr{..} => self.to_rust_str(),
+            Type::Box(_) => "*mut ::std::os::raw::c_void".to_string(),
+            Type::Arc(_) => "*mut ::std::os::raw::c_void".to_string(),
+            Type::Rc(_) => "*mut ::std::os::raw::c_void".to_string(),
+            Type::Iterator(_) => "*mut ::std::os::raw::c_void".to_string(),
         }
     }
 
@@ -111,12 +335,32 @@ impl Type {
                 ty => ty.to_rust_str(),
             }),
             Type::Enum { name, .. } => name.clone(),
+            Type::RawPtr { ty, is_mut } => format!(
+                "*{} {}",
+                if *is_mut { "mut" } else { "const" },
+                ty.to_rust_str(),
+            ),
+            Type::Box(ty) => format!("Box<{}>", ty.to_rust_str()),
+            Type::Arc(ty) => format!("std::sync::Arc<{}>", ty.to_rust_str()),
+            Type::Rc(ty) => format!("std::rc::Rc<{}>", ty.to_rust_str()),
+            // References yielded through a C handle are unbounded by any Rust
+            // lifetime, so the boxed iterator type uses 'static
+            Type::Iterator(ty) => format!(
+                "Box<dyn Iterator<Item = {}>>",
+                match &**ty {
+                    Type::Ref(ty) => format!("&'static {}", ty.to_rust_str()),
+                    Type::RefMut(ty) =>
+                        format!("&'static mut {}", ty.to_rust_str()),
+                    ty => ty.to_rust_str(),
+                },
+            ),
         }
     }
 }
 
-static PRIMITIVE_TYPES: [&str; 10] = [
-    "usize", "u8", "u16", "u32", "u64", "i8", "i16", "i32", "i64", "bool",
+static PRIMITIVE_TYPES: [&str; 13] = [
+    "usize", "isize", "u8", "u16", "u32", "u64", "i8", "i16", "i32", "i64",
+    "f32", "f64", "bool",
 ];
 
 impl From<syn::Receiver> for SelfType {
@@ -163,7 +407,33 @@ impl From<syn::Type> for Type {
             syn::Type::Array(_) => unimplemented!("Array"),
             syn::Type::BareFn(_) => unimplemented!("BareFn"),
             syn::Type::Group(_) => unimplemented!("Group"),
-            syn::Type::ImplTrait(_) => unimplemented!("ImplTrait"),
+            syn::Type::ImplTrait(impl_ty) => {
+                for bound in impl_ty.bounds {
+                    let seg = match bound {
+                        syn::TypeParamBound::Trait(bound) => {
+                            match bound.path.segments.into_iter().last() {
+                                Some(seg) => seg,
+                                None => continue,
+                            }
+                        }
+                        syn::TypeParamBound::Lifetime(_) => continue,
+                    };
+                    if seg.ident != "Iterator" && seg.ident != "IntoIterator" {
+                        continue;
+                    }
+                    if let syn::PathArguments::AngleBracketed(args) = seg.arguments {
+                        for arg in args.args {
+                            if let syn::GenericArgument::Binding(binding) = arg {
+                                if binding.ident == "Item" {
+                                    return Type::Iterator(
+                                        Box::new(Self::from(binding.ty)));
+                                }
+                            }
+                        }
+                    }
+                }
+                unimplemented!("ImplTrait")
+            }
             syn::Type::Infer(_) => unimplemented!("Infer"),
             syn::Type::Macro(_) => unimplemented!("Macro"),
             syn::Type::Never(_) => unimplemented!("Never"),
@@ -180,12 +450,29 @@ impl From<syn::Type> for Type {
                         Type::Struct { name, args: vec![] }
                     }
                 } else {
-                    if type_path.path.segments.len() != 1 {
+                    // Wrappers may be written with their path, e.g.,
+                    // `std::sync::Arc<T>`
+                    let is_wrapper = matches!(type_path.path.segments.last(),
+                        Some(seg) if seg.ident == "Box" || seg.ident == "Arc"
+                            || seg.ident == "Rc");
+                    if type_path.path.segments.len() != 1 && !is_wrapper {
                         unimplemented!();
                     }
-                    let seg = type_path.path.segments.into_iter().next()
+                    let seg = type_path.path.segments.into_iter().last()
                         .unwrap();
                     let name = seg.ident.to_string();
+                    if let syn::PathArguments::AngleBracketed(ref args) = seg.arguments {
+                        if let (Some(syn::GenericArgument::Type(ty)), 1) =
+                                (args.args.first(), args.args.len()) {
+                            let ty = Box::new(Type::from(ty.clone()));
+                            match name.as_str() {
+                                "Box" => return Type::Box(ty),
+                                "Arc" => return Type::Arc(ty),
+                                "Rc" => return Type::Rc(ty),
+                                _ => {}
+                            }
+                        }
+                    }
                     let args = match seg.arguments {
                         syn::PathArguments::None => unreachable!(),
                         syn::PathArguments::Parenthesized(_) => unreachable!(),
@@ -215,7 +502,10 @@ impl From<syn::Type> for Type {
                     Type::Struct { name, args }
                 }
             },
-            syn::Type::Ptr(_) => unimplemented!("Ptr"),
+            syn::Type::Ptr(ptr_ty) => Type::RawPtr {
+                ty: Box::new(Self::from(*ptr_ty.elem)),
+                is_mut: ptr_ty.mutability.is_some(),
+            },
             syn::Type::Slice(_) => unimplemented!("Slice"),
             syn::Type::TraitObject(_) => unimplemented!("TraitObject"),
             syn::Type::Tuple(_) => unimplemented!("Tuple"),
@@ -259,6 +549,92 @@ impl Type {
             Type::RefMut(ty) => {
                 ty.update_struct(struct_name);
             }
+            Type::RawPtr { ty, .. } => {
+                ty.update_struct(struct_name);
+            }
+            Type::Box(ty) | Type::Arc(ty) | Type::Rc(ty) | Type::Iterator(ty) => {
+                ty.update_struct(struct_name);
+            }
         }
     }
 }
+
+/// The type of a constant that can be exposed to C: a primitive, or a `&str`
+/// with any lifetime. Returns `None` for any other type.
+pub fn constant_type(ty: &syn::Type) -> Option<Type> {
+    match ty {
+        syn::Type::Path(syn::TypePath { qself: None, path }) => {
+            let name = path.get_ident()?.to_string();
+            PRIMITIVE_TYPES.contains(&name.as_str())
+                .then_some(Type::Primitive(name))
+        }
+        syn::Type::Reference(ty) if ty.mutability.is_none() => match &*ty.elem {
+            syn::Type::Path(path) if path.qself.is_none()
+                    && path.path.is_ident("str") =>
+                Some(Type::new_ref("str")),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+/// Formats a literal expression, optionally negated, as Rust source. Returns
+/// `None` if the expression is not a literal.
+pub fn literal_to_string(expr: &syn::Expr) -> Option<String> {
+    match expr {
+        syn::Expr::Lit(expr) => match &expr.lit {
+            syn::Lit::Str(lit) => Some(format!("{:?}", lit.value())),
+            syn::Lit::Int(lit) =>
+                Some(format!("{}{}", lit.base10_digits(), lit.suffix())),
+            syn::Lit::Float(lit) =>
+                Some(format!("{}{}", lit.base10_digits(), lit.suffix())),
+            syn::Lit::Bool(lit) => Some(lit.value.to_string()),
+            _ => None,
+        },
+        syn::Expr::Unary(syn::ExprUnary { op: syn::UnOp::Neg(_), expr, .. }) =>
+            literal_to_string(expr).map(|value| format!("-{}", value)),
+        syn::Expr::Paren(expr) => literal_to_string(&expr.expr),
+        _ => None,
+    }
+}
+
+/// The names that a `use` tree brings into scope: the last segment of each
+/// path, or its alias, where `parent` is the segment before the tree. A glob
+/// brings in no name known here.
+pub fn use_tree_names(
+    tree: &syn::UseTree,
+    parent: Option<&syn::Ident>,
+) -> Vec<String> {
+    match tree {
+        syn::UseTree::Path(path) =>
+            use_tree_names(&path.tree, Some(&path.ident)),
+        syn::UseTree::Name(name) if name.ident == "self" =>
+            parent.map(ToString::to_string).into_iter().collect(),
+        syn::UseTree::Name(name) => vec![name.ident.to_string()],
+        syn::UseTree::Rename(rename) => vec![rename.rename.to_string()],
+        syn::UseTree::Glob(_) => vec![],
+        syn::UseTree::Group(group) => group.items.iter()
+            .flat_map(|tree| use_tree_names(tree, parent))
+            .collect(),
+    }
+}
+
+/// Joins the doc comments in the given attributes, one line per `///` line,
+/// dropping the leading space. Returns `None` if there are none.
+pub fn doc_from_attrs(attrs: &[syn::Attribute]) -> Option<String> {
+    let lines = attrs.iter()
+        .filter(|attr| attr.path.is_ident("doc"))
+        .filter_map(|attr| match attr.parse_meta() {
+            Ok(syn::Meta::NameValue(syn::MetaNameValue {
+                lit: syn::Lit::Str(lit), ..
+            })) => Some(lit.value()),
+            _ => None,
+        })
+        .map(|line| line.strip_prefix(' ').unwrap_or(&line).to_string())
+        .collect::<Vec<_>>();
+    if lines.is_empty() {
+        None
+    } else {
+        Some(lines.join("\n"))
+    }
+}
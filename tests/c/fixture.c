@@ -0,0 +1,167 @@
+#include <assert.h>
+#include <stdio.h>
+#include <string.h>
+
+#include "fixture.h"
+
+static void raw_pointers(void) {
+    Conn *c;
+    Conn_new(5, &c);
+    uintptr_t v;
+    Conn_get(c, &v);
+    assert(v == 5);
+    Conn_set(c, 7);
+    Conn_get(c, &v);
+    assert(v == 7);
+    const uint8_t *raw;
+    Conn_raw(c, &raw);
+    assert(raw[0] == 1 && raw[2] == 3);
+    uint8_t out[4];
+    Conn_fill_raw(c, out, 4);
+    assert(out[0] == 1 && out[3] == 1);
+    Conn_free(c);
+}
+
+static void self_by_value(void) {
+    Conn *c;
+    Conn_new(5, &c);
+    uintptr_t v;
+    Conn_finish(c, &v);
+    assert(v == 5);
+    Conn_new(5, &c);
+    Conn_finish_mut(c, &v);
+    assert(v == 6);
+}
+
+static void shared_handles(void) {
+    ConnShared *s;
+    Conn_shared(4, &s);
+    Conn_retain(s);
+    uintptr_t v;
+    ConnShared_get(s, &v);
+    assert(v == 4);
+    Conn_release(s);
+    ConnShared_get(s, &v);
+    assert(v == 4);
+    Conn_release(s);
+
+    Pool *p;
+    Pool_new(9, &p);
+    ConnShared *borrowed;
+    Pool_conn(p, &borrowed);
+    ConnShared_get(borrowed, &v);
+    assert(v == 9);
+    ConnShared *owned;
+    Pool_shared_conn(p, &owned);
+    assert(owned == borrowed);
+    Pool_free(p);
+    ConnShared_get(owned, &v);
+    assert(v == 9);
+    Conn_release(owned);
+}
+
+static void buffers(void) {
+    Conn *c;
+    Conn_new(5, &c);
+    const uint8_t bytes[] = {1, 2};
+    uintptr_t v;
+    Conn_sum(c, bytes, 2, &v);
+    assert(v == 8);
+    Conn_sum(c, NULL, 0, &v);
+    assert(v == 5);
+    assert(Conn_try_sum(c, bytes, 2, &v) == 0);
+    assert(v == 8);
+    assert(Conn_try_sum(c, NULL, 0, &v) == 1);
+    Conn_free(c);
+}
+
+static void ownership(void) {
+    Conn *a;
+    Conn_new(1, &a);
+    Conn *b;
+    Conn_new(2, &b);
+    Conn_merge(a, b);
+    Conn_merge(a, b);
+    uintptr_t v;
+    Conn_get(a, &v);
+    assert(v == 5);
+    Conn_free(a);
+    Conn_free(b);
+
+    Pool *p;
+    Pool_new(4, &p);
+    Conn *first;
+    Pool_first(p, &first);
+    Pool_free(p);
+    Conn_get(first, &v);
+    assert(v == 4);
+    Conn_free(first);
+}
+
+static void iterators(void) {
+    Conn *c;
+    Conn_new(5, &c);
+    Conn_bytes_iter *bytes;
+    Conn_bytes(c, &bytes);
+    uint8_t b;
+    uint8_t sum = 0;
+    while (Conn_bytes_iter_next(bytes, &b)) {
+        sum += b;
+    }
+    assert(sum == 6);
+    Conn_bytes_iter_free(bytes);
+    Conn_free(c);
+
+    Pool *p;
+    Pool_new(3, &p);
+    Pool_conns_iter *conns;
+    Pool_conns(p, &conns);
+    Conn *conn;
+    assert(Pool_conns_iter_next(conns, &conn));
+    uintptr_t v;
+    Conn_get(conn, &v);
+    assert(v == 3);
+    assert(!Pool_conns_iter_next(conns, &conn));
+    Pool_conns_iter_free(conns);
+    Pool_free(p);
+}
+
+static void constants(void) {
+    assert(MAX_CONNS == 16);
+    assert(MIN_OFFSET == -3);
+    assert(RATIO == 0.5);
+    assert(LIMIT == 32);
+    assert(strcmp((const char *)NAME, "fixture") == 0);
+    assert(strcmp((const char *)LABEL, "conn") == 0);
+}
+
+/* Uses a reference after the iterator it was borrowed from is freed, which
+ * aborts when the crate is built with `debug-handles`. */
+static void use_after_free(void) {
+    Pool *p;
+    Pool_new(3, &p);
+    Pool_conns_iter *conns;
+    Pool_conns(p, &conns);
+    Conn *conn;
+    Pool_conns_iter_next(conns, &conn);
+    Pool_conns_iter_free(conns);
+    uintptr_t v;
+    Conn_get(conn, &v);
+    Pool_free(p);
+}
+
+int main(int argc, char **argv) {
+    if (argc > 1 && strcmp(argv[1], "use_after_free") == 0) {
+        use_after_free();
+        return 0;
+    }
+    raw_pointers();
+    self_by_value();
+    shared_handles();
+    buffers();
+    ownership();
+    iterators();
+    constants();
+    printf("ok\n");
+    return 0;
+}
@@ -0,0 +1,90 @@
+#include <cassert>
+#include <cstdio>
+#include <cstdint>
+#include <utility>
+
+#include "fixture.hpp"
+
+static void raw_pointers() {
+    fixture::Conn c = fixture::Conn::new_(5);
+    assert(c.get() == 5);
+    c.set(7);
+    assert(c.get() == 7);
+    const uint8_t *raw = c.raw();
+    assert(raw[0] == 1 && raw[2] == 3);
+    uint8_t out[4];
+    c.fill_raw(out, 4);
+    assert(out[0] == 1 && out[3] == 1);
+}
+
+static void self_by_value() {
+    fixture::Conn c = fixture::Conn::new_(5);
+    assert(std::move(c).finish() == 5);
+    assert(c.c_ptr() == nullptr);
+    assert(fixture::Conn::new_(5).finish_mut() == 6);
+}
+
+static void shared_handles() {
+    fixture::ConnShared s = fixture::Conn::shared(4);
+    assert(s.get() == 4);
+
+    fixture::ConnShared owned = [] {
+        fixture::Pool p = fixture::Pool::new_(9);
+        assert(p.conn().get() == 9);
+        return p.shared_conn();
+    }();
+    assert(owned.get() == 9);
+}
+
+static void buffers() {
+    fixture::Conn c = fixture::Conn::new_(5);
+    const uint8_t bytes[] = {1, 2};
+    assert(c.sum(bytes) == 8);
+    assert(c.sum({}) == 5);
+    assert(c.try_sum(bytes) == 8);
+    try {
+        c.try_sum({});
+        assert(false);
+    } catch (const fixture::Error &e) {
+        assert(e.code() == 1);
+    }
+}
+
+static void ownership() {
+    fixture::Conn a = fixture::Conn::new_(1);
+    fixture::Conn b = fixture::Conn::new_(2);
+    a.merge(b);
+    a.merge(b);
+    assert(a.get() == 5);
+
+    fixture::Conn first = fixture::Pool::new_(4).first();
+    assert(first.get() == 4);
+}
+
+static void iterators() {
+    fixture::Conn c = fixture::Conn::new_(5);
+    fixture::Conn_bytes_iter bytes = c.bytes();
+    uint8_t sum = 0;
+    while (std::optional<uint8_t> b = bytes.next()) {
+        sum += *b;
+    }
+    assert(sum == 6);
+
+    fixture::Pool p = fixture::Pool::new_(3);
+    fixture::Pool_conns_iter conns = p.conns();
+    std::optional<fixture::Conn> conn = conns.next();
+    assert(conn && conn->get() == 3);
+    assert(!conns.next());
+}
+
+int main() {
+    raw_pointers();
+    self_by_value();
+    shared_handles();
+    buffers();
+    ownership();
+    iterators();
+    assert(fixture::debug_live_handles() == 0);
+    std::printf("ok\n");
+    return 0;
+}
@@ -0,0 +1,111 @@
+//! A small library that the tests generate wrappers around.
+
+use std::borrow::Cow;
+use std::sync::Arc;
+
+pub const MAX_CONNS: usize = 16;
+pub const MIN_OFFSET: i32 = -3;
+pub const RATIO: f64 = 0.5;
+pub const NAME: &str = "fixture";
+pub const LABEL: &'static str = "conn";
+pub const LIMIT: usize = MAX_CONNS * 2;
+// Skipped: not of primitive or &str type, or not a literal string
+pub const VERSION: &str = env!("CARGO_PKG_VERSION");
+pub const GREETING: Cow<'static, str> = Cow::Borrowed("hi");
+pub const TIMEOUT: std::time::Duration = std::time::Duration::ZERO;
+pub const EMPTY: &[u8] = &[];
+
+#[derive(Clone)]
+pub struct Conn {
+    value: usize,
+    buf: Vec<u8>,
+}
+
+impl Conn {
+    pub fn new(value: usize) -> Conn {
+        Conn { value, buf: vec![1, 2, 3] }
+    }
+
+    pub fn shared(value: usize) -> Arc<Conn> {
+        Arc::new(Conn::new(value))
+    }
+
+    /// Returns the value of the connection.
+    pub fn get(&self) -> usize {
+        self.value
+    }
+
+    pub fn set(&mut self, value: usize) {
+        self.value = value;
+    }
+
+    /// Consumes the connection, returning its value.
+    pub fn finish(self) -> usize {
+        self.value
+    }
+
+    pub fn finish_mut(mut self) -> usize {
+        self.value += 1;
+        self.value
+    }
+
+    pub fn raw(&self) -> *const u8 {
+        self.buf.as_ptr()
+    }
+
+    pub fn fill_raw(&self, out: *mut u8, len: usize) {
+        for i in 0..len {
+            unsafe { *out.add(i) = self.buf[i % self.buf.len()] };
+        }
+    }
+
+    /// Adds the value of the other connection to this one.
+    pub fn merge(&mut self, other: Conn) {
+        self.value += other.value;
+    }
+
+    /// Adds the given bytes to the value.
+    pub fn sum(&self, bytes: &[u8]) -> usize {
+        self.value + bytes.iter().map(|b| *b as usize).sum::<usize>()
+    }
+
+    /// Like `sum`, but fails if the bytes are empty.
+    pub fn try_sum(&self, bytes: &[u8]) -> Result<usize, ()> {
+        if bytes.is_empty() {
+            return Err(());
+        }
+        Ok(self.sum(bytes))
+    }
+
+    /// Iterates over the bytes of the connection's buffer.
+    pub fn bytes(&self) -> impl Iterator<Item = u8> + '_ {
+        self.buf.iter().copied()
+    }
+}
+
+/// Owns a connection shared with its users.
+pub struct Pool {
+    conn: Arc<Conn>,
+}
+
+impl Pool {
+    pub fn new(value: usize) -> Pool {
+        Pool { conn: Conn::shared(value) }
+    }
+
+    pub fn conn(&self) -> &Arc<Conn> {
+        &self.conn
+    }
+
+    pub fn shared_conn(&self) -> Arc<Conn> {
+        self.conn.clone()
+    }
+
+    pub fn first(&self) -> &Conn {
+        &self.conn
+    }
+
+    pub fn conns(&self) -> impl Iterator<Item = &Conn> {
+        std::iter::once(&*self.conn)
+    }
+}
@@ -0,0 +1,834 @@
+//! Generates wrappers around the crate in `tests/fixture`, compares the
+//! generated code to the snapshots in `tests/snapshots`, and builds the
+//! generated crate. Run with `UPDATE_SNAPSHOTS=1` to rewrite the snapshots.
+
+use color_eyre::eyre::Result;
+use ffiber::manifest::{
+    CargoManifestConfig, Dependency, Lto, PanicStrategy, ReleaseProfile,
+};
+use ffiber::bindings::ParamRole;
+use ffiber::types::{
+    CppErrorStyle, CrateType, HandleLock, Language, NullPolicy, Ownership,
+    SelfType, Type,
+};
+use ffiber::CDylibCompiler;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output};
+
+fn fixture_dir() -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixture")
+}
+
+fn prim(ty: &str) -> Type {
+    Type::Primitive(ty.to_string())
+}
+
+/// Generates `fixture-c` in a folder of its own, with the functions that
+/// `add` adds, and returns the path to the generated crate.
+fn generate(
+    name: &str,
+    add: impl FnOnce(&mut CDylibCompiler) -> Result<()>,
+) -> Result<PathBuf> {
+    let output_folder = Path::new(env!("CARGO_TARGET_TMPDIR")).join(name);
+    if output_folder.exists() {
+        fs::remove_dir_all(&output_folder)?;
+    }
+    let mut compiler = CDylibCompiler::new_with_output_folder("fixture",
+        output_folder.to_str().unwrap());
+    compiler.add_dependency(Dependency::new("fixture")
+        .path(fixture_dir().to_str().unwrap()))?;
+    compiler.add_paths_from_source("fixture",
+        &fixture_dir().join("src/lib.rs"))?;
+    compiler.add_language(Language::C);
+    add(&mut compiler)?;
+    compiler.flush()?;
+    Ok(output_folder.join("fixture-c"))
+}
+
+/// Compares a generated file to the snapshot named after the test, with the
+/// same extensions, or the file name if it has none.
+fn assert_snapshot(name: &str, package_folder: &Path, generated: &str) {
+    let actual = fs::read_to_string(package_folder.join(generated)).unwrap();
+    let file_name = Path::new(generated).file_name().unwrap().to_str().unwrap();
+    let extensions = file_name.split_once('.')
+        .map_or(file_name, |(_, extensions)| extensions);
+    let snapshot = Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/snapshots")
+        .join(format!("{}.{}", name, extensions));
+    if std::env::var_os("UPDATE_SNAPSHOTS").is_some() {
+        fs::write(&snapshot, &actual).unwrap();
+        return;
+    }
+    let expected = fs::read_to_string(&snapshot).unwrap_or_default();
+    assert!(actual == expected, "{} does not match {}, rerun with \
+        UPDATE_SNAPSHOTS=1 to update it", generated, snapshot.display());
+}
+
+/// Compares the generated lib.rs and header to the snapshots named after
+/// the test.
+fn assert_snapshots(name: &str, package_folder: &Path) {
+    for generated in ["src/lib.rs", "fixture.h"] {
+        assert_snapshot(name, package_folder, generated);
+    }
+}
+
+fn add_raw_pointers(compiler: &mut CDylibCompiler) -> Result<()> {
+    let conn = Type::new_struct("Conn");
+    compiler.add_extern_c_function(conn.clone(), SelfType::None, "new",
+        vec![("value", prim("usize"))], Some(conn.clone()), false)?;
+    compiler.add_extern_c_function(conn.clone(), SelfType::Ref, "get",
+        vec![], Some(prim("usize")), false)?;
+    compiler.add_extern_c_function(conn.clone(), SelfType::RefMut, "set",
+        vec![("value", prim("usize"))], None, false)?;
+    compiler.add_extern_c_function(conn.clone(), SelfType::Ref, "raw",
+        vec![], Some(Type::new_ptr(prim("u8"))), false)?;
+    compiler.add_extern_c_function(conn, SelfType::Ref, "fill_raw",
+        vec![("out", Type::new_ptr_mut(prim("u8"))), ("len", prim("usize"))],
+        None, false)?;
+    Ok(())
+}
+
+fn add_self_by_value(compiler: &mut CDylibCompiler) -> Result<()> {
+    let conn = Type::new_struct("Conn");
+    compiler.add_extern_c_function(conn.clone(), SelfType::Value, "finish",
+        vec![], Some(prim("usize")), false)?;
+    compiler.add_extern_c_function(conn, SelfType::ValueMut, "finish_mut",
+        vec![], Some(prim("usize")), false)?;
+    Ok(())
+}
+
+fn add_shared_handles(compiler: &mut CDylibCompiler) -> Result<()> {
+    let conn = Type::new_struct("Conn");
+    let shared_conn = Type::new_arc("Conn");
+    let pool = Type::new_struct("Pool");
+    compiler.add_extern_c_function(conn, SelfType::None, "shared",
+        vec![("value", prim("usize"))], Some(shared_conn.clone()), false)?;
+    compiler.add_extern_c_function(shared_conn.clone(), SelfType::Ref, "get",
+        vec![], Some(prim("usize")), false)?;
+    compiler.add_extern_c_function(pool.clone(), SelfType::None, "new",
+        vec![("value", prim("usize"))], Some(pool.clone()), false)?;
+    compiler.add_extern_c_function(pool.clone(), SelfType::Ref, "conn",
+        vec![], Some(Type::Ref(Box::new(shared_conn.clone()))), false)?;
+    compiler.add_extern_c_function(pool, SelfType::Ref, "shared_conn",
+        vec![], Some(shared_conn), false)?;
+    Ok(())
+}
+
+fn add_buffers(compiler: &mut CDylibCompiler) -> Result<()> {
+    let conn = Type::new_struct("Conn");
+    let bytes = Type::Buffer(Box::new(prim("u8")));
+    compiler.add_extern_c_function(conn.clone(), SelfType::Ref, "sum",
+        vec![("bytes", bytes.clone())], Some(prim("usize")), false)?;
+    compiler.add_extern_c_function(conn, SelfType::Ref, "try_sum",
+        vec![("bytes", bytes)], Some(prim("usize")), true)?;
+    Ok(())
+}
+
+fn add_ownership(compiler: &mut CDylibCompiler) -> Result<()> {
+    let conn = Type::new_struct("Conn");
+    compiler.set_arg_ownership("other", Ownership::BorrowClone);
+    compiler.add_extern_c_function(conn.clone(), SelfType::RefMut, "merge",
+        vec![("other", conn.clone())], None, false)?;
+    compiler.set_ret_ownership(Ownership::BorrowClone);
+    compiler.add_extern_c_function(Type::new_struct("Pool"), SelfType::Ref,
+        "first", vec![], Some(Type::Ref(Box::new(conn))), false)?;
+    Ok(())
+}
+
+fn add_iterators(compiler: &mut CDylibCompiler) -> Result<()> {
+    let conn = Type::new_struct("Conn");
+    let pool = Type::new_struct("Pool");
+    compiler.add_extern_c_function(conn.clone(), SelfType::Ref, "bytes",
+        vec![], Some(Type::Iterator(Box::new(prim("u8")))), false)?;
+    compiler.add_extern_c_function(pool, SelfType::Ref, "conns", vec![],
+        Some(Type::Iterator(Box::new(Type::Ref(Box::new(conn))))), false)?;
+    Ok(())
+}
+
+/// Adds the constants of the fixture and returns those it skipped.
+fn add_constants(compiler: &mut CDylibCompiler) -> Result<Vec<String>> {
+    compiler.add_constants_from_source("fixture",
+        &fixture_dir().join("src/lib.rs"))
+}
+
+#[test]
+fn raw_pointers() -> Result<()> {
+    let package_folder = generate("raw_pointers", add_raw_pointers)?;
+    assert_snapshots("raw_pointers", &package_folder);
+    Ok(())
+}
+
+#[test]
+fn self_by_value() -> Result<()> {
+    let package_folder = generate("self_by_value", add_self_by_value)?;
+    assert_snapshots("self_by_value", &package_folder);
+    Ok(())
+}
+
+#[test]
+fn shared_handles() -> Result<()> {
+    let package_folder = generate("shared_handles", add_shared_handles)?;
+    assert_snapshots("shared_handles", &package_folder);
+    Ok(())
+}
+
+#[test]
+fn mutably_borrowed_shared_handles_are_rejected() -> Result<()> {
+    generate("mutably_borrowed_shared_handles", |compiler| {
+        let shared_conn = Type::new_arc("Conn");
+        assert!(compiler.add_extern_c_function(shared_conn.clone(),
+            SelfType::RefMut, "set", vec![("value", prim("usize"))], None,
+            false).is_err());
+        assert!(compiler.add_extern_c_function_standalone("fixture_bump",
+            "bump", vec![("conn", Type::RefMut(Box::new(shared_conn)))], None,
+            false).is_err());
+        assert!(compiler.interface().functions.is_empty());
+        Ok(())
+    })?;
+    Ok(())
+}
+
+#[test]
+fn consumed_shared_handles_are_rejected() -> Result<()> {
+    generate("consumed_shared_handles", |compiler| {
+        for self_ty in [SelfType::Value, SelfType::ValueMut] {
+            assert!(compiler.add_extern_c_function(Type::new_arc("Conn"),
+                self_ty, "finish", vec![], Some(prim("usize")), false)
+                .is_err());
+        }
+        assert!(compiler.add_extern_c_function(Type::new_rc("Conn"),
+            SelfType::Value, "finish", vec![], Some(prim("usize")), false)
+            .is_err());
+        assert!(compiler.interface().functions.is_empty());
+        Ok(())
+    })?;
+    Ok(())
+}
+
+#[test]
+fn buffers() -> Result<()> {
+    let package_folder = generate("buffers", add_buffers)?;
+    assert_snapshots("buffers", &package_folder);
+    Ok(())
+}
+
+#[test]
+fn null_checks() -> Result<()> {
+    let package_folder = generate("null_checks", |compiler| {
+        compiler.set_null_policy(NullPolicy::ReturnError);
+        add_raw_pointers(compiler)?;
+        add_buffers(compiler)
+    })?;
+    assert_snapshots("null_checks", &package_folder);
+    Ok(())
+}
+
+#[test]
+fn languages() -> Result<()> {
+    let package_folder = generate("languages", |compiler| {
+        compiler.add_language(Language::Cpp(CppErrorStyle::Exceptions));
+        compiler.add_language(Language::Python);
+        compiler.add_language(Language::Go);
+        compiler.add_language(Language::CSharp);
+        compiler.add_language(Language::Java);
+        compiler.add_language(Language::Lua);
+        // Replaces the earlier error style
+        compiler.add_language(Language::Cpp(CppErrorStyle::Expected));
+        add_raw_pointers(compiler)
+    })?;
+    for file in ["fixture.h", "fixture.hpp", "fixture.py", "go/fixture.go",
+            "fixture.cs", "java/fixture/Fixture.java", "fixture.lua"] {
+        assert!(package_folder.join(file).exists(), "{} is missing", file);
+    }
+    assert!(!package_folder.join("build.rs").exists());
+    let cpp_header = fs::read_to_string(package_folder.join("fixture.hpp"))?;
+    assert!(cpp_header.contains("std::expected"));
+    Ok(())
+}
+
+#[test]
+fn cpp_header() -> Result<()> {
+    let package_folder = generate("cpp_header", |compiler| {
+        compiler.add_language(Language::Cpp(CppErrorStyle::Exceptions));
+        add_all(compiler)
+    })?;
+    assert_snapshot("cpp_header", &package_folder, "fixture.hpp");
+    build(&package_folder, &[])?;
+    if let Some(binary) = compile_test(&package_folder, "c++",
+            "tests/cpp/fixture.cpp", &["-std=c++20"])? {
+        let output = run_c_test(&package_folder, &binary, &[])?;
+        assert!(output.status.success(), "tests/cpp/fixture.cpp failed: {}",
+            String::from_utf8_lossy(&output.stderr));
+    }
+    Ok(())
+}
+
+#[test]
+fn cpp_header_with_expected() -> Result<()> {
+    let package_folder = generate("cpp_header_with_expected", |compiler| {
+        compiler.add_language(Language::Cpp(CppErrorStyle::Expected));
+        add_all(compiler)
+    })?;
+    assert_snapshot("cpp_header_with_expected", &package_folder,
+        "fixture.hpp");
+    Ok(())
+}
+
+#[test]
+fn python_module() -> Result<()> {
+    let package_folder = generate("python_module", |compiler| {
+        compiler.add_language(Language::Python);
+        add_all(compiler)
+    })?;
+    assert_snapshot("python_module", &package_folder, "fixture.py");
+    build(&package_folder, &[])?;
+    if let Ok(python) = which::which("python3") {
+        let output = Command::new(python)
+            .arg(Path::new(env!("CARGO_MANIFEST_DIR"))
+                .join("tests/python/test_fixture.py"))
+            .env("PYTHONPATH", &package_folder)
+            .output()?;
+        assert!(output.status.success(),
+            "tests/python/test_fixture.py failed: {}",
+            String::from_utf8_lossy(&output.stderr));
+    }
+    Ok(())
+}
+
+#[test]
+fn go_package() -> Result<()> {
+    let package_folder = generate("go_package", |compiler| {
+        compiler.add_language(Language::Go);
+        add_all(compiler)
+    })?;
+    assert_snapshot("go_package", &package_folder, "go/fixture.go");
+    build(&package_folder, &[])?;
+    if let Ok(go) = which::which("go") {
+        for command in ["vet", "build"] {
+            let output = Command::new(&go)
+                .args([command, "fixture.go"])
+                .current_dir(package_folder.join("go"))
+                .env("CGO_ENABLED", "1")
+                .output()?;
+            assert!(output.status.success(), "go {} failed: {}", command,
+                String::from_utf8_lossy(&output.stderr));
+        }
+    }
+    Ok(())
+}
+
+#[test]
+fn csharp_bindings() -> Result<()> {
+    let package_folder = generate("csharp_bindings", |compiler| {
+        compiler.add_language(Language::CSharp);
+        add_all(compiler)
+    })?;
+    assert_snapshot("csharp_bindings", &package_folder, "fixture.cs");
+    Ok(())
+}
+
+#[test]
+fn java_bindings() -> Result<()> {
+    let package_folder = generate("java_bindings", |compiler| {
+        compiler.add_language(Language::Java);
+        add_all(compiler)
+    })?;
+    assert_snapshot("java_bindings", &package_folder,
+        "java/fixture/Fixture.java");
+    Ok(())
+}
+
+#[test]
+fn lua_module() -> Result<()> {
+    let package_folder = generate("lua_module", |compiler| {
+        compiler.add_language(Language::Lua);
+        add_all(compiler)
+    })?;
+    assert_snapshot("lua_module", &package_folder, "fixture.lua");
+    Ok(())
+}
+
+#[test]
+fn docs() -> Result<()> {
+    let package_folder = generate("docs", |compiler| {
+        compiler.add_docs_from_source(&fixture_dir().join("src/lib.rs"))?;
+        let conn = Type::new_struct("Conn");
+        let bytes = Type::Buffer(Box::new(prim("u8")));
+        compiler.add_extern_c_function(conn.clone(), SelfType::Ref, "get",
+            vec![], Some(prim("usize")), false)?;
+        compiler.add_extern_c_function(conn.clone(), SelfType::Ref, "try_sum",
+            vec![("bytes", bytes)], Some(prim("usize")), true)?;
+        compiler.set_doc("Replaces the value of the connection.");
+        compiler.add_extern_c_function(conn, SelfType::RefMut, "set",
+            vec![("value", prim("usize"))], None, false)?;
+        let docs = |name: &str| compiler.interface().functions.iter()
+            .find(|func| func.name == name)
+            .unwrap()
+            .docs
+            .clone();
+        assert_eq!(docs("Conn_get")[0],
+            "Returns the value of the connection.");
+        assert!(docs("Conn_try_sum").iter()
+            .any(|line| line.starts_with("Returns 0 on success")));
+        assert_eq!(docs("Conn_set")[0],
+            "Replaces the value of the connection.");
+        Ok(())
+    })?;
+    assert_snapshots("docs", &package_folder);
+    Ok(())
+}
+
+#[test]
+fn package_files() -> Result<()> {
+    let package_folder = generate("package_files", add_raw_pointers)?;
+    for generated in ["fixture.pc.in", "FixtureConfig.cmake", "Makefile"] {
+        assert_snapshot("package_files", &package_folder, generated);
+    }
+    let make = match which::which("make") {
+        Ok(make) => make,
+        Err(_) => return Ok(()),
+    };
+    let destdir = package_folder.join("destdir");
+    let output = Command::new(make)
+        .arg("install")
+        .arg("PREFIX=/usr")
+        .arg(format!("DESTDIR={}", destdir.display()))
+        .env("CARGO_NET_OFFLINE", "true")
+        .current_dir(&package_folder)
+        .output()?;
+    assert!(output.status.success(), "make install failed: {}",
+        String::from_utf8_lossy(&output.stderr));
+    for installed in ["lib/libfixture_c.so", "include/fixture.h",
+            "lib/cmake/Fixture/FixtureConfig.cmake"] {
+        assert!(destdir.join("usr").join(installed).exists(),
+            "{} was not installed", installed);
+    }
+    let pc = fs::read_to_string(destdir.join("usr/lib/pkgconfig/fixture.pc"))?;
+    assert!(pc.starts_with("prefix=/usr\n"));
+    Ok(())
+}
+
+#[test]
+fn static_and_shared_libraries() -> Result<()> {
+    let package_folder = generate("static_and_shared_libraries", |compiler| {
+        compiler.set_crate_type(CrateType::Both);
+        add_raw_pointers(compiler)
+    })?;
+    for generated in ["fixture.pc.in", "FixtureConfig.cmake", "Makefile"] {
+        assert_snapshot("static_and_shared_libraries", &package_folder,
+            generated);
+    }
+    let cargo_toml = fs::read_to_string(package_folder.join("Cargo.toml"))?;
+    assert!(cargo_toml.contains("crate-type = [\"cdylib\", \"staticlib\"]"));
+    let make = match which::which("make") {
+        Ok(make) => make,
+        Err(_) => return Ok(()),
+    };
+    let output = Command::new(make)
+        .arg("build")
+        .env("CARGO_NET_OFFLINE", "true")
+        .current_dir(&package_folder)
+        .output()?;
+    assert!(output.status.success(), "make build failed: {}",
+        String::from_utf8_lossy(&output.stderr));
+    for built in ["libfixture_c.so", "libfixture_c.a"] {
+        assert!(package_folder.join("target/release").join(built).exists(),
+            "{} was not built", built);
+    }
+    // The static library needs the native libraries of the standard library
+    let native_static_libs =
+        fs::read_to_string(package_folder.join("native-static-libs"))?;
+    assert!(!native_static_libs.trim().is_empty());
+    let pc = fs::read_to_string(package_folder.join("fixture.pc"))?;
+    assert!(pc.contains(&format!("Libs.private: {}",
+        native_static_libs.trim())), "{}", pc);
+    Ok(())
+}
+
+#[test]
+fn cargo_manifest() -> Result<()> {
+    let package_folder = generate("cargo_manifest", |compiler| {
+        assert!(compiler.set_manifest_config(CargoManifestConfig {
+            patches: vec![("crates-io".to_string(),
+                vec![Dependency::new("bumpalo")])],
+            ..Default::default()
+        }).is_err());
+        compiler.set_manifest_config(CargoManifestConfig {
+            version: "1.2.3".to_string(),
+            authors: vec!["A \"B\" C".to_string()],
+            license: Some("MIT".to_string()),
+            release_profile: ReleaseProfile {
+                lto: Some(Lto::Thin),
+                codegen_units: Some(1),
+                panic: Some(PanicStrategy::Abort),
+            },
+            features: vec![("extra".to_string(),
+                vec!["fixture/extra".to_string()])],
+            patches: vec![("crates-io".to_string(),
+                vec![Dependency::new("bumpalo").path("../bumpalo")])],
+            target_dependencies: vec![("cfg(unix)".to_string(),
+                vec![Dependency::new("libc").version("0.2")])],
+            ..Default::default()
+        })?;
+        add_raw_pointers(compiler)
+    })?;
+    let manifest: toml::Value =
+        fs::read_to_string(package_folder.join("Cargo.toml"))?.parse()?;
+    assert_eq!(manifest["package"]["version"].as_str(), Some("1.2.3"));
+    assert_eq!(manifest["package"]["authors"][0].as_str(), Some("A \"B\" C"));
+    assert_eq!(manifest["package"]["license"].as_str(), Some("MIT"));
+    assert!(manifest["package"].get("description").is_none());
+    let profile = &manifest["profile"]["release"];
+    assert_eq!(profile["lto"].as_str(), Some("thin"));
+    assert_eq!(profile["codegen-units"].as_integer(), Some(1));
+    assert_eq!(profile["panic"].as_str(), Some("abort"));
+    assert!(manifest["features"].get("debug-handles").is_some());
+    assert_eq!(manifest["features"]["extra"][0].as_str(),
+        Some("fixture/extra"));
+    assert_eq!(manifest["patch"]["crates-io"]["bumpalo"]["path"].as_str(),
+        Some("../bumpalo"));
+    assert_eq!(manifest["target"]["cfg(unix)"]["dependencies"]["libc"]
+        .as_str(), Some("0.2"));
+    // The native header needs no build dependencies
+    assert!(manifest.get("build-dependencies").is_none());
+    let pc = fs::read_to_string(package_folder.join("fixture.pc"))?;
+    assert!(pc.contains("Version: 1.2.3\n"));
+    Ok(())
+}
+
+/// Imports the items the wrappers use once however often the crate is
+/// flushed, unless an import already brings their name into scope.
+#[test]
+fn auto_imports() -> Result<()> {
+    let output_folder =
+        Path::new(env!("CARGO_TARGET_TMPDIR")).join("auto_imports");
+    let mut compiler = CDylibCompiler::new_with_output_folder("fixture",
+        output_folder.to_str().unwrap());
+    compiler.add_dependency(Dependency::new("fixture")
+        .path(fixture_dir().to_str().unwrap()))?;
+    compiler.add_paths_from_source("fixture",
+        &fixture_dir().join("src/lib.rs"))?;
+    compiler.add_language(Language::C);
+    // Only the alias is in scope, so `Conn` is still imported
+    compiler.import("fixture::Conn as Connection")?;
+    compiler.import("fixture::{Pool}")?;
+    let conn = Type::new_struct("Conn");
+    let pool = Type::new_struct("Pool");
+    compiler.add_extern_c_function(conn.clone(), SelfType::None, "new",
+        vec![("value", prim("usize"))], Some(conn), false)?;
+    compiler.add_extern_c_function(pool.clone(), SelfType::None, "new",
+        vec![("value", prim("usize"))], Some(pool), false)?;
+    compiler.flush()?;
+    compiler.flush()?;
+    let lib_rs = fs::read_to_string(
+        output_folder.join("fixture-c/src/lib.rs"))?;
+    assert_eq!(lib_rs.matches("use fixture::Conn;").count(), 1);
+    assert_eq!(lib_rs.matches("use fixture::Conn as Connection;").count(), 1);
+    // rustfmt writes the group import as `use fixture::Pool;`
+    assert_eq!(lib_rs.matches("use fixture::Pool;").count(), 1);
+    // An import that is not a path is an error
+    compiler.import("fixture::")?;
+    assert!(compiler.flush().is_err());
+    Ok(())
+}
+
+/// Reads the dependencies of a crate that the wrappers use through an
+/// import, a path in a bound type, and a path in a call.
+#[test]
+fn dependencies_from_manifest() -> Result<()> {
+    let source_dir = Path::new(env!("CARGO_TARGET_TMPDIR")).join("source");
+    fs::create_dir_all(&source_dir)?;
+    fs::write(source_dir.join("Cargo.toml"), r#"
+[package]
+name = "source"
+version = "0.1.0"
+
+[dependencies]
+bumpalo = { version = "3", features = ["collections"] }
+helper = { path = "../helper" }
+shared = { workspace = true, features = ["extra"] }
+serde = "1"
+
+[workspace]
+
+[workspace.dependencies]
+shared = "0.3"
+"#)?;
+    let package_folder = generate("dependencies_from_manifest", |compiler| {
+        compiler.add_dependencies_from_manifest(
+            &source_dir.join("Cargo.toml"))?;
+        compiler.import("helper::Helper")?;
+        compiler.add_extern_c_function(Type::new_struct("bumpalo::Bump"),
+            SelfType::RefMut, "reset", vec![], None, false)?;
+        compiler.add_extern_c_function_standalone("fixture_init",
+            "shared::init", vec![], None, false)
+    })?;
+    let manifest: toml::Value =
+        fs::read_to_string(package_folder.join("Cargo.toml"))?.parse()?;
+    let dependencies = &manifest["dependencies"];
+    assert!(dependencies["source"]["path"].as_str().unwrap()
+        .ends_with("source"));
+    assert_eq!(dependencies["bumpalo"]["features"][0].as_str(),
+        Some("collections"));
+    assert!(dependencies["helper"]["path"].as_str().unwrap()
+        .ends_with("helper"));
+    assert_eq!(dependencies["shared"]["version"].as_str(), Some("0.3"));
+    assert_eq!(dependencies["shared"]["features"][0].as_str(), Some("extra"));
+    assert!(dependencies.get("serde").is_none());
+    // C names the struct without its path
+    let header = fs::read_to_string(package_folder.join("fixture.h"))?;
+    assert!(header.contains("typedef struct Bump Bump;"));
+    assert!(header.contains("void Bump_reset(Bump *self_);"));
+    assert!(!header.contains("bumpalo"));
+    let lib_rs = fs::read_to_string(package_folder.join("src/lib.rs"))?;
+    assert!(lib_rs.contains("bumpalo::Bump"));
+    Ok(())
+}
+
+#[test]
+fn unsafe_functions() -> Result<()> {
+    let package_folder = generate("unsafe_functions", |compiler| {
+        compiler.set_unsafe_functions(true)?;
+        add_raw_pointers(compiler)?;
+        add_buffers(compiler)
+    })?;
+    assert_snapshots("unsafe_functions", &package_folder);
+    build(&package_folder, &[])?;
+    Ok(())
+}
+
+#[test]
+fn late_unsafe_functions_are_rejected() -> Result<()> {
+    generate("late_unsafe_functions", |compiler| {
+        add_raw_pointers(compiler)?;
+        assert!(compiler.set_unsafe_functions(true).is_err());
+        Ok(())
+    })?;
+    Ok(())
+}
+
+#[test]
+fn ownership() -> Result<()> {
+    let package_folder = generate("ownership", add_ownership)?;
+    assert_snapshots("ownership", &package_folder);
+    Ok(())
+}
+
+#[test]
+fn invalid_ownership_is_rejected() -> Result<()> {
+    generate("invalid_ownership", |compiler| {
+        let conn = Type::new_struct("Conn");
+        compiler.set_arg_ownership("missing", Ownership::BorrowClone);
+        assert!(compiler.add_extern_c_function(conn.clone(), SelfType::RefMut,
+            "merge", vec![("other", conn.clone())], None, false).is_err());
+        compiler.set_ret_ownership(Ownership::Borrow);
+        assert!(compiler.add_extern_c_function(conn.clone(), SelfType::None,
+            "new", vec![("value", prim("usize"))], Some(conn.clone()), false)
+            .is_err());
+        assert!(compiler.interface().functions.is_empty());
+        // Annotations only apply to the next function, even if it fails
+        compiler.add_extern_c_function(conn.clone(), SelfType::RefMut, "merge",
+            vec![("other", conn)], None, false)?;
+        assert_eq!(compiler.interface().functions[0].params[1].role,
+            ParamRole::Arg(Ownership::Transfer));
+        Ok(())
+    })?;
+    Ok(())
+}
+
+#[test]
+fn handle_locks() -> Result<()> {
+    let package_folder = generate("handle_locks", |compiler| {
+        compiler.set_handle_lock("Conn", HandleLock::Mutex)?;
+        compiler.set_handle_lock("Pool", HandleLock::RwLock)?;
+        add_raw_pointers(compiler)?;
+        add_self_by_value(compiler)?;
+        let pool = Type::new_struct("Pool");
+        compiler.add_extern_c_function(pool.clone(), SelfType::None, "new",
+            vec![("value", prim("usize"))], Some(pool.clone()), false)?;
+        compiler.add_extern_c_function(pool.clone(), SelfType::Ref,
+            "shared_conn", vec![], Some(Type::new_arc("Conn")), false)?;
+        compiler.assert_shareable(Type::new_struct("Conn"))?;
+        compiler.assert_shareable(pool)
+    })?;
+    assert_snapshots("handle_locks", &package_folder);
+    build(&package_folder, &[])
+}
+
+#[test]
+fn late_handle_locks_are_rejected() -> Result<()> {
+    generate("late_handle_locks", |compiler| {
+        add_raw_pointers(compiler)?;
+        assert!(compiler.set_handle_lock("Conn", HandleLock::Mutex).is_err());
+        compiler.assert_shareable(Type::new_struct("Pool"))?;
+        assert!(compiler.set_handle_lock("Pool", HandleLock::Mutex).is_err());
+        Ok(())
+    })?;
+    generate("late_shared_handle_locks", |compiler| {
+        // Shared handles are never locked
+        compiler.add_extern_c_function(Type::new_arc("Conn"), SelfType::Ref,
+            "get", vec![], Some(prim("usize")), false)?;
+        compiler.set_handle_lock("Conn", HandleLock::Mutex)
+    })?;
+    Ok(())
+}
+
+#[test]
+fn iterators() -> Result<()> {
+    let package_folder = generate("iterators", |compiler| {
+        add_iterators(compiler)?;
+        let bytes = compiler.interface().functions.iter()
+            .find(|func| func.name == "Conn_bytes")
+            .unwrap();
+        assert_eq!(bytes.params.last().unwrap().borrows, ["self_"]);
+        Ok(())
+    })?;
+    assert_snapshots("iterators", &package_folder);
+    Ok(())
+}
+
+#[test]
+fn unsupported_iterators_are_rejected() -> Result<()> {
+    generate("unsupported_iterators", |compiler| {
+        let conn = Type::new_struct("Conn");
+        let nested = Type::Iterator(Box::new(Type::Iterator(Box::new(
+            prim("u8")))));
+        assert!(compiler.add_extern_c_function(conn.clone(), SelfType::Ref,
+            "nested", vec![], Some(nested), false).is_err());
+        assert!(compiler.add_extern_c_function(conn.clone(), SelfType::Ref,
+            "extend", vec![("bytes", Type::Iterator(Box::new(prim("u8"))))],
+            None, false).is_err());
+        compiler.set_handle_lock("Conn", HandleLock::Mutex)?;
+        assert!(compiler.add_extern_c_function(conn, SelfType::Ref, "bytes",
+            vec![], Some(Type::Iterator(Box::new(prim("u8")))), false)
+            .is_err());
+        assert!(compiler.interface().functions.is_empty());
+        Ok(())
+    })?;
+    Ok(())
+}
+
+#[test]
+fn constants() -> Result<()> {
+    let package_folder = generate("constants", |compiler| {
+        let skipped = add_constants(compiler)?;
+        assert_eq!(skipped, ["VERSION", "GREETING", "TIMEOUT", "EMPTY"]);
+        let names = compiler.interface().constants.iter()
+            .map(|constant| constant.name.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(names, ["MAX_CONNS", "MIN_OFFSET", "RATIO", "NAME", "LABEL",
+            "LIMIT"]);
+        Ok(())
+    })?;
+    assert_snapshots("constants", &package_folder);
+    Ok(())
+}
+
+/// Adds every function above.
+fn add_all(compiler: &mut CDylibCompiler) -> Result<()> {
+    add_raw_pointers(compiler)?;
+    add_self_by_value(compiler)?;
+    add_shared_handles(compiler)?;
+    add_buffers(compiler)?;
+    add_ownership(compiler)?;
+    add_iterators(compiler)?;
+    add_constants(compiler)?;
+    Ok(())
+}
+
+/// Generates a crate with every function above.
+fn generate_all(name: &str) -> Result<PathBuf> {
+    generate(name, add_all)
+}
+
+/// Builds the generated crate with the given features.
+fn build(package_folder: &Path, features: &[&str]) -> Result<()> {
+    let cargo = std::env::var("CARGO").unwrap_or_else(|_| "cargo".to_string());
+    let output = Command::new(cargo)
+        .args(["build", "--offline", "--features", &features.join(",")])
+        .current_dir(package_folder)
+        .output()?;
+    assert!(output.status.success(), "failed to build the generated crate: {}",
+        String::from_utf8_lossy(&output.stderr));
+    Ok(())
+}
+
+/// Builds the generated crate with the given features, and compiles
+/// `tests/c/fixture.c` against it. Returns the path to the test binary, or
+/// `None` if no C compiler is installed.
+fn build_c_test(package_folder: &Path, features: &[&str])
+        -> Result<Option<PathBuf>> {
+    build(package_folder, features)?;
+    compile_test(package_folder, "cc", "tests/c/fixture.c", &[])
+}
+
+/// Compiles the given test source against the built crate with `compiler`.
+/// Returns the path to the test binary, or `None` if the compiler is not
+/// installed.
+fn compile_test(
+    package_folder: &Path,
+    compiler: &str,
+    source: &str,
+    flags: &[&str],
+) -> Result<Option<PathBuf>> {
+    let compiler = match which::which(compiler) {
+        Ok(compiler) => compiler,
+        Err(_) => return Ok(None),
+    };
+    let source = Path::new(env!("CARGO_MANIFEST_DIR")).join(source);
+    let binary = package_folder.join(format!("{}_test",
+        source.extension().unwrap().to_str().unwrap()));
+    let status = Command::new(compiler)
+        .args(flags)
+        .arg(&source)
+        .arg("-I").arg(package_folder)
+        .arg("-L").arg(package_folder.join("target/debug"))
+        .args(["-lfixture_c", "-o"]).arg(&binary)
+        .status()?;
+    assert!(status.success(), "failed to compile {}", source.display());
+    Ok(Some(binary))
+}
+
+/// Runs a test binary with the given arguments.
+fn run_c_test(package_folder: &Path, binary: &Path, args: &[&str])
+        -> Result<Output> {
+    let target_folder = package_folder.join("target/debug");
+    Ok(Command::new(binary)
+        .args(args)
+        .env("LD_LIBRARY_PATH", &target_folder)
+        .env("DYLD_LIBRARY_PATH", &target_folder)
+        .output()?)
+}
+
+/// Builds a crate with every function above, and runs `tests/c/fixture.c`
+/// against it if a C compiler is installed.
+#[test]
+fn builds_and_runs_from_c() -> Result<()> {
+    let package_folder = generate_all("build")?;
+    if let Some(binary) = build_c_test(&package_folder, &[])? {
+        let output = run_c_test(&package_folder, &binary, &[])?;
+        assert!(output.status.success(), "tests/c/fixture.c failed: {}",
+            String::from_utf8_lossy(&output.stderr));
+    }
+    Ok(())
+}
+
+/// Runs `tests/c/fixture.c` against a crate that tracks handles, which
+/// aborts when a reference is used after the handle it borrows from is freed.
+#[test]
+fn debug_handles_track_borrowed_references() -> Result<()> {
+    let package_folder = generate_all("debug_handles")?;
+    if let Some(binary) = build_c_test(&package_folder, &["debug-handles"])? {
+        let output = run_c_test(&package_folder, &binary, &[])?;
+        assert!(output.status.success(), "tests/c/fixture.c failed: {}",
+            String::from_utf8_lossy(&output.stderr));
+        let output = run_c_test(&package_folder, &binary,
+            &["use_after_free"])?;
+        assert!(!output.status.success());
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        assert!(stderr.contains("Conn_get: handle")
+            && stderr.contains("to a Conn was already freed"), "{}", stderr);
+    }
+    Ok(())
+}
@@ -0,0 +1,49 @@
+//! Checks how dependencies of the generated crate are validated and written
+//! to its Cargo.toml.
+
+use ffiber::manifest::Dependency;
+use ffiber::CDylibCompiler;
+
+#[test]
+fn dependencies_are_written_as_toml() {
+    assert_eq!(Dependency::new("serde").version("1.0").to_toml(),
+        "serde = \"1.0\"");
+    assert_eq!(Dependency::new("fixture").path("../fixture").to_toml(),
+        "fixture = { path = \"../fixture\" }");
+    assert_eq!(Dependency::new("bumpalo")
+            .git("https://github.com/fitzgen/bumpalo")
+            .branch("main")
+            .features(&["collections"])
+            .to_toml(),
+        "bumpalo = { git = \"https://github.com/fitzgen/bumpalo\", \
+            branch = \"main\", features = [\"collections\"] }");
+    assert_eq!(Dependency::new("a-b")
+            .version("0.2")
+            .default_features(false)
+            .optional(true)
+            .package("ab")
+            .to_toml(),
+        "a-b = { package = \"ab\", version = \"0.2\", \
+            default-features = false, optional = true }");
+    // Strings are escaped, and keys that are not bare are quoted
+    assert_eq!(Dependency::new("a.b").path("C:\\dir\\\"q\"").to_toml(),
+        "\"a.b\" = { path = \"C:\\\\dir\\\\\\\"q\\\"\" }");
+}
+
+#[test]
+fn invalid_dependencies_are_rejected() {
+    assert!(Dependency::new("serde").version("1.0").validate().is_ok());
+    for dependency in [
+        Dependency::new(""),
+        Dependency::new("x"),
+        Dependency::new("x").path("../x").git("https://x"),
+        Dependency::new("x").version("1").tag("t"),
+        Dependency::new("x").git("https://x").rev("r").branch("b"),
+        Dependency::new("x").version("1").features(&["a b"]),
+    ] {
+        assert!(dependency.validate().is_err(), "{:?}", dependency);
+    }
+    let mut compiler = CDylibCompiler::new_with_output_folder("fixture",
+        env!("CARGO_TARGET_TMPDIR"));
+    assert!(compiler.add_dependency(Dependency::new("x")).is_err());
+}
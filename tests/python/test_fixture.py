@@ -0,0 +1,81 @@
+"""Runs the generated ctypes module against the fixture crate. The module is
+imported from the generated crate, which must be on `PYTHONPATH`."""
+
+import gc
+
+import fixture
+
+
+def raw_pointers():
+    c = fixture.Conn.new(5)
+    assert c.get() == 5
+    c.set(7)
+    assert c.get() == 7
+    raw = c.raw()
+    assert raw[0] == 1 and raw[2] == 3
+
+
+def self_by_value():
+    c = fixture.Conn.new(5)
+    assert c.finish() == 5
+    assert c._ptr is None
+    assert fixture.Conn.new(5).finish_mut() == 6
+
+
+def shared_handles():
+    s = fixture.Conn.shared(4)
+    assert s.get() == 4
+
+    p = fixture.Pool.new(9)
+    borrowed = p.conn()
+    owned = p.shared_conn()
+    del p
+    gc.collect()
+    # The borrowed handle keeps the pool alive
+    assert borrowed.get() == 9
+    assert owned.get() == 9
+
+
+def buffers():
+    c = fixture.Conn.new(5)
+    assert c.sum([1, 2]) == 8
+    assert c.sum([]) == 5
+    assert c.try_sum([1, 2]) == 8
+    try:
+        c.try_sum([])
+        assert False
+    except fixture.Error as e:
+        assert e.code == 1
+
+
+def ownership():
+    a = fixture.Conn.new(1)
+    b = fixture.Conn.new(2)
+    a.merge(b)
+    a.merge(b)
+    assert a.get() == 5
+
+    first = fixture.Pool.new(4).first()
+    gc.collect()
+    assert first.get() == 4
+
+
+def iterators():
+    c = fixture.Conn.new(5)
+    bytes = c.bytes()
+    del c
+    gc.collect()
+    assert sum(bytes) == 6
+
+    conns = [conn.get() for conn in fixture.Pool.new(3).conns()]
+    assert conns == [3]
+
+
+raw_pointers()
+self_by_value()
+shared_handles()
+buffers()
+ownership()
+iterators()
+assert fixture.debug_live_handles() == 0
+print("ok")
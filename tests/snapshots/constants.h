@@ -0,0 +1,34 @@
+#ifndef FIXTURE_H
+#define FIXTURE_H
+
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+#define MAX_CONNS 16
+
+#define MIN_OFFSET -3
+
+#define RATIO 0.5
+
+
+extern const uint8_t NAME[8];
+
+extern const uint8_t LABEL[5];
+
+extern const uintptr_t LIMIT;
+
+/**
+ * Prints every handle owned by C to stderr and returns how many there are. Handles are only tracked when the crate is built with the `debug-handles` feature, and this returns 0 otherwise.
+ */
+uintptr_t fixture_debug_live_handles(void);
+
+#ifdef __cplusplus
+}  // extern "C"
+#endif
+
+#endif  /* FIXTURE_H */
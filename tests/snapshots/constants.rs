@@ -0,0 +1,22 @@
+pub const MAX_CONNS: usize = 16;
+
+pub const MIN_OFFSET: i32 = -3;
+
+pub const RATIO: f64 = 0.5;
+
+#[no_mangle]
+pub static NAME: [u8; 8] = *b"fixture\0";
+
+#[no_mangle]
+pub static LABEL: [u8; 5] = *b"conn\0";
+
+#[no_mangle]
+pub static LIMIT: usize = fixture::LIMIT;
+mod debug_handles;
+
+/// Prints every handle owned by C to stderr and returns how many there are. Handles are only tracked when the crate is built with the `debug-handles` feature, and this returns 0 otherwise.
+#[inline]
+#[no_mangle]
+pub extern "C" fn fixture_debug_live_handles() -> usize {
+    debug_handles::live_handles()
+}
@@ -0,0 +1,333 @@
+#ifndef FIXTURE_HPP
+#define FIXTURE_HPP
+
+#include <cstddef>
+#include <cstdint>
+#include <optional>
+#include <span>
+#include <stdexcept>
+#include <string>
+#include <utility>
+
+#include "fixture.h"
+
+namespace fixture {
+
+/// Owns a C handle, freeing it with `free` on destruction. A handle with no
+/// `free` function is borrowed and never freed.
+template <typename T>
+class Handle {
+public:
+  Handle() noexcept = default;
+  Handle(T *ptr, void (*free)(T *)) noexcept : ptr_(ptr), free_(free) {}
+  Handle(const Handle &) = delete;
+  Handle &operator=(const Handle &) = delete;
+  Handle(Handle &&other) noexcept
+      : ptr_(std::exchange(other.ptr_, nullptr)), free_(other.free_) {}
+  Handle &operator=(Handle &&other) noexcept {
+    if (this != &other) {
+      reset();
+      ptr_ = std::exchange(other.ptr_, nullptr);
+      free_ = other.free_;
+    }
+    return *this;
+  }
+  ~Handle() { reset(); }
+
+  T *get() const noexcept { return ptr_; }
+  T *release() noexcept { return std::exchange(ptr_, nullptr); }
+  void reset() noexcept {
+    if (ptr_ != nullptr && free_ != nullptr) {
+      free_(ptr_);
+    }
+    ptr_ = nullptr;
+  }
+  explicit operator bool() const noexcept { return ptr_ != nullptr; }
+
+private:
+  T *ptr_ = nullptr;
+  void (*free_)(T *) = nullptr;
+};
+
+/// A non-zero error code returned by a wrapped function.
+class Error : public std::runtime_error {
+public:
+  explicit Error(uint32_t code)
+      : std::runtime_error("error code " + std::to_string(code)),
+        code_(code) {}
+  uint32_t code() const noexcept { return code_; }
+
+private:
+  uint32_t code_;
+};
+
+class Conn;
+class ConnShared;
+class Pool;
+class Conn_bytes_iter;
+class Pool_conns_iter;
+
+class Conn {
+public:
+  explicit Conn(Handle<::Conn> handle) noexcept
+      : handle_(std::move(handle)) {}
+
+  /// The C handle, which is still owned by this object.
+  ::Conn *c_ptr() const noexcept { return handle_.get(); }
+  /// Gives up ownership of the C handle.
+  ::Conn *into_c_ptr() noexcept { return handle_.release(); }
+
+  /// Calls `Conn_new`.
+  static Conn new_(uintptr_t value);
+
+  /// Calls `Conn_get`.
+  uintptr_t get();
+
+  /// Calls `Conn_set`.
+  void set(uintptr_t value);
+
+  /// Calls `Conn_raw`.
+  const uint8_t *raw();
+
+  /// Calls `Conn_fill_raw`.
+  void fill_raw(uint8_t *out, uintptr_t len);
+
+  /// Calls `Conn_finish`.
+  uintptr_t finish() &&;
+
+  /// Calls `Conn_finish_mut`.
+  uintptr_t finish_mut() &&;
+
+  /// Calls `Conn_shared`.
+  static ConnShared shared(uintptr_t value);
+
+  /// Calls `Conn_sum`.
+  uintptr_t sum(std::span<uint8_t const> bytes);
+
+  /// Calls `Conn_try_sum`.
+  uintptr_t try_sum(std::span<uint8_t const> bytes);
+
+  /// Calls `Conn_merge`.
+  void merge(const Conn &other);
+
+  /// Calls `Conn_bytes`.
+  Conn_bytes_iter bytes() &;
+  Conn_bytes_iter bytes() && = delete;
+
+private:
+  Handle<::Conn> handle_;
+};
+
+class ConnShared {
+public:
+  explicit ConnShared(Handle<::ConnShared> handle) noexcept
+      : handle_(std::move(handle)) {}
+
+  /// The C handle, which is still owned by this object.
+  ::ConnShared *c_ptr() const noexcept { return handle_.get(); }
+  /// Gives up ownership of the C handle.
+  ::ConnShared *into_c_ptr() noexcept { return handle_.release(); }
+
+  /// Calls `ConnShared_get`.
+  uintptr_t get();
+
+private:
+  Handle<::ConnShared> handle_;
+};
+
+class Pool {
+public:
+  explicit Pool(Handle<::Pool> handle) noexcept
+      : handle_(std::move(handle)) {}
+
+  /// The C handle, which is still owned by this object.
+  ::Pool *c_ptr() const noexcept { return handle_.get(); }
+  /// Gives up ownership of the C handle.
+  ::Pool *into_c_ptr() noexcept { return handle_.release(); }
+
+  /// Calls `Pool_new`.
+  static Pool new_(uintptr_t value);
+
+  /// Calls `Pool_conn`.
+  ConnShared conn() &;
+  ConnShared conn() && = delete;
+
+  /// Calls `Pool_shared_conn`.
+  ConnShared shared_conn();
+
+  /// Calls `Pool_first`.
+  Conn first();
+
+  /// Calls `Pool_conns`.
+  Pool_conns_iter conns() &;
+  Pool_conns_iter conns() && = delete;
+
+private:
+  Handle<::Pool> handle_;
+};
+
+class Conn_bytes_iter {
+public:
+  explicit Conn_bytes_iter(Handle<::Conn_bytes_iter> handle) noexcept
+      : handle_(std::move(handle)) {}
+
+  /// The C handle, which is still owned by this object.
+  ::Conn_bytes_iter *c_ptr() const noexcept { return handle_.get(); }
+  /// Gives up ownership of the C handle.
+  ::Conn_bytes_iter *into_c_ptr() noexcept { return handle_.release(); }
+
+  /// Calls `Conn_bytes_iter_next`.
+  std::optional<uint8_t> next();
+
+private:
+  Handle<::Conn_bytes_iter> handle_;
+};
+
+class Pool_conns_iter {
+public:
+  explicit Pool_conns_iter(Handle<::Pool_conns_iter> handle) noexcept
+      : handle_(std::move(handle)) {}
+
+  /// The C handle, which is still owned by this object.
+  ::Pool_conns_iter *c_ptr() const noexcept { return handle_.get(); }
+  /// Gives up ownership of the C handle.
+  ::Pool_conns_iter *into_c_ptr() noexcept { return handle_.release(); }
+
+  /// Calls `Pool_conns_iter_next`.
+  std::optional<Conn> next() &;
+  std::optional<Conn> next() && = delete;
+
+private:
+  Handle<::Pool_conns_iter> handle_;
+};
+
+uintptr_t debug_live_handles();
+
+inline Conn Conn::new_(uintptr_t value) {
+  ::Conn *ret = {};
+  ::Conn_new(value, &ret);
+  return Conn(Handle<::Conn>(ret, ::Conn_free));
+}
+
+inline uintptr_t Conn::get() {
+  uintptr_t ret = {};
+  ::Conn_get(handle_.get(), &ret);
+  return ret;
+}
+
+inline void Conn::set(uintptr_t value) {
+  ::Conn_set(handle_.get(), value);
+}
+
+inline const uint8_t *Conn::raw() {
+  const uint8_t *ret = {};
+  ::Conn_raw(handle_.get(), &ret);
+  return ret;
+}
+
+inline void Conn::fill_raw(uint8_t *out, uintptr_t len) {
+  ::Conn_fill_raw(handle_.get(), out, len);
+}
+
+inline uintptr_t Conn::finish() && {
+  uintptr_t ret = {};
+  ::Conn_finish(handle_.release(), &ret);
+  return ret;
+}
+
+inline uintptr_t Conn::finish_mut() && {
+  uintptr_t ret = {};
+  ::Conn_finish_mut(handle_.release(), &ret);
+  return ret;
+}
+
+inline ConnShared Conn::shared(uintptr_t value) {
+  ::ConnShared *ret = {};
+  ::Conn_shared(value, &ret);
+  return ConnShared(Handle<::ConnShared>(ret, ::Conn_release));
+}
+
+inline uintptr_t Conn::sum(std::span<uint8_t const> bytes) {
+  uintptr_t ret = {};
+  ::Conn_sum(handle_.get(), bytes.data(), bytes.size(), &ret);
+  return ret;
+}
+
+inline uintptr_t Conn::try_sum(std::span<uint8_t const> bytes) {
+  uintptr_t ret = {};
+  uint32_t code = ::Conn_try_sum(handle_.get(), bytes.data(), bytes.size(), &ret);
+  if (code != 0) {
+    throw Error(code);
+  }
+  return ret;
+}
+
+inline void Conn::merge(const Conn &other) {
+  ::Conn_merge(handle_.get(), other.c_ptr());
+}
+
+inline Conn_bytes_iter Conn::bytes() & {
+  ::Conn_bytes_iter *ret = {};
+  ::Conn_bytes(handle_.get(), &ret);
+  return Conn_bytes_iter(Handle<::Conn_bytes_iter>(ret, ::Conn_bytes_iter_free));
+}
+
+inline uintptr_t ConnShared::get() {
+  uintptr_t ret = {};
+  ::ConnShared_get(handle_.get(), &ret);
+  return ret;
+}
+
+inline Pool Pool::new_(uintptr_t value) {
+  ::Pool *ret = {};
+  ::Pool_new(value, &ret);
+  return Pool(Handle<::Pool>(ret, ::Pool_free));
+}
+
+inline ConnShared Pool::conn() & {
+  ::ConnShared *ret = {};
+  ::Pool_conn(handle_.get(), &ret);
+  return ConnShared(Handle<::ConnShared>(ret, nullptr));
+}
+
+inline ConnShared Pool::shared_conn() {
+  ::ConnShared *ret = {};
+  ::Pool_shared_conn(handle_.get(), &ret);
+  return ConnShared(Handle<::ConnShared>(ret, ::Conn_release));
+}
+
+inline Conn Pool::first() {
+  ::Conn *ret = {};
+  ::Pool_first(handle_.get(), &ret);
+  return Conn(Handle<::Conn>(ret, ::Conn_free));
+}
+
+inline Pool_conns_iter Pool::conns() & {
+  ::Pool_conns_iter *ret = {};
+  ::Pool_conns(handle_.get(), &ret);
+  return Pool_conns_iter(Handle<::Pool_conns_iter>(ret, ::Pool_conns_iter_free));
+}
+
+inline std::optional<uint8_t> Conn_bytes_iter::next() {
+  uint8_t ret = {};
+  if (!::Conn_bytes_iter_next(handle_.get(), &ret)) {
+    return std::nullopt;
+  }
+  return ret;
+}
+
+inline std::optional<Conn> Pool_conns_iter::next() & {
+  ::Conn *ret = {};
+  if (!::Pool_conns_iter_next(handle_.get(), &ret)) {
+    return std::nullopt;
+  }
+  return Conn(Handle<::Conn>(ret, nullptr));
+}
+
+inline uintptr_t debug_live_handles() {
+  return ::fixture_debug_live_handles();
+}
+
+}  // namespace fixture
+
+#endif  // FIXTURE_HPP
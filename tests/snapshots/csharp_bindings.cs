@@ -0,0 +1,534 @@
+// <auto-generated/>
+#nullable enable
+
+using System;
+using System.Collections;
+using System.Collections.Generic;
+using System.Runtime.InteropServices;
+
+namespace Fixture
+{
+    /// <summary>A non-zero error code returned by a wrapped function.</summary>
+    public sealed class NativeException : Exception
+    {
+        public NativeException(uint code) : base($"error code {code}")
+        {
+            Code = code;
+        }
+
+        public uint Code { get; }
+    }
+
+    internal static unsafe class Native
+    {
+        private const string Library = "fixture_c";
+
+        [DllImport(Library, CallingConvention = CallingConvention.Cdecl, ExactSpelling = true)]
+        internal static extern void Conn_new(nuint value, IntPtr* return_ptr);
+
+        [DllImport(Library, CallingConvention = CallingConvention.Cdecl, ExactSpelling = true)]
+        internal static extern void Conn_free(IntPtr self_);
+
+        [DllImport(Library, CallingConvention = CallingConvention.Cdecl, ExactSpelling = true)]
+        internal static extern void Conn_get(IntPtr self_, nuint* return_ptr);
+
+        [DllImport(Library, CallingConvention = CallingConvention.Cdecl, ExactSpelling = true)]
+        internal static extern void Conn_set(IntPtr self_, nuint value);
+
+        [DllImport(Library, CallingConvention = CallingConvention.Cdecl, ExactSpelling = true)]
+        internal static extern void Conn_raw(IntPtr self_, byte** return_ptr);
+
+        [DllImport(Library, CallingConvention = CallingConvention.Cdecl, ExactSpelling = true)]
+        internal static extern void Conn_fill_raw(IntPtr self_, byte* @out, nuint len);
+
+        [DllImport(Library, CallingConvention = CallingConvention.Cdecl, ExactSpelling = true)]
+        internal static extern void Conn_finish(IntPtr self_, nuint* return_ptr);
+
+        [DllImport(Library, CallingConvention = CallingConvention.Cdecl, ExactSpelling = true)]
+        internal static extern void Conn_finish_mut(IntPtr self_, nuint* return_ptr);
+
+        [DllImport(Library, CallingConvention = CallingConvention.Cdecl, ExactSpelling = true)]
+        internal static extern void Conn_shared(nuint value, IntPtr* return_ptr);
+
+        [DllImport(Library, CallingConvention = CallingConvention.Cdecl, ExactSpelling = true)]
+        internal static extern void Conn_retain(IntPtr self_);
+
+        [DllImport(Library, CallingConvention = CallingConvention.Cdecl, ExactSpelling = true)]
+        internal static extern void Conn_release(IntPtr self_);
+
+        [DllImport(Library, CallingConvention = CallingConvention.Cdecl, ExactSpelling = true)]
+        internal static extern void ConnShared_get(IntPtr self_, nuint* return_ptr);
+
+        [DllImport(Library, CallingConvention = CallingConvention.Cdecl, ExactSpelling = true)]
+        internal static extern void Pool_new(nuint value, IntPtr* return_ptr);
+
+        [DllImport(Library, CallingConvention = CallingConvention.Cdecl, ExactSpelling = true)]
+        internal static extern void Pool_free(IntPtr self_);
+
+        [DllImport(Library, CallingConvention = CallingConvention.Cdecl, ExactSpelling = true)]
+        internal static extern void Pool_conn(IntPtr self_, IntPtr* return_ptr);
+
+        [DllImport(Library, CallingConvention = CallingConvention.Cdecl, ExactSpelling = true)]
+        internal static extern void Pool_shared_conn(IntPtr self_, IntPtr* return_ptr);
+
+        [DllImport(Library, CallingConvention = CallingConvention.Cdecl, ExactSpelling = true)]
+        internal static extern void Conn_sum(IntPtr self_, byte* bytes, nuint bytes_len, nuint* return_ptr);
+
+        [DllImport(Library, CallingConvention = CallingConvention.Cdecl, ExactSpelling = true)]
+        internal static extern uint Conn_try_sum(IntPtr self_, byte* bytes, nuint bytes_len, nuint* return_ptr);
+
+        [DllImport(Library, CallingConvention = CallingConvention.Cdecl, ExactSpelling = true)]
+        internal static extern void Conn_merge(IntPtr self_, IntPtr other);
+
+        [DllImport(Library, CallingConvention = CallingConvention.Cdecl, ExactSpelling = true)]
+        internal static extern void Pool_first(IntPtr self_, IntPtr* return_ptr);
+
+        [DllImport(Library, CallingConvention = CallingConvention.Cdecl, ExactSpelling = true)]
+        internal static extern void Conn_bytes(IntPtr self_, IntPtr* return_ptr);
+
+        [DllImport(Library, CallingConvention = CallingConvention.Cdecl, ExactSpelling = true)]
+        internal static extern byte Conn_bytes_iter_next(IntPtr it, byte* return_ptr);
+
+        [DllImport(Library, CallingConvention = CallingConvention.Cdecl, ExactSpelling = true)]
+        internal static extern void Conn_bytes_iter_free(IntPtr it);
+
+        [DllImport(Library, CallingConvention = CallingConvention.Cdecl, ExactSpelling = true)]
+        internal static extern void Pool_conns(IntPtr self_, IntPtr* return_ptr);
+
+        [DllImport(Library, CallingConvention = CallingConvention.Cdecl, ExactSpelling = true)]
+        internal static extern byte Pool_conns_iter_next(IntPtr it, IntPtr* return_ptr);
+
+        [DllImport(Library, CallingConvention = CallingConvention.Cdecl, ExactSpelling = true)]
+        internal static extern void Pool_conns_iter_free(IntPtr it);
+
+        [DllImport(Library, CallingConvention = CallingConvention.Cdecl, ExactSpelling = true)]
+        internal static extern nuint fixture_debug_live_handles();
+    }
+
+    /// <summary>Owns a <c>Conn</c> handle.</summary>
+    public sealed class Conn : SafeHandle
+    {
+        internal Conn(IntPtr ptr, Action<IntPtr>? free, params SafeHandle[] borrows)
+            : base(IntPtr.Zero, free != null || borrows.Length > 0)
+        {
+            this.free = free;
+            this.borrows = borrows;
+            foreach (SafeHandle borrowed in borrows)
+            {
+                bool success = false;
+                borrowed.DangerousAddRef(ref success);
+            }
+            SetHandle(ptr);
+        }
+
+        private readonly Action<IntPtr>? free;
+
+        /// <summary>The handles this handle borrows from, kept alive until it is
+        /// released.</summary>
+        private readonly SafeHandle[] borrows;
+
+        public override bool IsInvalid => handle == IntPtr.Zero;
+
+        protected override bool ReleaseHandle()
+        {
+            free?.Invoke(handle);
+            foreach (SafeHandle borrowed in borrows)
+            {
+                borrowed.DangerousRelease();
+            }
+            return true;
+        }
+
+        /// <summary>Gives up ownership of the C handle.</summary>
+        internal IntPtr TakeHandle()
+        {
+            IntPtr ptr = handle;
+            SetHandleAsInvalid();
+            return ptr;
+        }
+
+        /// <summary>Calls <c>Conn_new</c>.</summary>
+        public static unsafe Conn New(nuint value)
+        {
+            IntPtr ret;
+            Native.Conn_new(value, &ret);
+            return new Conn(ret, Native.Conn_free);
+        }
+
+        /// <summary>Calls <c>Conn_get</c>.</summary>
+        public unsafe nuint Get()
+        {
+            nuint ret;
+            Native.Conn_get(handle, &ret);
+            GC.KeepAlive(this);
+            return ret;
+        }
+
+        /// <summary>Calls <c>Conn_set</c>.</summary>
+        public unsafe void Set(nuint value)
+        {
+            Native.Conn_set(handle, value);
+            GC.KeepAlive(this);
+        }
+
+        /// <summary>Calls <c>Conn_raw</c>.</summary>
+        public unsafe byte* Raw()
+        {
+            byte* ret;
+            Native.Conn_raw(handle, &ret);
+            GC.KeepAlive(this);
+            return ret;
+        }
+
+        /// <summary>Calls <c>Conn_fill_raw</c>.</summary>
+        public unsafe void FillRaw(byte* @out, nuint len)
+        {
+            Native.Conn_fill_raw(handle, @out, len);
+            GC.KeepAlive(this);
+        }
+
+        /// <summary>Calls <c>Conn_finish</c>.</summary>
+        public unsafe nuint Finish()
+        {
+            nuint ret;
+            Native.Conn_finish(TakeHandle(), &ret);
+            return ret;
+        }
+
+        /// <summary>Calls <c>Conn_finish_mut</c>.</summary>
+        public unsafe nuint FinishMut()
+        {
+            nuint ret;
+            Native.Conn_finish_mut(TakeHandle(), &ret);
+            return ret;
+        }
+
+        /// <summary>Calls <c>Conn_shared</c>.</summary>
+        public static unsafe ConnShared Shared(nuint value)
+        {
+            IntPtr ret;
+            Native.Conn_shared(value, &ret);
+            return new ConnShared(ret, Native.Conn_release);
+        }
+
+        /// <summary>Calls <c>Conn_sum</c>.</summary>
+        public unsafe nuint Sum(ReadOnlySpan<byte> bytes)
+        {
+            nuint ret;
+            fixed (byte* bytes_ptr = bytes)
+            {
+                Native.Conn_sum(handle, bytes_ptr, (nuint)bytes.Length, &ret);
+                GC.KeepAlive(this);
+                return ret;
+            }
+        }
+
+        /// <summary>Calls <c>Conn_try_sum</c>.</summary>
+        public unsafe nuint TrySum(ReadOnlySpan<byte> bytes)
+        {
+            nuint ret;
+            fixed (byte* bytes_ptr = bytes)
+            {
+                uint code = Native.Conn_try_sum(handle, bytes_ptr, (nuint)bytes.Length, &ret);
+                GC.KeepAlive(this);
+                if (code != 0)
+                {
+                    throw new NativeException(code);
+                }
+                return ret;
+            }
+        }
+
+        /// <summary>Calls <c>Conn_merge</c>.</summary>
+        public unsafe void Merge(Conn other)
+        {
+            Native.Conn_merge(handle, other.DangerousGetHandle());
+            GC.KeepAlive(this);
+            GC.KeepAlive(other);
+        }
+
+        /// <summary>Calls <c>Conn_bytes</c>.</summary>
+        public unsafe ConnBytesIter Bytes()
+        {
+            IntPtr ret;
+            Native.Conn_bytes(handle, &ret);
+            GC.KeepAlive(this);
+            return new ConnBytesIter(ret, Native.Conn_bytes_iter_free, this);
+        }
+    }
+
+    /// <summary>Owns a <c>ConnShared</c> handle.</summary>
+    public sealed class ConnShared : SafeHandle
+    {
+        internal ConnShared(IntPtr ptr, Action<IntPtr>? free, params SafeHandle[] borrows)
+            : base(IntPtr.Zero, free != null || borrows.Length > 0)
+        {
+            this.free = free;
+            this.borrows = borrows;
+            foreach (SafeHandle borrowed in borrows)
+            {
+                bool success = false;
+                borrowed.DangerousAddRef(ref success);
+            }
+            SetHandle(ptr);
+        }
+
+        private readonly Action<IntPtr>? free;
+
+        /// <summary>The handles this handle borrows from, kept alive until it is
+        /// released.</summary>
+        private readonly SafeHandle[] borrows;
+
+        public override bool IsInvalid => handle == IntPtr.Zero;
+
+        protected override bool ReleaseHandle()
+        {
+            free?.Invoke(handle);
+            foreach (SafeHandle borrowed in borrows)
+            {
+                borrowed.DangerousRelease();
+            }
+            return true;
+        }
+
+        /// <summary>Gives up ownership of the C handle.</summary>
+        internal IntPtr TakeHandle()
+        {
+            IntPtr ptr = handle;
+            SetHandleAsInvalid();
+            return ptr;
+        }
+
+        /// <summary>Calls <c>ConnShared_get</c>.</summary>
+        public unsafe nuint Get()
+        {
+            nuint ret;
+            Native.ConnShared_get(handle, &ret);
+            GC.KeepAlive(this);
+            return ret;
+        }
+    }
+
+    /// <summary>Owns a <c>Pool</c> handle.</summary>
+    public sealed class Pool : SafeHandle
+    {
+        internal Pool(IntPtr ptr, Action<IntPtr>? free, params SafeHandle[] borrows)
+            : base(IntPtr.Zero, free != null || borrows.Length > 0)
+        {
+            this.free = free;
+            this.borrows = borrows;
+            foreach (SafeHandle borrowed in borrows)
+            {
+                bool success = false;
+                borrowed.DangerousAddRef(ref success);
+            }
+            SetHandle(ptr);
+        }
+
+        private readonly Action<IntPtr>? free;
+
+        /// <summary>The handles this handle borrows from, kept alive until it is
+        /// released.</summary>
+        private readonly SafeHandle[] borrows;
+
+        public override bool IsInvalid => handle == IntPtr.Zero;
+
+        protected override bool ReleaseHandle()
+        {
+            free?.Invoke(handle);
+            foreach (SafeHandle borrowed in borrows)
+            {
+                borrowed.DangerousRelease();
+            }
+            return true;
+        }
+
+        /// <summary>Gives up ownership of the C handle.</summary>
+        internal IntPtr TakeHandle()
+        {
+            IntPtr ptr = handle;
+            SetHandleAsInvalid();
+            return ptr;
+        }
+
+        /// <summary>Calls <c>Pool_new</c>.</summary>
+        public static unsafe Pool New(nuint value)
+        {
+            IntPtr ret;
+            Native.Pool_new(value, &ret);
+            return new Pool(ret, Native.Pool_free);
+        }
+
+        /// <summary>Calls <c>Pool_conn</c>.</summary>
+        public unsafe ConnShared Conn()
+        {
+            IntPtr ret;
+            Native.Pool_conn(handle, &ret);
+            GC.KeepAlive(this);
+            return new ConnShared(ret, null, this);
+        }
+
+        /// <summary>Calls <c>Pool_shared_conn</c>.</summary>
+        public unsafe ConnShared SharedConn()
+        {
+            IntPtr ret;
+            Native.Pool_shared_conn(handle, &ret);
+            GC.KeepAlive(this);
+            return new ConnShared(ret, Native.Conn_release);
+        }
+
+        /// <summary>Calls <c>Pool_first</c>.</summary>
+        public unsafe Conn First()
+        {
+            IntPtr ret;
+            Native.Pool_first(handle, &ret);
+            GC.KeepAlive(this);
+            return new Conn(ret, Native.Conn_free);
+        }
+
+        /// <summary>Calls <c>Pool_conns</c>.</summary>
+        public unsafe PoolConnsIter Conns()
+        {
+            IntPtr ret;
+            Native.Pool_conns(handle, &ret);
+            GC.KeepAlive(this);
+            return new PoolConnsIter(ret, Native.Pool_conns_iter_free, this);
+        }
+    }
+
+    /// <summary>Owns a <c>Conn_bytes_iter</c> handle.</summary>
+    public sealed class ConnBytesIter : SafeHandle, IEnumerable<byte>
+    {
+        internal ConnBytesIter(IntPtr ptr, Action<IntPtr>? free, params SafeHandle[] borrows)
+            : base(IntPtr.Zero, free != null || borrows.Length > 0)
+        {
+            this.free = free;
+            this.borrows = borrows;
+            foreach (SafeHandle borrowed in borrows)
+            {
+                bool success = false;
+                borrowed.DangerousAddRef(ref success);
+            }
+            SetHandle(ptr);
+        }
+
+        private readonly Action<IntPtr>? free;
+
+        /// <summary>The handles this handle borrows from, kept alive until it is
+        /// released.</summary>
+        private readonly SafeHandle[] borrows;
+
+        public override bool IsInvalid => handle == IntPtr.Zero;
+
+        protected override bool ReleaseHandle()
+        {
+            free?.Invoke(handle);
+            foreach (SafeHandle borrowed in borrows)
+            {
+                borrowed.DangerousRelease();
+            }
+            return true;
+        }
+
+        /// <summary>Gives up ownership of the C handle.</summary>
+        internal IntPtr TakeHandle()
+        {
+            IntPtr ptr = handle;
+            SetHandleAsInvalid();
+            return ptr;
+        }
+
+        /// <summary>Calls <c>Conn_bytes_iter_next</c>.</summary>
+        public unsafe bool TryNext(out byte item)
+        {
+            byte ret;
+            bool ok = Native.Conn_bytes_iter_next(handle, &ret) != 0;
+            GC.KeepAlive(this);
+            item = ok ? ret : default!;
+            return ok;
+        }
+
+        public IEnumerator<byte> GetEnumerator()
+        {
+            while (TryNext(out var item))
+            {
+                yield return item;
+            }
+        }
+
+        IEnumerator IEnumerable.GetEnumerator() => GetEnumerator();
+    }
+
+    /// <summary>Owns a <c>Pool_conns_iter</c> handle.</summary>
+    public sealed class PoolConnsIter : SafeHandle, IEnumerable<Conn>
+    {
+        internal PoolConnsIter(IntPtr ptr, Action<IntPtr>? free, params SafeHandle[] borrows)
+            : base(IntPtr.Zero, free != null || borrows.Length > 0)
+        {
+            this.free = free;
+            this.borrows = borrows;
+            foreach (SafeHandle borrowed in borrows)
+            {
+                bool success = false;
+                borrowed.DangerousAddRef(ref success);
+            }
+            SetHandle(ptr);
+        }
+
+        private readonly Action<IntPtr>? free;
+
+        /// <summary>The handles this handle borrows from, kept alive until it is
+        /// released.</summary>
+        private readonly SafeHandle[] borrows;
+
+        public override bool IsInvalid => handle == IntPtr.Zero;
+
+        protected override bool ReleaseHandle()
+        {
+            free?.Invoke(handle);
+            foreach (SafeHandle borrowed in borrows)
+            {
+                borrowed.DangerousRelease();
+            }
+            return true;
+        }
+
+        /// <summary>Gives up ownership of the C handle.</summary>
+        internal IntPtr TakeHandle()
+        {
+            IntPtr ptr = handle;
+            SetHandleAsInvalid();
+            return ptr;
+        }
+
+        /// <summary>Calls <c>Pool_conns_iter_next</c>.</summary>
+        public unsafe bool TryNext(out Conn item)
+        {
+            IntPtr ret;
+            bool ok = Native.Pool_conns_iter_next(handle, &ret) != 0;
+            GC.KeepAlive(this);
+            item = ok ? new Conn(ret, null, this) : default!;
+            return ok;
+        }
+
+        public IEnumerator<Conn> GetEnumerator()
+        {
+            while (TryNext(out var item))
+            {
+                yield return item;
+            }
+        }
+
+        IEnumerator IEnumerable.GetEnumerator() => GetEnumerator();
+    }
+
+    public static class Functions
+    {
+        /// <summary>Calls <c>fixture_debug_live_handles</c>.</summary>
+        public static unsafe nuint DebugLiveHandles()
+        {
+            nuint result = Native.fixture_debug_live_handles();
+            return result;
+        }
+    }
+}
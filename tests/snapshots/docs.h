@@ -0,0 +1,46 @@
+#ifndef FIXTURE_H
+#define FIXTURE_H
+
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+typedef struct Conn Conn;
+
+/**
+ * Returns the value of the connection.
+ *
+ * Writes the return value to `return_ptr`.
+ */
+void Conn_get(Conn *self_, uintptr_t *return_ptr);
+
+/**
+ * Like `sum`, but fails if the bytes are empty.
+ *
+ * Writes the return value to `return_ptr`.
+ * Returns 0 on success, or 1 if `try_sum` returned an error.
+ *
+ * Ownership:
+ * - `bytes`: borrowed for the duration of the call.
+ */
+uint32_t Conn_try_sum(Conn *self_, const uint8_t *bytes, uintptr_t bytes_len, uintptr_t *return_ptr);
+
+/**
+ * Replaces the value of the connection.
+ */
+void Conn_set(Conn *self_, uintptr_t value);
+
+/**
+ * Prints every handle owned by C to stderr and returns how many there are. Handles are only tracked when the crate is built with the `debug-handles` feature, and this returns 0 otherwise.
+ */
+uintptr_t fixture_debug_live_handles(void);
+
+#ifdef __cplusplus
+}  // extern "C"
+#endif
+
+#endif  /* FIXTURE_H */
@@ -0,0 +1,63 @@
+use fixture::Conn;
+/// Returns the value of the connection.
+///
+/// Writes the return value to `return_ptr`.
+#[inline]
+#[no_mangle]
+pub extern "C" fn Conn_get(self_: *mut ::std::os::raw::c_void, return_ptr: *mut usize) {
+    debug_handles::check(self_ as _, "Conn", "Conn_get");
+    let self_ = unsafe { &*(self_ as *const Conn) };
+    let value = self_.get();
+    unsafe { *return_ptr = value };
+}
+
+/// Like `sum`, but fails if the bytes are empty.
+///
+/// Writes the return value to `return_ptr`.
+/// Returns 0 on success, or 1 if `try_sum` returned an error.
+///
+/// Ownership:
+/// - `bytes`: borrowed for the duration of the call.
+#[inline]
+#[no_mangle]
+pub extern "C" fn Conn_try_sum(
+    self_: *mut ::std::os::raw::c_void,
+    bytes: *const u8,
+    bytes_len: usize,
+    return_ptr: *mut usize,
+) -> u32 {
+    debug_handles::check(self_ as _, "Conn", "Conn_try_sum");
+    let self_ = unsafe { &*(self_ as *const Conn) };
+    let arg0 = if bytes_len == 0 {
+        &[]
+    } else {
+        unsafe { std::slice::from_raw_parts(bytes as *const u8, bytes_len) }
+    };
+    let value = self_.try_sum(arg0);
+    let value = match value {
+        Ok(value) => value,
+        Err(_) => {
+            return 1;
+        }
+    };
+    unsafe { *return_ptr = value };
+    0
+}
+
+/// Replaces the value of the connection.
+#[inline]
+#[no_mangle]
+pub extern "C" fn Conn_set(self_: *mut ::std::os::raw::c_void, value: usize) {
+    debug_handles::check(self_ as _, "Conn", "Conn_set");
+    let self_ = unsafe { &mut *(self_ as *mut Conn) };
+    let arg0 = value;
+    self_.set(arg0);
+}
+mod debug_handles;
+
+/// Prints every handle owned by C to stderr and returns how many there are. Handles are only tracked when the crate is built with the `debug-handles` feature, and this returns 0 otherwise.
+#[inline]
+#[no_mangle]
+pub extern "C" fn fixture_debug_live_handles() -> usize {
+    debug_handles::live_handles()
+}
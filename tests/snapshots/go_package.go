@@ -0,0 +1,365 @@
+// Package fixture calls libfixture_c through cgo.
+package fixture
+
+/*
+#cgo CFLAGS: -I${SRCDIR}/..
+#cgo LDFLAGS: -L${SRCDIR}/../target/release -L${SRCDIR}/../target/debug -Wl,-rpath,${SRCDIR}/../target/release -Wl,-rpath,${SRCDIR}/../target/debug -lfixture_c
+#include "fixture.h"
+*/
+import "C"
+
+import (
+	"fmt"
+	"runtime"
+	"unsafe"
+)
+
+// Error is a non-zero error code returned by a wrapped function.
+type Error struct {
+	Code uint32
+}
+
+func (e *Error) Error() string {
+	return fmt.Sprintf("error code %d", e.Code)
+}
+
+// Conn owns a `Conn` handle, freeing it when garbage collected.
+type Conn struct {
+	ptr     *C.Conn
+	free    func(*C.Conn)
+	borrows []any
+}
+
+// newConn wraps a handle. A handle with no free function is borrowed and
+// never freed. A handle that borrows from other handles keeps them alive.
+func newConn(ptr *C.Conn, free func(*C.Conn), borrows ...any) *Conn {
+	h := &Conn{ptr: ptr, free: free, borrows: borrows}
+	if free != nil {
+		runtime.SetFinalizer(h, (*Conn).Close)
+	}
+	return h
+}
+
+// Close frees the handle now rather than when it is garbage collected.
+func (h *Conn) Close() {
+	if h.ptr != nil && h.free != nil {
+		h.free(h.ptr)
+	}
+	h.ptr = nil
+	runtime.SetFinalizer(h, nil)
+}
+
+// CPtr returns the C handle, which is still owned by h.
+func (h *Conn) CPtr() *C.Conn {
+	return h.ptr
+}
+
+// take gives up ownership of the C handle.
+func (h *Conn) take() *C.Conn {
+	ptr := h.ptr
+	h.ptr = nil
+	runtime.SetFinalizer(h, nil)
+	return ptr
+}
+
+// ConnNew calls `Conn_new`.
+func ConnNew(value uintptr) *Conn {
+	var ret *C.Conn
+	C.Conn_new(C.uintptr_t(value), &ret)
+	return newConn(ret, func(ptr *C.Conn) { C.Conn_free(ptr) })
+}
+
+// Get calls `Conn_get`.
+func (h *Conn) Get() uintptr {
+	var ret C.uintptr_t
+	C.Conn_get(h.ptr, &ret)
+	return uintptr(ret)
+}
+
+// Set calls `Conn_set`.
+func (h *Conn) Set(value uintptr) {
+	C.Conn_set(h.ptr, C.uintptr_t(value))
+}
+
+// Raw calls `Conn_raw`.
+func (h *Conn) Raw() unsafe.Pointer {
+	var ret *C.uint8_t
+	C.Conn_raw(h.ptr, &ret)
+	return unsafe.Pointer(ret)
+}
+
+// FillRaw calls `Conn_fill_raw`.
+func (h *Conn) FillRaw(out unsafe.Pointer, len uintptr) {
+	C.Conn_fill_raw(h.ptr, (*C.uint8_t)(out), C.uintptr_t(len))
+}
+
+// Finish calls `Conn_finish`.
+func (h *Conn) Finish() uintptr {
+	var ret C.uintptr_t
+	C.Conn_finish(h.take(), &ret)
+	return uintptr(ret)
+}
+
+// FinishMut calls `Conn_finish_mut`.
+func (h *Conn) FinishMut() uintptr {
+	var ret C.uintptr_t
+	C.Conn_finish_mut(h.take(), &ret)
+	return uintptr(ret)
+}
+
+// ConnSharedFunc calls `Conn_shared`.
+func ConnSharedFunc(value uintptr) *ConnShared {
+	var ret *C.ConnShared
+	C.Conn_shared(C.uintptr_t(value), &ret)
+	return newConnShared(ret, func(ptr *C.ConnShared) { C.Conn_release(ptr) })
+}
+
+// Sum calls `Conn_sum`.
+func (h *Conn) Sum(bytes []uint8) uintptr {
+	var ret C.uintptr_t
+	C.Conn_sum(h.ptr, (*C.uint8_t)(unsafe.Pointer(unsafe.SliceData(bytes))), C.uintptr_t(len(bytes)), &ret)
+	return uintptr(ret)
+}
+
+// TrySum calls `Conn_try_sum`.
+func (h *Conn) TrySum(bytes []uint8) (result uintptr, err error) {
+	var ret C.uintptr_t
+	if code := C.Conn_try_sum(h.ptr, (*C.uint8_t)(unsafe.Pointer(unsafe.SliceData(bytes))), C.uintptr_t(len(bytes)), &ret); code != 0 {
+		err = &Error{Code: uint32(code)}
+		return
+	}
+	return uintptr(ret), nil
+}
+
+// Merge calls `Conn_merge`.
+func (h *Conn) Merge(other *Conn) {
+	C.Conn_merge(h.ptr, other.ptr)
+}
+
+// Bytes calls `Conn_bytes`.
+func (h *Conn) Bytes() *ConnBytesIter {
+	var ret *C.Conn_bytes_iter
+	C.Conn_bytes(h.ptr, &ret)
+	return newConnBytesIter(ret, func(ptr *C.Conn_bytes_iter) { C.Conn_bytes_iter_free(ptr) }, h)
+}
+
+// ConnShared owns a `ConnShared` handle, freeing it when garbage collected.
+type ConnShared struct {
+	ptr     *C.ConnShared
+	free    func(*C.ConnShared)
+	borrows []any
+}
+
+// newConnShared wraps a handle. A handle with no free function is borrowed and
+// never freed. A handle that borrows from other handles keeps them alive.
+func newConnShared(ptr *C.ConnShared, free func(*C.ConnShared), borrows ...any) *ConnShared {
+	h := &ConnShared{ptr: ptr, free: free, borrows: borrows}
+	if free != nil {
+		runtime.SetFinalizer(h, (*ConnShared).Close)
+	}
+	return h
+}
+
+// Close frees the handle now rather than when it is garbage collected.
+func (h *ConnShared) Close() {
+	if h.ptr != nil && h.free != nil {
+		h.free(h.ptr)
+	}
+	h.ptr = nil
+	runtime.SetFinalizer(h, nil)
+}
+
+// CPtr returns the C handle, which is still owned by h.
+func (h *ConnShared) CPtr() *C.ConnShared {
+	return h.ptr
+}
+
+// take gives up ownership of the C handle.
+func (h *ConnShared) take() *C.ConnShared {
+	ptr := h.ptr
+	h.ptr = nil
+	runtime.SetFinalizer(h, nil)
+	return ptr
+}
+
+// Get calls `ConnShared_get`.
+func (h *ConnShared) Get() uintptr {
+	var ret C.uintptr_t
+	C.ConnShared_get(h.ptr, &ret)
+	return uintptr(ret)
+}
+
+// Pool owns a `Pool` handle, freeing it when garbage collected.
+type Pool struct {
+	ptr     *C.Pool
+	free    func(*C.Pool)
+	borrows []any
+}
+
+// newPool wraps a handle. A handle with no free function is borrowed and
+// never freed. A handle that borrows from other handles keeps them alive.
+func newPool(ptr *C.Pool, free func(*C.Pool), borrows ...any) *Pool {
+	h := &Pool{ptr: ptr, free: free, borrows: borrows}
+	if free != nil {
+		runtime.SetFinalizer(h, (*Pool).Close)
+	}
+	return h
+}
+
+// Close frees the handle now rather than when it is garbage collected.
+func (h *Pool) Close() {
+	if h.ptr != nil && h.free != nil {
+		h.free(h.ptr)
+	}
+	h.ptr = nil
+	runtime.SetFinalizer(h, nil)
+}
+
+// CPtr returns the C handle, which is still owned by h.
+func (h *Pool) CPtr() *C.Pool {
+	return h.ptr
+}
+
+// take gives up ownership of the C handle.
+func (h *Pool) take() *C.Pool {
+	ptr := h.ptr
+	h.ptr = nil
+	runtime.SetFinalizer(h, nil)
+	return ptr
+}
+
+// PoolNew calls `Pool_new`.
+func PoolNew(value uintptr) *Pool {
+	var ret *C.Pool
+	C.Pool_new(C.uintptr_t(value), &ret)
+	return newPool(ret, func(ptr *C.Pool) { C.Pool_free(ptr) })
+}
+
+// Conn calls `Pool_conn`.
+func (h *Pool) Conn() *ConnShared {
+	var ret *C.ConnShared
+	C.Pool_conn(h.ptr, &ret)
+	return newConnShared(ret, nil, h)
+}
+
+// SharedConn calls `Pool_shared_conn`.
+func (h *Pool) SharedConn() *ConnShared {
+	var ret *C.ConnShared
+	C.Pool_shared_conn(h.ptr, &ret)
+	return newConnShared(ret, func(ptr *C.ConnShared) { C.Conn_release(ptr) })
+}
+
+// First calls `Pool_first`.
+func (h *Pool) First() *Conn {
+	var ret *C.Conn
+	C.Pool_first(h.ptr, &ret)
+	return newConn(ret, func(ptr *C.Conn) { C.Conn_free(ptr) })
+}
+
+// Conns calls `Pool_conns`.
+func (h *Pool) Conns() *PoolConnsIter {
+	var ret *C.Pool_conns_iter
+	C.Pool_conns(h.ptr, &ret)
+	return newPoolConnsIter(ret, func(ptr *C.Pool_conns_iter) { C.Pool_conns_iter_free(ptr) }, h)
+}
+
+// ConnBytesIter owns a `Conn_bytes_iter` handle, freeing it when garbage collected.
+type ConnBytesIter struct {
+	ptr     *C.Conn_bytes_iter
+	free    func(*C.Conn_bytes_iter)
+	borrows []any
+}
+
+// newConnBytesIter wraps a handle. A handle with no free function is borrowed and
+// never freed. A handle that borrows from other handles keeps them alive.
+func newConnBytesIter(ptr *C.Conn_bytes_iter, free func(*C.Conn_bytes_iter), borrows ...any) *ConnBytesIter {
+	h := &ConnBytesIter{ptr: ptr, free: free, borrows: borrows}
+	if free != nil {
+		runtime.SetFinalizer(h, (*ConnBytesIter).Close)
+	}
+	return h
+}
+
+// Close frees the handle now rather than when it is garbage collected.
+func (h *ConnBytesIter) Close() {
+	if h.ptr != nil && h.free != nil {
+		h.free(h.ptr)
+	}
+	h.ptr = nil
+	runtime.SetFinalizer(h, nil)
+}
+
+// CPtr returns the C handle, which is still owned by h.
+func (h *ConnBytesIter) CPtr() *C.Conn_bytes_iter {
+	return h.ptr
+}
+
+// take gives up ownership of the C handle.
+func (h *ConnBytesIter) take() *C.Conn_bytes_iter {
+	ptr := h.ptr
+	h.ptr = nil
+	runtime.SetFinalizer(h, nil)
+	return ptr
+}
+
+// Next calls `Conn_bytes_iter_next`.
+func (h *ConnBytesIter) Next() (item uint8, ok bool) {
+	var ret C.uint8_t
+	if !C.Conn_bytes_iter_next(h.ptr, &ret) {
+		return
+	}
+	return uint8(ret), true
+}
+
+// PoolConnsIter owns a `Pool_conns_iter` handle, freeing it when garbage collected.
+type PoolConnsIter struct {
+	ptr     *C.Pool_conns_iter
+	free    func(*C.Pool_conns_iter)
+	borrows []any
+}
+
+// newPoolConnsIter wraps a handle. A handle with no free function is borrowed and
+// never freed. A handle that borrows from other handles keeps them alive.
+func newPoolConnsIter(ptr *C.Pool_conns_iter, free func(*C.Pool_conns_iter), borrows ...any) *PoolConnsIter {
+	h := &PoolConnsIter{ptr: ptr, free: free, borrows: borrows}
+	if free != nil {
+		runtime.SetFinalizer(h, (*PoolConnsIter).Close)
+	}
+	return h
+}
+
+// Close frees the handle now rather than when it is garbage collected.
+func (h *PoolConnsIter) Close() {
+	if h.ptr != nil && h.free != nil {
+		h.free(h.ptr)
+	}
+	h.ptr = nil
+	runtime.SetFinalizer(h, nil)
+}
+
+// CPtr returns the C handle, which is still owned by h.
+func (h *PoolConnsIter) CPtr() *C.Pool_conns_iter {
+	return h.ptr
+}
+
+// take gives up ownership of the C handle.
+func (h *PoolConnsIter) take() *C.Pool_conns_iter {
+	ptr := h.ptr
+	h.ptr = nil
+	runtime.SetFinalizer(h, nil)
+	return ptr
+}
+
+// Next calls `Pool_conns_iter_next`.
+func (h *PoolConnsIter) Next() (item *Conn, ok bool) {
+	var ret *C.Conn
+	if !C.Pool_conns_iter_next(h.ptr, &ret) {
+		return
+	}
+	return newConn(ret, nil, h), true
+}
+
+// DebugLiveHandles calls `fixture_debug_live_handles`.
+func DebugLiveHandles() uintptr {
+	return uintptr(C.fixture_debug_live_handles())
+}
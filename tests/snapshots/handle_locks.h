@@ -0,0 +1,95 @@
+#ifndef FIXTURE_H
+#define FIXTURE_H
+
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+typedef struct Conn Conn;
+typedef struct Pool Pool;
+typedef struct ConnShared ConnShared;
+
+/**
+ * Writes the return value to `return_ptr`.
+ *
+ * Ownership:
+ * - `return_ptr`: receives a new handle owned by the caller, which must be freed with `Conn_free`.
+ */
+void Conn_new(uintptr_t value, Conn **return_ptr);
+
+/**
+ * Frees the handle. The handle is invalid after this call.
+ */
+void Conn_free(Conn *self_);
+
+/**
+ * Writes the return value to `return_ptr`.
+ */
+void Conn_get(Conn *self_, uintptr_t *return_ptr);
+
+void Conn_set(Conn *self_, uintptr_t value);
+
+/**
+ * Writes the return value to `return_ptr`.
+ */
+void Conn_raw(Conn *self_, const uint8_t **return_ptr);
+
+void Conn_fill_raw(Conn *self_, uint8_t *out, uintptr_t len);
+
+/**
+ * Consumes `self_`. The handle is invalid after this call.
+ * Writes the return value to `return_ptr`.
+ */
+void Conn_finish(Conn *self_, uintptr_t *return_ptr);
+
+/**
+ * Consumes `self_`. The handle is invalid after this call.
+ * Writes the return value to `return_ptr`.
+ */
+void Conn_finish_mut(Conn *self_, uintptr_t *return_ptr);
+
+/**
+ * Writes the return value to `return_ptr`.
+ *
+ * Ownership:
+ * - `return_ptr`: receives a new handle owned by the caller, which must be freed with `Pool_free`.
+ */
+void Pool_new(uintptr_t value, Pool **return_ptr);
+
+/**
+ * Frees the handle. The handle is invalid after this call.
+ */
+void Pool_free(Pool *self_);
+
+/**
+ * Writes the return value to `return_ptr`.
+ *
+ * Ownership:
+ * - `return_ptr`: receives a new handle owned by the caller, which must be freed with `Conn_release`.
+ */
+void Pool_shared_conn(Pool *self_, ConnShared **return_ptr);
+
+/**
+ * Increments the strong reference count of the handle.
+ */
+void Conn_retain(ConnShared *self_);
+
+/**
+ * Decrements the strong reference count of the handle. The handle is invalid after this call.
+ */
+void Conn_release(ConnShared *self_);
+
+/**
+ * Prints every handle owned by C to stderr and returns how many there are. Handles are only tracked when the crate is built with the `debug-handles` feature, and this returns 0 otherwise.
+ */
+uintptr_t fixture_debug_live_handles(void);
+
+#ifdef __cplusplus
+}  // extern "C"
+#endif
+
+#endif  /* FIXTURE_H */
@@ -0,0 +1,215 @@
+use fixture::Conn;
+use fixture::Pool;
+/// Writes the return value to `return_ptr`.
+///
+/// Ownership:
+/// - `return_ptr`: receives a new handle owned by the caller, which must be freed with `Conn_free`.
+#[inline]
+#[no_mangle]
+pub extern "C" fn Conn_new(value: usize, return_ptr: *mut *mut ::std::os::raw::c_void) {
+    let arg0 = value;
+    let value = Conn::new(arg0);
+    let value = Box::into_raw(Box::new(std::sync::Mutex::new(value)));
+    debug_handles::register(value as _, "Box", "Conn");
+    unsafe { *return_ptr = value as _ };
+}
+
+/// Frees the handle. The handle is invalid after this call.
+#[inline]
+#[no_mangle]
+pub extern "C" fn Conn_free(self_: *mut ::std::os::raw::c_void) {
+    debug_handles::release(self_ as _, "Box", "Conn", "Conn_free");
+    drop(unsafe {
+        Box::new(
+            Box::from_raw(self_ as *mut std::sync::Mutex<Conn>)
+                .into_inner()
+                .unwrap_or_else(|_| {
+                    eprintln!("Conn_free: the lock of `self_` is poisoned");
+                    std::process::abort()
+                }),
+        )
+    });
+}
+
+/// Writes the return value to `return_ptr`.
+#[inline]
+#[no_mangle]
+pub extern "C" fn Conn_get(self_: *mut ::std::os::raw::c_void, return_ptr: *mut usize) {
+    debug_handles::check(self_ as _, "Conn", "Conn_get");
+    let self_ = unsafe { &*(self_ as *const std::sync::Mutex<Conn>) }
+        .lock()
+        .unwrap_or_else(|_| {
+            eprintln!("Conn_get: the lock of `self_` is poisoned");
+            std::process::abort()
+        });
+    let value = self_.get();
+    unsafe { *return_ptr = value };
+}
+
+#[inline]
+#[no_mangle]
+pub extern "C" fn Conn_set(self_: *mut ::std::os::raw::c_void, value: usize) {
+    debug_handles::check(self_ as _, "Conn", "Conn_set");
+    let mut self_ = unsafe { &*(self_ as *const std::sync::Mutex<Conn>) }
+        .lock()
+        .unwrap_or_else(|_| {
+            eprintln!("Conn_set: the lock of `self_` is poisoned");
+            std::process::abort()
+        });
+    let arg0 = value;
+    self_.set(arg0);
+}
+
+/// Writes the return value to `return_ptr`.
+#[inline]
+#[no_mangle]
+pub extern "C" fn Conn_raw(self_: *mut ::std::os::raw::c_void, return_ptr: *mut *const u8) {
+    debug_handles::check(self_ as _, "Conn", "Conn_raw");
+    let self_ = unsafe { &*(self_ as *const std::sync::Mutex<Conn>) }
+        .lock()
+        .unwrap_or_else(|_| {
+            eprintln!("Conn_raw: the lock of `self_` is poisoned");
+            std::process::abort()
+        });
+    let value = self_.raw();
+    unsafe { *return_ptr = value };
+}
+
+#[inline]
+#[no_mangle]
+pub extern "C" fn Conn_fill_raw(self_: *mut ::std::os::raw::c_void, out: *mut u8, len: usize) {
+    debug_handles::check(self_ as _, "Conn", "Conn_fill_raw");
+    let self_ = unsafe { &*(self_ as *const std::sync::Mutex<Conn>) }
+        .lock()
+        .unwrap_or_else(|_| {
+            eprintln!("Conn_fill_raw: the lock of `self_` is poisoned");
+            std::process::abort()
+        });
+    let arg0 = out;
+    let arg1 = len;
+    self_.fill_raw(arg0, arg1);
+}
+
+/// Consumes `self_`. The handle is invalid after this call.
+/// Writes the return value to `return_ptr`.
+#[inline]
+#[no_mangle]
+pub extern "C" fn Conn_finish(self_: *mut ::std::os::raw::c_void, return_ptr: *mut usize) {
+    debug_handles::release(self_ as _, "Box", "Conn", "Conn_finish");
+    let self_ = unsafe {
+        Box::from_raw(self_ as *mut std::sync::Mutex<Conn>)
+            .into_inner()
+            .unwrap_or_else(|_| {
+                eprintln!("Conn_finish: the lock of `self_` is poisoned");
+                std::process::abort()
+            })
+    };
+    let value = self_.finish();
+    unsafe { *return_ptr = value };
+}
+
+/// Consumes `self_`. The handle is invalid after this call.
+/// Writes the return value to `return_ptr`.
+#[inline]
+#[no_mangle]
+pub extern "C" fn Conn_finish_mut(self_: *mut ::std::os::raw::c_void, return_ptr: *mut usize) {
+    debug_handles::release(self_ as _, "Box", "Conn", "Conn_finish_mut");
+    let self_ = unsafe {
+        Box::from_raw(self_ as *mut std::sync::Mutex<Conn>)
+            .into_inner()
+            .unwrap_or_else(|_| {
+                eprintln!("Conn_finish_mut: the lock of `self_` is poisoned");
+                std::process::abort()
+            })
+    };
+    let value = self_.finish_mut();
+    unsafe { *return_ptr = value };
+}
+
+/// Writes the return value to `return_ptr`.
+///
+/// Ownership:
+/// - `return_ptr`: receives a new handle owned by the caller, which must be freed with `Pool_free`.
+#[inline]
+#[no_mangle]
+pub extern "C" fn Pool_new(value: usize, return_ptr: *mut *mut ::std::os::raw::c_void) {
+    let arg0 = value;
+    let value = Pool::new(arg0);
+    let value = Box::into_raw(Box::new(std::sync::RwLock::new(value)));
+    debug_handles::register(value as _, "Box", "Pool");
+    unsafe { *return_ptr = value as _ };
+}
+
+/// Frees the handle. The handle is invalid after this call.
+#[inline]
+#[no_mangle]
+pub extern "C" fn Pool_free(self_: *mut ::std::os::raw::c_void) {
+    debug_handles::release(self_ as _, "Box", "Pool", "Pool_free");
+    drop(unsafe {
+        Box::new(
+            Box::from_raw(self_ as *mut std::sync::RwLock<Pool>)
+                .into_inner()
+                .unwrap_or_else(|_| {
+                    eprintln!("Pool_free: the lock of `self_` is poisoned");
+                    std::process::abort()
+                }),
+        )
+    });
+}
+
+/// Writes the return value to `return_ptr`.
+///
+/// Ownership:
+/// - `return_ptr`: receives a new handle owned by the caller, which must be freed with `Conn_release`.
+#[inline]
+#[no_mangle]
+pub extern "C" fn Pool_shared_conn(
+    self_: *mut ::std::os::raw::c_void,
+    return_ptr: *mut *mut ::std::os::raw::c_void,
+) {
+    debug_handles::check(self_ as _, "Pool", "Pool_shared_conn");
+    let self_ = unsafe { &*(self_ as *const std::sync::RwLock<Pool>) }
+        .read()
+        .unwrap_or_else(|_| {
+            eprintln!("Pool_shared_conn: the lock of `self_` is poisoned");
+            std::process::abort()
+        });
+    let value = self_.shared_conn();
+    let value = std::sync::Arc::into_raw(value) as *mut Conn;
+    debug_handles::register(value as _, "Arc", "Conn");
+    unsafe { *return_ptr = value as _ };
+}
+
+/// Increments the strong reference count of the handle.
+#[inline]
+#[no_mangle]
+pub extern "C" fn Conn_retain(self_: *mut ::std::os::raw::c_void) {
+    debug_handles::retain(self_ as _, "Arc", "Conn", "Conn_retain");
+    unsafe { std::sync::Arc::increment_strong_count(self_ as *const Conn) };
+}
+
+/// Decrements the strong reference count of the handle. The handle is invalid after this call.
+#[inline]
+#[no_mangle]
+pub extern "C" fn Conn_release(self_: *mut ::std::os::raw::c_void) {
+    debug_handles::release(self_ as _, "Arc", "Conn", "Conn_release");
+    drop(unsafe { std::sync::Arc::from_raw(self_ as *const Conn) });
+}
+
+const _: fn() = || {
+    fn assert_shareable<T: ?Sized + Send + Sync>() {}
+    assert_shareable::<std::sync::Mutex<Conn>>();
+};
+
+const _: fn() = || {
+    fn assert_shareable<T: ?Sized + Send + Sync>() {}
+    assert_shareable::<std::sync::RwLock<Pool>>();
+};
+mod debug_handles;
+
+/// Prints every handle owned by C to stderr and returns how many there are. Handles are only tracked when the crate is built with the `debug-handles` feature, and this returns 0 otherwise.
+#[inline]
+#[no_mangle]
+pub extern "C" fn fixture_debug_live_handles() -> usize {
+    debug_handles::live_handles()
+}
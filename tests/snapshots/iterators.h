@@ -0,0 +1,58 @@
+#ifndef FIXTURE_H
+#define FIXTURE_H
+
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+typedef struct Conn Conn;
+typedef struct Conn_bytes_iter Conn_bytes_iter;
+typedef struct Pool Pool;
+typedef struct Pool_conns_iter Pool_conns_iter;
+
+/**
+ * Writes an iterator handle to `return_ptr`. Advance it with `Conn_bytes_iter_next` and free it with `Conn_bytes_iter_free`.
+ * The iterator may borrow from `self_`, so free it first.
+ */
+void Conn_bytes(Conn *self_, Conn_bytes_iter **return_ptr);
+
+/**
+ * Advances the iterator, writing the next item to `return_ptr`. Returns false once the iterator is exhausted.
+ */
+bool Conn_bytes_iter_next(Conn_bytes_iter *it, uint8_t *return_ptr);
+
+/**
+ * Frees the iterator. The handle is invalid after this call.
+ */
+void Conn_bytes_iter_free(Conn_bytes_iter *it);
+
+/**
+ * Writes an iterator handle to `return_ptr`. Advance it with `Pool_conns_iter_next` and free it with `Pool_conns_iter_free`.
+ * The iterator may borrow from `self_`, so free it first.
+ */
+void Pool_conns(Pool *self_, Pool_conns_iter **return_ptr);
+
+/**
+ * Advances the iterator, writing the next item to `return_ptr`. Returns false once the iterator is exhausted.
+ */
+bool Pool_conns_iter_next(Pool_conns_iter *it, Conn **return_ptr);
+
+/**
+ * Frees the iterator. The handle is invalid after this call.
+ */
+void Pool_conns_iter_free(Pool_conns_iter *it);
+
+/**
+ * Prints every handle owned by C to stderr and returns how many there are. Handles are only tracked when the crate is built with the `debug-handles` feature, and this returns 0 otherwise.
+ */
+uintptr_t fixture_debug_live_handles(void);
+
+#ifdef __cplusplus
+}  // extern "C"
+#endif
+
+#endif  /* FIXTURE_H */
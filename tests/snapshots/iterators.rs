@@ -0,0 +1,118 @@
+use fixture::Conn;
+use fixture::Pool;
+/// Writes an iterator handle to `return_ptr`. Advance it with `Conn_bytes_iter_next` and free it with `Conn_bytes_iter_free`.
+/// The iterator may borrow from `self_`, so free it first.
+#[inline]
+#[no_mangle]
+pub extern "C" fn Conn_bytes(
+    self_: *mut ::std::os::raw::c_void,
+    return_ptr: *mut *mut ::std::os::raw::c_void,
+) {
+    debug_handles::check(self_ as _, "Conn", "Conn_bytes");
+    let self_ = unsafe { &*(self_ as *const Conn) };
+    let value = self_.bytes();
+    let value: Box<dyn Iterator<Item = u8>> = Box::new(value.into_iter());
+    let value = Box::into_raw(Box::new(value));
+    debug_handles::register(value as _, "Box", "Box<dyn Iterator<Item = u8>>");
+    unsafe { *return_ptr = value as _ };
+}
+
+/// Advances the iterator, writing the next item to `return_ptr`. Returns false once the iterator is exhausted.
+#[inline]
+#[no_mangle]
+pub extern "C" fn Conn_bytes_iter_next(
+    it: *mut ::std::os::raw::c_void,
+    return_ptr: *mut u8,
+) -> bool {
+    debug_handles::check(
+        it as _,
+        "Box<dyn Iterator<Item = u8>>",
+        "Conn_bytes_iter_next",
+    );
+    let it = unsafe { &mut *(it as *mut Box<dyn Iterator<Item = u8>>) };
+    match it.next() {
+        Some(value) => {
+            unsafe { *return_ptr = value };
+            true
+        }
+        None => false,
+    }
+}
+
+/// Frees the iterator. The handle is invalid after this call.
+#[inline]
+#[no_mangle]
+pub extern "C" fn Conn_bytes_iter_free(it: *mut ::std::os::raw::c_void) {
+    debug_handles::release(
+        it as _,
+        "Box",
+        "Box<dyn Iterator<Item = u8>>",
+        "Conn_bytes_iter_free",
+    );
+    drop(unsafe { Box::from_raw(it as *mut Box<dyn Iterator<Item = u8>>) });
+}
+
+/// Writes an iterator handle to `return_ptr`. Advance it with `Pool_conns_iter_next` and free it with `Pool_conns_iter_free`.
+/// The iterator may borrow from `self_`, so free it first.
+#[inline]
+#[no_mangle]
+pub extern "C" fn Pool_conns(
+    self_: *mut ::std::os::raw::c_void,
+    return_ptr: *mut *mut ::std::os::raw::c_void,
+) {
+    debug_handles::check(self_ as _, "Pool", "Pool_conns");
+    let self_ = unsafe { &*(self_ as *const Pool) };
+    let value = self_.conns();
+    let value: Box<dyn Iterator<Item = &'static Conn>> = Box::new(value.into_iter());
+    let value = Box::into_raw(Box::new(value));
+    debug_handles::register(value as _, "Box", "Box<dyn Iterator<Item = &'static Conn>>");
+    unsafe { *return_ptr = value as _ };
+}
+
+/// Advances the iterator, writing the next item to `return_ptr`. Returns false once the iterator is exhausted.
+#[inline]
+#[no_mangle]
+pub extern "C" fn Pool_conns_iter_next(
+    it: *mut ::std::os::raw::c_void,
+    return_ptr: *mut *mut ::std::os::raw::c_void,
+) -> bool {
+    debug_handles::check(
+        it as _,
+        "Box<dyn Iterator<Item = &'static Conn>>",
+        "Pool_conns_iter_next",
+    );
+    let it = unsafe { &mut *(it as *mut Box<dyn Iterator<Item = &'static Conn>>) };
+    match it.next() {
+        Some(value) => {
+            debug_handles::register_borrowed(
+                value as *const Conn as _,
+                "Conn",
+                it as *const _ as _,
+            );
+            unsafe { *return_ptr = value as *const Conn as _ };
+            true
+        }
+        None => false,
+    }
+}
+
+/// Frees the iterator. The handle is invalid after this call.
+#[inline]
+#[no_mangle]
+pub extern "C" fn Pool_conns_iter_free(it: *mut ::std::os::raw::c_void) {
+    debug_handles::release(
+        it as _,
+        "Box",
+        "Box<dyn Iterator<Item = &'static Conn>>",
+        "Pool_conns_iter_free",
+    );
+    drop(unsafe { Box::from_raw(it as *mut Box<dyn Iterator<Item = &'static Conn>>) });
+}
+mod debug_handles;
+
+/// Prints every handle owned by C to stderr and returns how many there are. Handles are only tracked when the crate is built with the `debug-handles` feature, and this returns 0 otherwise.
+#[inline]
+#[no_mangle]
+pub extern "C" fn fixture_debug_live_handles() -> usize {
+    debug_handles::live_handles()
+}
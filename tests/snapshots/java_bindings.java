@@ -0,0 +1,526 @@
+package fixture;
+
+import java.lang.foreign.Arena;
+import java.lang.foreign.FunctionDescriptor;
+import java.lang.foreign.Linker;
+import java.lang.foreign.MemorySegment;
+import java.lang.foreign.SymbolLookup;
+import java.lang.invoke.MethodHandle;
+import java.nio.file.Path;
+import java.util.Optional;
+
+import static java.lang.foreign.ValueLayout.*;
+
+/** Bindings for libfixture_c. */
+public final class Fixture {
+    private Fixture() {}
+
+    private static final Linker LINKER = Linker.nativeLinker();
+    private static final SymbolLookup LOOKUP = lookup();
+
+    private static SymbolLookup lookup() {
+        String path = System.getProperty("fixture.library");
+        if (path != null) {
+            return SymbolLookup.libraryLookup(Path.of(path), Arena.global());
+        }
+        return SymbolLookup.libraryLookup(System.mapLibraryName("fixture_c"),
+            Arena.global());
+    }
+
+    private static MethodHandle downcall(String name, FunctionDescriptor descriptor) {
+        MemorySegment symbol = LOOKUP.find(name)
+            .orElseThrow(() -> new UnsatisfiedLinkError(name));
+        return LINKER.downcallHandle(symbol, descriptor);
+    }
+
+    private static RuntimeException rethrow(Throwable t) {
+        if (t instanceof RuntimeException e) {
+            return e;
+        }
+        if (t instanceof Error e) {
+            throw e;
+        }
+        return new RuntimeException(t);
+    }
+
+    /** A non-zero error code returned by a wrapped function. */
+    public static final class NativeException extends RuntimeException {
+        private final int code;
+
+        public NativeException(int code) {
+            super("error code " + code);
+            this.code = code;
+        }
+
+        public int code() {
+            return code;
+        }
+    }
+
+    private static final MethodHandle Conn_new =
+        downcall("Conn_new", FunctionDescriptor.ofVoid(JAVA_LONG, ADDRESS));
+    private static final MethodHandle Conn_free =
+        downcall("Conn_free", FunctionDescriptor.ofVoid(ADDRESS));
+    private static final MethodHandle Conn_get =
+        downcall("Conn_get", FunctionDescriptor.ofVoid(ADDRESS, ADDRESS));
+    private static final MethodHandle Conn_set =
+        downcall("Conn_set", FunctionDescriptor.ofVoid(ADDRESS, JAVA_LONG));
+    private static final MethodHandle Conn_raw =
+        downcall("Conn_raw", FunctionDescriptor.ofVoid(ADDRESS, ADDRESS));
+    private static final MethodHandle Conn_fill_raw =
+        downcall("Conn_fill_raw", FunctionDescriptor.ofVoid(ADDRESS, ADDRESS, JAVA_LONG));
+    private static final MethodHandle Conn_finish =
+        downcall("Conn_finish", FunctionDescriptor.ofVoid(ADDRESS, ADDRESS));
+    private static final MethodHandle Conn_finish_mut =
+        downcall("Conn_finish_mut", FunctionDescriptor.ofVoid(ADDRESS, ADDRESS));
+    private static final MethodHandle Conn_shared =
+        downcall("Conn_shared", FunctionDescriptor.ofVoid(JAVA_LONG, ADDRESS));
+    private static final MethodHandle Conn_retain =
+        downcall("Conn_retain", FunctionDescriptor.ofVoid(ADDRESS));
+    private static final MethodHandle Conn_release =
+        downcall("Conn_release", FunctionDescriptor.ofVoid(ADDRESS));
+    private static final MethodHandle ConnShared_get =
+        downcall("ConnShared_get", FunctionDescriptor.ofVoid(ADDRESS, ADDRESS));
+    private static final MethodHandle Pool_new =
+        downcall("Pool_new", FunctionDescriptor.ofVoid(JAVA_LONG, ADDRESS));
+    private static final MethodHandle Pool_free =
+        downcall("Pool_free", FunctionDescriptor.ofVoid(ADDRESS));
+    private static final MethodHandle Pool_conn =
+        downcall("Pool_conn", FunctionDescriptor.ofVoid(ADDRESS, ADDRESS));
+    private static final MethodHandle Pool_shared_conn =
+        downcall("Pool_shared_conn", FunctionDescriptor.ofVoid(ADDRESS, ADDRESS));
+    private static final MethodHandle Conn_sum =
+        downcall("Conn_sum", FunctionDescriptor.ofVoid(ADDRESS, ADDRESS, JAVA_LONG, ADDRESS));
+    private static final MethodHandle Conn_try_sum =
+        downcall("Conn_try_sum", FunctionDescriptor.of(JAVA_INT, ADDRESS, ADDRESS, JAVA_LONG, ADDRESS));
+    private static final MethodHandle Conn_merge =
+        downcall("Conn_merge", FunctionDescriptor.ofVoid(ADDRESS, ADDRESS));
+    private static final MethodHandle Pool_first =
+        downcall("Pool_first", FunctionDescriptor.ofVoid(ADDRESS, ADDRESS));
+    private static final MethodHandle Conn_bytes =
+        downcall("Conn_bytes", FunctionDescriptor.ofVoid(ADDRESS, ADDRESS));
+    private static final MethodHandle Conn_bytes_iter_next =
+        downcall("Conn_bytes_iter_next", FunctionDescriptor.of(JAVA_BOOLEAN, ADDRESS, ADDRESS));
+    private static final MethodHandle Conn_bytes_iter_free =
+        downcall("Conn_bytes_iter_free", FunctionDescriptor.ofVoid(ADDRESS));
+    private static final MethodHandle Pool_conns =
+        downcall("Pool_conns", FunctionDescriptor.ofVoid(ADDRESS, ADDRESS));
+    private static final MethodHandle Pool_conns_iter_next =
+        downcall("Pool_conns_iter_next", FunctionDescriptor.of(JAVA_BOOLEAN, ADDRESS, ADDRESS));
+    private static final MethodHandle Pool_conns_iter_free =
+        downcall("Pool_conns_iter_free", FunctionDescriptor.ofVoid(ADDRESS));
+    private static final MethodHandle fixture_debug_live_handles =
+        downcall("fixture_debug_live_handles", FunctionDescriptor.of(JAVA_LONG));
+
+    /** Owns a {@code Conn} handle. */
+    public static final class Conn implements AutoCloseable {
+        private MemorySegment ptr;
+        private final MethodHandle free;
+
+        /** Wraps a handle. A handle with no free function is borrowed. */
+        Conn(MemorySegment ptr, MethodHandle free) {
+            this.ptr = ptr;
+            this.free = free;
+        }
+
+        /** The C handle, which is still owned by this object. */
+        public MemorySegment cPtr() {
+            return ptr;
+        }
+
+        /** Gives up ownership of the C handle. */
+        MemorySegment takePtr() {
+            MemorySegment taken = ptr;
+            ptr = MemorySegment.NULL;
+            return taken;
+        }
+
+        @Override
+        public void close() {
+            MemorySegment taken = takePtr();
+            if (free != null && !taken.equals(MemorySegment.NULL)) {
+                try {
+                    free.invokeExact(taken);
+                } catch (Throwable t) {
+                    throw rethrow(t);
+                }
+            }
+        }
+
+        /** Calls {@code Conn_new}. */
+        public static Conn new_(long value) {
+            try (Arena arena = Arena.ofConfined()) {
+                MemorySegment ret = arena.allocate(ADDRESS);
+                Conn_new.invokeExact(value, ret);
+                return new Conn(ret.get(ADDRESS, 0), Conn_free);
+            } catch (Throwable t) {
+                throw rethrow(t);
+            }
+        }
+
+        /** Calls {@code Conn_get}. */
+        public long get() {
+            try (Arena arena = Arena.ofConfined()) {
+                MemorySegment ret = arena.allocate(JAVA_LONG);
+                Conn_get.invokeExact(ptr, ret);
+                return ret.get(JAVA_LONG, 0);
+            } catch (Throwable t) {
+                throw rethrow(t);
+            }
+        }
+
+        /** Calls {@code Conn_set}. */
+        public void set(long value) {
+            try {
+                Conn_set.invokeExact(ptr, value);
+            } catch (Throwable t) {
+                throw rethrow(t);
+            }
+        }
+
+        /** Calls {@code Conn_raw}. */
+        public MemorySegment raw() {
+            try (Arena arena = Arena.ofConfined()) {
+                MemorySegment ret = arena.allocate(ADDRESS);
+                Conn_raw.invokeExact(ptr, ret);
+                return ret.get(ADDRESS, 0);
+            } catch (Throwable t) {
+                throw rethrow(t);
+            }
+        }
+
+        /** Calls {@code Conn_fill_raw}. */
+        public void fillRaw(MemorySegment out, long len) {
+            try {
+                Conn_fill_raw.invokeExact(ptr, out, len);
+            } catch (Throwable t) {
+                throw rethrow(t);
+            }
+        }
+
+        /** Calls {@code Conn_finish}. */
+        public long finish() {
+            try (Arena arena = Arena.ofConfined()) {
+                MemorySegment ret = arena.allocate(JAVA_LONG);
+                Conn_finish.invokeExact(takePtr(), ret);
+                return ret.get(JAVA_LONG, 0);
+            } catch (Throwable t) {
+                throw rethrow(t);
+            }
+        }
+
+        /** Calls {@code Conn_finish_mut}. */
+        public long finishMut() {
+            try (Arena arena = Arena.ofConfined()) {
+                MemorySegment ret = arena.allocate(JAVA_LONG);
+                Conn_finish_mut.invokeExact(takePtr(), ret);
+                return ret.get(JAVA_LONG, 0);
+            } catch (Throwable t) {
+                throw rethrow(t);
+            }
+        }
+
+        /** Calls {@code Conn_shared}. */
+        public static ConnShared shared(long value) {
+            try (Arena arena = Arena.ofConfined()) {
+                MemorySegment ret = arena.allocate(ADDRESS);
+                Conn_shared.invokeExact(value, ret);
+                return new ConnShared(ret.get(ADDRESS, 0), Conn_release);
+            } catch (Throwable t) {
+                throw rethrow(t);
+            }
+        }
+
+        /** Calls {@code Conn_sum}. */
+        public long sum(byte[] bytes) {
+            try (Arena arena = Arena.ofConfined()) {
+                MemorySegment bytesSegment = arena.allocateFrom(JAVA_BYTE, bytes);
+                MemorySegment ret = arena.allocate(JAVA_LONG);
+                Conn_sum.invokeExact(ptr, bytesSegment, (long) bytes.length, ret);
+                return ret.get(JAVA_LONG, 0);
+            } catch (Throwable t) {
+                throw rethrow(t);
+            }
+        }
+
+        /** Calls {@code Conn_try_sum}. */
+        public long trySum(byte[] bytes) {
+            try (Arena arena = Arena.ofConfined()) {
+                MemorySegment bytesSegment = arena.allocateFrom(JAVA_BYTE, bytes);
+                MemorySegment ret = arena.allocate(JAVA_LONG);
+                int code = (int) Conn_try_sum.invokeExact(ptr, bytesSegment, (long) bytes.length, ret);
+                if (code != 0) {
+                    throw new NativeException(code);
+                }
+                return ret.get(JAVA_LONG, 0);
+            } catch (Throwable t) {
+                throw rethrow(t);
+            }
+        }
+
+        /** Calls {@code Conn_merge}. */
+        public void merge(Conn other) {
+            try {
+                Conn_merge.invokeExact(ptr, other.ptr);
+            } catch (Throwable t) {
+                throw rethrow(t);
+            }
+        }
+
+        /** Calls {@code Conn_bytes}. */
+        public ConnBytesIter bytes() {
+            try (Arena arena = Arena.ofConfined()) {
+                MemorySegment ret = arena.allocate(ADDRESS);
+                Conn_bytes.invokeExact(ptr, ret);
+                return new ConnBytesIter(ret.get(ADDRESS, 0), Conn_bytes_iter_free);
+            } catch (Throwable t) {
+                throw rethrow(t);
+            }
+        }
+    }
+
+    /** Owns a {@code ConnShared} handle. */
+    public static final class ConnShared implements AutoCloseable {
+        private MemorySegment ptr;
+        private final MethodHandle free;
+
+        /** Wraps a handle. A handle with no free function is borrowed. */
+        ConnShared(MemorySegment ptr, MethodHandle free) {
+            this.ptr = ptr;
+            this.free = free;
+        }
+
+        /** The C handle, which is still owned by this object. */
+        public MemorySegment cPtr() {
+            return ptr;
+        }
+
+        /** Gives up ownership of the C handle. */
+        MemorySegment takePtr() {
+            MemorySegment taken = ptr;
+            ptr = MemorySegment.NULL;
+            return taken;
+        }
+
+        @Override
+        public void close() {
+            MemorySegment taken = takePtr();
+            if (free != null && !taken.equals(MemorySegment.NULL)) {
+                try {
+                    free.invokeExact(taken);
+                } catch (Throwable t) {
+                    throw rethrow(t);
+                }
+            }
+        }
+
+        /** Calls {@code ConnShared_get}. */
+        public long get() {
+            try (Arena arena = Arena.ofConfined()) {
+                MemorySegment ret = arena.allocate(JAVA_LONG);
+                ConnShared_get.invokeExact(ptr, ret);
+                return ret.get(JAVA_LONG, 0);
+            } catch (Throwable t) {
+                throw rethrow(t);
+            }
+        }
+    }
+
+    /** Owns a {@code Pool} handle. */
+    public static final class Pool implements AutoCloseable {
+        private MemorySegment ptr;
+        private final MethodHandle free;
+
+        /** Wraps a handle. A handle with no free function is borrowed. */
+        Pool(MemorySegment ptr, MethodHandle free) {
+            this.ptr = ptr;
+            this.free = free;
+        }
+
+        /** The C handle, which is still owned by this object. */
+        public MemorySegment cPtr() {
+            return ptr;
+        }
+
+        /** Gives up ownership of the C handle. */
+        MemorySegment takePtr() {
+            MemorySegment taken = ptr;
+            ptr = MemorySegment.NULL;
+            return taken;
+        }
+
+        @Override
+        public void close() {
+            MemorySegment taken = takePtr();
+            if (free != null && !taken.equals(MemorySegment.NULL)) {
+                try {
+                    free.invokeExact(taken);
+                } catch (Throwable t) {
+                    throw rethrow(t);
+                }
+            }
+        }
+
+        /** Calls {@code Pool_new}. */
+        public static Pool new_(long value) {
+            try (Arena arena = Arena.ofConfined()) {
+                MemorySegment ret = arena.allocate(ADDRESS);
+                Pool_new.invokeExact(value, ret);
+                return new Pool(ret.get(ADDRESS, 0), Pool_free);
+            } catch (Throwable t) {
+                throw rethrow(t);
+            }
+        }
+
+        /** Calls {@code Pool_conn}. */
+        public ConnShared conn() {
+            try (Arena arena = Arena.ofConfined()) {
+                MemorySegment ret = arena.allocate(ADDRESS);
+                Pool_conn.invokeExact(ptr, ret);
+                return new ConnShared(ret.get(ADDRESS, 0), null);
+            } catch (Throwable t) {
+                throw rethrow(t);
+            }
+        }
+
+        /** Calls {@code Pool_shared_conn}. */
+        public ConnShared sharedConn() {
+            try (Arena arena = Arena.ofConfined()) {
+                MemorySegment ret = arena.allocate(ADDRESS);
+                Pool_shared_conn.invokeExact(ptr, ret);
+                return new ConnShared(ret.get(ADDRESS, 0), Conn_release);
+            } catch (Throwable t) {
+                throw rethrow(t);
+            }
+        }
+
+        /** Calls {@code Pool_first}. */
+        public Conn first() {
+            try (Arena arena = Arena.ofConfined()) {
+                MemorySegment ret = arena.allocate(ADDRESS);
+                Pool_first.invokeExact(ptr, ret);
+                return new Conn(ret.get(ADDRESS, 0), Conn_free);
+            } catch (Throwable t) {
+                throw rethrow(t);
+            }
+        }
+
+        /** Calls {@code Pool_conns}. */
+        public PoolConnsIter conns() {
+            try (Arena arena = Arena.ofConfined()) {
+                MemorySegment ret = arena.allocate(ADDRESS);
+                Pool_conns.invokeExact(ptr, ret);
+                return new PoolConnsIter(ret.get(ADDRESS, 0), Pool_conns_iter_free);
+            } catch (Throwable t) {
+                throw rethrow(t);
+            }
+        }
+    }
+
+    /** Owns a {@code Conn_bytes_iter} handle. */
+    public static final class ConnBytesIter implements AutoCloseable {
+        private MemorySegment ptr;
+        private final MethodHandle free;
+
+        /** Wraps a handle. A handle with no free function is borrowed. */
+        ConnBytesIter(MemorySegment ptr, MethodHandle free) {
+            this.ptr = ptr;
+            this.free = free;
+        }
+
+        /** The C handle, which is still owned by this object. */
+        public MemorySegment cPtr() {
+            return ptr;
+        }
+
+        /** Gives up ownership of the C handle. */
+        MemorySegment takePtr() {
+            MemorySegment taken = ptr;
+            ptr = MemorySegment.NULL;
+            return taken;
+        }
+
+        @Override
+        public void close() {
+            MemorySegment taken = takePtr();
+            if (free != null && !taken.equals(MemorySegment.NULL)) {
+                try {
+                    free.invokeExact(taken);
+                } catch (Throwable t) {
+                    throw rethrow(t);
+                }
+            }
+        }
+
+        /** Calls {@code Conn_bytes_iter_next}. */
+        public Optional<Byte> next() {
+            try (Arena arena = Arena.ofConfined()) {
+                MemorySegment ret = arena.allocate(JAVA_BYTE);
+                if (!(boolean) Conn_bytes_iter_next.invokeExact(ptr, ret)) {
+                    return Optional.empty();
+                }
+                return Optional.of(ret.get(JAVA_BYTE, 0));
+            } catch (Throwable t) {
+                throw rethrow(t);
+            }
+        }
+    }
+
+    /** Owns a {@code Pool_conns_iter} handle. */
+    public static final class PoolConnsIter implements AutoCloseable {
+        private MemorySegment ptr;
+        private final MethodHandle free;
+
+        /** Wraps a handle. A handle with no free function is borrowed. */
+        PoolConnsIter(MemorySegment ptr, MethodHandle free) {
+            this.ptr = ptr;
+            this.free = free;
+        }
+
+        /** The C handle, which is still owned by this object. */
+        public MemorySegment cPtr() {
+            return ptr;
+        }
+
+        /** Gives up ownership of the C handle. */
+        MemorySegment takePtr() {
+            MemorySegment taken = ptr;
+            ptr = MemorySegment.NULL;
+            return taken;
+        }
+
+        @Override
+        public void close() {
+            MemorySegment taken = takePtr();
+            if (free != null && !taken.equals(MemorySegment.NULL)) {
+                try {
+                    free.invokeExact(taken);
+                } catch (Throwable t) {
+                    throw rethrow(t);
+                }
+            }
+        }
+
+        /** Calls {@code Pool_conns_iter_next}. */
+        public Optional<Conn> next() {
+            try (Arena arena = Arena.ofConfined()) {
+                MemorySegment ret = arena.allocate(ADDRESS);
+                if (!(boolean) Pool_conns_iter_next.invokeExact(ptr, ret)) {
+                    return Optional.empty();
+                }
+                return Optional.of(new Conn(ret.get(ADDRESS, 0), null));
+            } catch (Throwable t) {
+                throw rethrow(t);
+            }
+        }
+    }
+
+    /** Calls {@code fixture_debug_live_handles}. */
+    public static long debugLiveHandles() {
+        try {
+            return (long) fixture_debug_live_handles.invokeExact();
+        } catch (Throwable t) {
+            throw rethrow(t);
+        }
+    }
+}
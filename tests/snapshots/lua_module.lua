@@ -0,0 +1,276 @@
+--- LuaJIT FFI bindings for libfixture_c.
+local ffi = require("ffi")
+
+ffi.cdef[[
+typedef struct Conn Conn;
+typedef struct ConnShared ConnShared;
+typedef struct Pool Pool;
+typedef struct Conn_bytes_iter Conn_bytes_iter;
+typedef struct Pool_conns_iter Pool_conns_iter;
+extern const uint8_t NAME[8];
+extern const uint8_t LABEL[5];
+extern const uintptr_t LIMIT;
+void Conn_new(uintptr_t value, Conn **return_ptr);
+void Conn_free(Conn *self_);
+void Conn_get(Conn *self_, uintptr_t *return_ptr);
+void Conn_set(Conn *self_, uintptr_t value);
+void Conn_raw(Conn *self_, const uint8_t **return_ptr);
+void Conn_fill_raw(Conn *self_, uint8_t *out, uintptr_t len);
+void Conn_finish(Conn *self_, uintptr_t *return_ptr);
+void Conn_finish_mut(Conn *self_, uintptr_t *return_ptr);
+void Conn_shared(uintptr_t value, ConnShared **return_ptr);
+void Conn_retain(ConnShared *self_);
+void Conn_release(ConnShared *self_);
+void ConnShared_get(ConnShared *self_, uintptr_t *return_ptr);
+void Pool_new(uintptr_t value, Pool **return_ptr);
+void Pool_free(Pool *self_);
+void Pool_conn(Pool *self_, ConnShared **return_ptr);
+void Pool_shared_conn(Pool *self_, ConnShared **return_ptr);
+void Conn_sum(Conn *self_, const uint8_t *bytes, uintptr_t bytes_len, uintptr_t *return_ptr);
+uint32_t Conn_try_sum(Conn *self_, const uint8_t *bytes, uintptr_t bytes_len, uintptr_t *return_ptr);
+void Conn_merge(Conn *self_, Conn *other);
+void Pool_first(Pool *self_, Conn **return_ptr);
+void Conn_bytes(Conn *self_, Conn_bytes_iter **return_ptr);
+bool Conn_bytes_iter_next(Conn_bytes_iter *it, uint8_t *return_ptr);
+void Conn_bytes_iter_free(Conn_bytes_iter *it);
+void Pool_conns(Pool *self_, Pool_conns_iter **return_ptr);
+bool Pool_conns_iter_next(Pool_conns_iter *it, Conn **return_ptr);
+void Pool_conns_iter_free(Pool_conns_iter *it);
+uintptr_t fixture_debug_live_handles(void);
+]]
+
+local lib = ffi.load(os.getenv("FIXTURE_C_LIB") or "fixture_c")
+
+local M = { lib = lib }
+
+--- A non-zero error code returned by a wrapped function.
+local Error = {}
+Error.__index = Error
+Error.__tostring = function(err)
+  return "error code " .. err.code
+end
+M.Error = Error
+
+local function check(code)
+  if code ~= 0 then
+    error(setmetatable({ code = code }, Error))
+  end
+end
+
+--- Wraps a handle, freeing it with `free` when garbage collected. A handle
+--- with no `free` function is borrowed and never freed. A handle that
+--- borrows from the handles in `borrows` keeps them alive.
+local function wrap(class, ptr, free, borrows)
+  if free ~= nil then
+    ptr = ffi.gc(ptr, free)
+  end
+  return setmetatable({ ptr = ptr, free = free, borrows = borrows }, class)
+end
+
+--- Gives up ownership of the C handle.
+local function take(handle)
+  local ptr = handle.ptr
+  handle.ptr = nil
+  if handle.free ~= nil then
+    ffi.gc(ptr, nil)
+  end
+  return ptr
+end
+
+--- Frees the handle now rather than when garbage collected.
+local function close(handle)
+  if handle.ptr ~= nil then
+    local free = handle.free
+    local ptr = take(handle)
+    if free ~= nil then
+      free(ptr)
+    end
+  end
+end
+
+--- Copies `len` elements starting at `ptr` into a table.
+local function to_table(ptr, len)
+  local items = {}
+  for i = 0, tonumber(len) - 1 do
+    items[i + 1] = ptr[i]
+  end
+  return items
+end
+
+M.MAX_CONNS = 16
+
+M.MIN_OFFSET = -3
+
+M.RATIO = 0.5
+
+--- A handle to a `Conn`.
+local Conn = { close = close }
+Conn.__index = Conn
+M.Conn = Conn
+
+--- A handle to a `ConnShared`.
+local ConnShared = { close = close }
+ConnShared.__index = ConnShared
+M.ConnShared = ConnShared
+
+--- A handle to a `Pool`.
+local Pool = { close = close }
+Pool.__index = Pool
+M.Pool = Pool
+
+--- A handle to a `Conn_bytes_iter`.
+local Conn_bytes_iter = { close = close }
+Conn_bytes_iter.__index = Conn_bytes_iter
+M.Conn_bytes_iter = Conn_bytes_iter
+
+--- A handle to a `Pool_conns_iter`.
+local Pool_conns_iter = { close = close }
+Pool_conns_iter.__index = Pool_conns_iter
+M.Pool_conns_iter = Pool_conns_iter
+
+--- Calls `Conn_new`.
+function Conn.new(value)
+  local ret = ffi.new("Conn *[1]")
+  lib.Conn_new(value, ret)
+  return wrap(Conn, ret[0], lib.Conn_free)
+end
+
+--- Calls `Conn_get`.
+function Conn:get()
+  local ret = ffi.new("uintptr_t[1]")
+  lib.Conn_get(self.ptr, ret)
+  return ret[0]
+end
+
+--- Calls `Conn_set`.
+function Conn:set(value)
+  lib.Conn_set(self.ptr, value)
+end
+
+--- Calls `Conn_raw`.
+function Conn:raw()
+  local ret = ffi.new("const uint8_t *[1]")
+  lib.Conn_raw(self.ptr, ret)
+  return ret[0]
+end
+
+--- Calls `Conn_fill_raw`.
+function Conn:fill_raw(out, len)
+  lib.Conn_fill_raw(self.ptr, out, len)
+end
+
+--- Calls `Conn_finish`.
+function Conn:finish()
+  local ret = ffi.new("uintptr_t[1]")
+  lib.Conn_finish(take(self), ret)
+  return ret[0]
+end
+
+--- Calls `Conn_finish_mut`.
+function Conn:finish_mut()
+  local ret = ffi.new("uintptr_t[1]")
+  lib.Conn_finish_mut(take(self), ret)
+  return ret[0]
+end
+
+--- Calls `Conn_shared`.
+function Conn.shared(value)
+  local ret = ffi.new("ConnShared *[1]")
+  lib.Conn_shared(value, ret)
+  return wrap(ConnShared, ret[0], lib.Conn_release)
+end
+
+--- Calls `Conn_sum`.
+function Conn:sum(bytes)
+  local bytes_buf = ffi.new("uint8_t[?]", #bytes, bytes)
+  local ret = ffi.new("uintptr_t[1]")
+  lib.Conn_sum(self.ptr, bytes_buf, #bytes, ret)
+  return ret[0]
+end
+
+--- Calls `Conn_try_sum`.
+function Conn:try_sum(bytes)
+  local bytes_buf = ffi.new("uint8_t[?]", #bytes, bytes)
+  local ret = ffi.new("uintptr_t[1]")
+  check(lib.Conn_try_sum(self.ptr, bytes_buf, #bytes, ret))
+  return ret[0]
+end
+
+--- Calls `Conn_merge`.
+function Conn:merge(other)
+  lib.Conn_merge(self.ptr, other.ptr)
+end
+
+--- Calls `Conn_bytes`.
+function Conn:bytes()
+  local ret = ffi.new("Conn_bytes_iter *[1]")
+  lib.Conn_bytes(self.ptr, ret)
+  return wrap(Conn_bytes_iter, ret[0], lib.Conn_bytes_iter_free, { self })
+end
+
+--- Calls `ConnShared_get`.
+function ConnShared:get()
+  local ret = ffi.new("uintptr_t[1]")
+  lib.ConnShared_get(self.ptr, ret)
+  return ret[0]
+end
+
+--- Calls `Pool_new`.
+function Pool.new(value)
+  local ret = ffi.new("Pool *[1]")
+  lib.Pool_new(value, ret)
+  return wrap(Pool, ret[0], lib.Pool_free)
+end
+
+--- Calls `Pool_conn`.
+function Pool:conn()
+  local ret = ffi.new("ConnShared *[1]")
+  lib.Pool_conn(self.ptr, ret)
+  return wrap(ConnShared, ret[0], nil, { self })
+end
+
+--- Calls `Pool_shared_conn`.
+function Pool:shared_conn()
+  local ret = ffi.new("ConnShared *[1]")
+  lib.Pool_shared_conn(self.ptr, ret)
+  return wrap(ConnShared, ret[0], lib.Conn_release)
+end
+
+--- Calls `Pool_first`.
+function Pool:first()
+  local ret = ffi.new("Conn *[1]")
+  lib.Pool_first(self.ptr, ret)
+  return wrap(Conn, ret[0], lib.Conn_free)
+end
+
+--- Calls `Pool_conns`.
+function Pool:conns()
+  local ret = ffi.new("Pool_conns_iter *[1]")
+  lib.Pool_conns(self.ptr, ret)
+  return wrap(Pool_conns_iter, ret[0], lib.Pool_conns_iter_free, { self })
+end
+
+--- Calls `Conn_bytes_iter_next`.
+function Conn_bytes_iter:next()
+  local ret = ffi.new("uint8_t[1]")
+  if not lib.Conn_bytes_iter_next(self.ptr, ret) then
+    return nil
+  end
+  return ret[0]
+end
+
+--- Calls `Pool_conns_iter_next`.
+function Pool_conns_iter:next()
+  local ret = ffi.new("Conn *[1]")
+  if not lib.Pool_conns_iter_next(self.ptr, ret) then
+    return nil
+  end
+  return wrap(Conn, ret[0], nil, { self })
+end
+
+--- Calls `fixture_debug_live_handles`.
+function M.debug_live_handles()
+  return lib.fixture_debug_live_handles()
+end
+
+return M
@@ -0,0 +1,68 @@
+#ifndef FIXTURE_H
+#define FIXTURE_H
+
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+typedef struct Conn Conn;
+
+/**
+ * Writes the return value to `return_ptr`.
+ *
+ * Ownership:
+ * - `return_ptr`: receives a new handle owned by the caller, which must be freed with `Conn_free`.
+ */
+void Conn_new(uintptr_t value, Conn **return_ptr);
+
+/**
+ * Frees the handle. The handle is invalid after this call.
+ */
+void Conn_free(Conn *self_);
+
+/**
+ * Writes the return value to `return_ptr`.
+ */
+void Conn_get(Conn *self_, uintptr_t *return_ptr);
+
+void Conn_set(Conn *self_, uintptr_t value);
+
+/**
+ * Writes the return value to `return_ptr`.
+ */
+void Conn_raw(Conn *self_, const uint8_t **return_ptr);
+
+void Conn_fill_raw(Conn *self_, uint8_t *out, uintptr_t len);
+
+/**
+ * Writes the return value to `return_ptr`.
+ *
+ * Ownership:
+ * - `bytes`: borrowed for the duration of the call.
+ */
+void Conn_sum(Conn *self_, const uint8_t *bytes, uintptr_t bytes_len, uintptr_t *return_ptr);
+
+/**
+ * Writes the return value to `return_ptr`.
+ * Returns 0 on success, or 1 if `try_sum` returned an error.
+ * Returns 2 if a pointer argument is null.
+ *
+ * Ownership:
+ * - `bytes`: borrowed for the duration of the call.
+ */
+uint32_t Conn_try_sum(Conn *self_, const uint8_t *bytes, uintptr_t bytes_len, uintptr_t *return_ptr);
+
+/**
+ * Prints every handle owned by C to stderr and returns how many there are. Handles are only tracked when the crate is built with the `debug-handles` feature, and this returns 0 otherwise.
+ */
+uintptr_t fixture_debug_live_handles(void);
+
+#ifdef __cplusplus
+}  // extern "C"
+#endif
+
+#endif  /* FIXTURE_H */
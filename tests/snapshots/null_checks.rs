@@ -0,0 +1,176 @@
+use fixture::Conn;
+/// Writes the return value to `return_ptr`.
+///
+/// Ownership:
+/// - `return_ptr`: receives a new handle owned by the caller, which must be freed with `Conn_free`.
+#[inline]
+#[no_mangle]
+pub extern "C" fn Conn_new(value: usize, return_ptr: *mut *mut ::std::os::raw::c_void) {
+    if return_ptr.is_null() {
+        eprintln!("Conn_new: `return_ptr` is null");
+        std::process::abort();
+    }
+    let arg0 = value;
+    let value = Conn::new(arg0);
+    let value = Box::into_raw(Box::new(value));
+    debug_handles::register(value as _, "Box", "Conn");
+    unsafe { *return_ptr = value as _ };
+}
+
+/// Frees the handle. The handle is invalid after this call.
+#[inline]
+#[no_mangle]
+pub extern "C" fn Conn_free(self_: *mut ::std::os::raw::c_void) {
+    if self_.is_null() {
+        return;
+    }
+    debug_handles::release(self_ as _, "Box", "Conn", "Conn_free");
+    drop(unsafe { Box::from_raw(self_ as *mut Conn) });
+}
+
+/// Writes the return value to `return_ptr`.
+#[inline]
+#[no_mangle]
+pub extern "C" fn Conn_get(self_: *mut ::std::os::raw::c_void, return_ptr: *mut usize) {
+    if self_.is_null() {
+        eprintln!("Conn_get: `self_` is null");
+        std::process::abort();
+    }
+    if return_ptr.is_null() {
+        eprintln!("Conn_get: `return_ptr` is null");
+        std::process::abort();
+    }
+    debug_handles::check(self_ as _, "Conn", "Conn_get");
+    let self_ = unsafe { &*(self_ as *const Conn) };
+    let value = self_.get();
+    unsafe { *return_ptr = value };
+}
+
+#[inline]
+#[no_mangle]
+pub extern "C" fn Conn_set(self_: *mut ::std::os::raw::c_void, value: usize) {
+    if self_.is_null() {
+        eprintln!("Conn_set: `self_` is null");
+        std::process::abort();
+    }
+    debug_handles::check(self_ as _, "Conn", "Conn_set");
+    let self_ = unsafe { &mut *(self_ as *mut Conn) };
+    let arg0 = value;
+    self_.set(arg0);
+}
+
+/// Writes the return value to `return_ptr`.
+#[inline]
+#[no_mangle]
+pub extern "C" fn Conn_raw(self_: *mut ::std::os::raw::c_void, return_ptr: *mut *const u8) {
+    if self_.is_null() {
+        eprintln!("Conn_raw: `self_` is null");
+        std::process::abort();
+    }
+    if return_ptr.is_null() {
+        eprintln!("Conn_raw: `return_ptr` is null");
+        std::process::abort();
+    }
+    debug_handles::check(self_ as _, "Conn", "Conn_raw");
+    let self_ = unsafe { &*(self_ as *const Conn) };
+    let value = self_.raw();
+    unsafe { *return_ptr = value };
+}
+
+#[inline]
+#[no_mangle]
+pub extern "C" fn Conn_fill_raw(self_: *mut ::std::os::raw::c_void, out: *mut u8, len: usize) {
+    if self_.is_null() {
+        eprintln!("Conn_fill_raw: `self_` is null");
+        std::process::abort();
+    }
+    debug_handles::check(self_ as _, "Conn", "Conn_fill_raw");
+    let self_ = unsafe { &*(self_ as *const Conn) };
+    let arg0 = out;
+    let arg1 = len;
+    self_.fill_raw(arg0, arg1);
+}
+
+/// Writes the return value to `return_ptr`.
+///
+/// Ownership:
+/// - `bytes`: borrowed for the duration of the call.
+#[inline]
+#[no_mangle]
+pub extern "C" fn Conn_sum(
+    self_: *mut ::std::os::raw::c_void,
+    bytes: *const u8,
+    bytes_len: usize,
+    return_ptr: *mut usize,
+) {
+    if self_.is_null() {
+        eprintln!("Conn_sum: `self_` is null");
+        std::process::abort();
+    }
+    if bytes.is_null() && bytes_len != 0 {
+        eprintln!("Conn_sum: `bytes` is null");
+        std::process::abort();
+    }
+    if return_ptr.is_null() {
+        eprintln!("Conn_sum: `return_ptr` is null");
+        std::process::abort();
+    }
+    debug_handles::check(self_ as _, "Conn", "Conn_sum");
+    let self_ = unsafe { &*(self_ as *const Conn) };
+    let arg0 = if bytes_len == 0 {
+        &[]
+    } else {
+        unsafe { std::slice::from_raw_parts(bytes as *const u8, bytes_len) }
+    };
+    let value = self_.sum(arg0);
+    unsafe { *return_ptr = value };
+}
+
+/// Writes the return value to `return_ptr`.
+/// Returns 0 on success, or 1 if `try_sum` returned an error.
+/// Returns 2 if a pointer argument is null.
+///
+/// Ownership:
+/// - `bytes`: borrowed for the duration of the call.
+#[inline]
+#[no_mangle]
+pub extern "C" fn Conn_try_sum(
+    self_: *mut ::std::os::raw::c_void,
+    bytes: *const u8,
+    bytes_len: usize,
+    return_ptr: *mut usize,
+) -> u32 {
+    if self_.is_null() {
+        return 2;
+    }
+    if bytes.is_null() && bytes_len != 0 {
+        return 2;
+    }
+    if return_ptr.is_null() {
+        return 2;
+    }
+    debug_handles::check(self_ as _, "Conn", "Conn_try_sum");
+    let self_ = unsafe { &*(self_ as *const Conn) };
+    let arg0 = if bytes_len == 0 {
+        &[]
+    } else {
+        unsafe { std::slice::from_raw_parts(bytes as *const u8, bytes_len) }
+    };
+    let value = self_.try_sum(arg0);
+    let value = match value {
+        Ok(value) => value,
+        Err(_) => {
+            return 1;
+        }
+    };
+    unsafe { *return_ptr = value };
+    0
+}
+mod debug_handles;
+
+/// Prints every handle owned by C to stderr and returns how many there are. Handles are only tracked when the crate is built with the `debug-handles` feature, and this returns 0 otherwise.
+#[inline]
+#[no_mangle]
+pub extern "C" fn fixture_debug_live_handles() -> usize {
+    debug_handles::live_handles()
+}
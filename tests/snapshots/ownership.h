@@ -0,0 +1,43 @@
+#ifndef FIXTURE_H
+#define FIXTURE_H
+
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+typedef struct Conn Conn;
+typedef struct Pool Pool;
+
+/**
+ * Ownership:
+ * - `other`: cloned. The caller still owns the handle.
+ */
+void Conn_merge(Conn *self_, Conn *other);
+
+/**
+ * Writes the return value to `return_ptr`.
+ *
+ * Ownership:
+ * - `return_ptr`: receives a new handle to a clone of the reference, which must be freed with `Conn_free`.
+ */
+void Pool_first(Pool *self_, Conn **return_ptr);
+
+/**
+ * Frees the handle. The handle is invalid after this call.
+ */
+void Conn_free(Conn *self_);
+
+/**
+ * Prints every handle owned by C to stderr and returns how many there are. Handles are only tracked when the crate is built with the `debug-handles` feature, and this returns 0 otherwise.
+ */
+uintptr_t fixture_debug_live_handles(void);
+
+#ifdef __cplusplus
+}  // extern "C"
+#endif
+
+#endif  /* FIXTURE_H */
@@ -0,0 +1,51 @@
+use fixture::Conn;
+use fixture::Pool;
+/// Ownership:
+/// - `other`: cloned. The caller still owns the handle.
+#[inline]
+#[no_mangle]
+pub extern "C" fn Conn_merge(
+    self_: *mut ::std::os::raw::c_void,
+    other: *mut ::std::os::raw::c_void,
+) {
+    debug_handles::check(self_ as _, "Conn", "Conn_merge");
+    let self_ = unsafe { &mut *(self_ as *mut Conn) };
+    debug_handles::check(other as _, "Conn", "Conn_merge");
+    let arg0 = unsafe { &*(other as *const Conn) }.clone();
+    self_.merge(arg0);
+}
+
+/// Writes the return value to `return_ptr`.
+///
+/// Ownership:
+/// - `return_ptr`: receives a new handle to a clone of the reference, which must be freed with `Conn_free`.
+#[inline]
+#[no_mangle]
+pub extern "C" fn Pool_first(
+    self_: *mut ::std::os::raw::c_void,
+    return_ptr: *mut *mut ::std::os::raw::c_void,
+) {
+    debug_handles::check(self_ as _, "Pool", "Pool_first");
+    let self_ = unsafe { &*(self_ as *const Pool) };
+    let value = self_.first();
+    let value = value.clone();
+    let value = Box::into_raw(Box::new(value));
+    debug_handles::register(value as _, "Box", "Conn");
+    unsafe { *return_ptr = value as _ };
+}
+
+/// Frees the handle. The handle is invalid after this call.
+#[inline]
+#[no_mangle]
+pub extern "C" fn Conn_free(self_: *mut ::std::os::raw::c_void) {
+    debug_handles::release(self_ as _, "Box", "Conn", "Conn_free");
+    drop(unsafe { Box::from_raw(self_ as *mut Conn) });
+}
+mod debug_handles;
+
+/// Prints every handle owned by C to stderr and returns how many there are. Handles are only tracked when the crate is built with the `debug-handles` feature, and this returns 0 otherwise.
+#[inline]
+#[no_mangle]
+pub extern "C" fn fixture_debug_live_handles() -> usize {
+    debug_handles::live_handles()
+}
@@ -0,0 +1,11 @@
+# Found by find_package(Fixture), which defines the imported targets Fixture::fixture_c.
+get_filename_component(_fixture_prefix "${CMAKE_CURRENT_LIST_DIR}/../../.." ABSOLUTE)
+
+if(NOT TARGET Fixture::fixture_c)
+  add_library(Fixture::fixture_c SHARED IMPORTED)
+  set_target_properties(Fixture::fixture_c PROPERTIES
+    IMPORTED_LOCATION "${_fixture_prefix}/lib/${CMAKE_SHARED_LIBRARY_PREFIX}fixture_c${CMAKE_SHARED_LIBRARY_SUFFIX}"
+    INTERFACE_INCLUDE_DIRECTORIES "${_fixture_prefix}/include")
+endif()
+
+unset(_fixture_prefix)
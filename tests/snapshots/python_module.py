@@ -0,0 +1,262 @@
+"""ctypes bindings for libfixture_c."""
+
+import ctypes
+import enum
+import os
+import sys
+
+
+def _load():
+    path = os.environ.get("FIXTURE_C_LIB")
+    if path is None:
+        name = {
+            "darwin": "libfixture_c.dylib",
+            "win32": "fixture_c.dll",
+        }.get(sys.platform, "libfixture_c.so")
+        root = os.path.dirname(os.path.abspath(__file__))
+        for profile in ("release", "debug"):
+            path = os.path.join(root, "target", profile, name)
+            if os.path.exists(path):
+                break
+    return ctypes.CDLL(path)
+
+
+_lib = _load()
+
+
+class Error(Exception):
+    """A non-zero error code returned by a wrapped function."""
+
+    def __init__(self, code):
+        super().__init__(f"error code {code}")
+        self.code = code
+
+
+class _Handle:
+    """Owns a C handle, freeing it with `free` when garbage collected. A
+    handle with no `free` function is borrowed and never freed. A handle that
+    borrows from the handles in `borrows` keeps them alive."""
+
+    def __init__(self, ptr, free=None, borrows=()):
+        self._ptr = ptr
+        self._free = free
+        self._borrows = borrows
+
+    def __del__(self):
+        if self._ptr is not None and self._free is not None:
+            self._free(self._ptr)
+        self._ptr = None
+
+    def _take(self):
+        """Gives up ownership of the C handle."""
+        ptr, self._ptr = self._ptr, None
+        return ptr
+
+
+_lib.Conn_new.argtypes = [ctypes.c_size_t, ctypes.POINTER(ctypes.c_void_p)]
+_lib.Conn_new.restype = None
+_lib.Conn_free.argtypes = [ctypes.c_void_p]
+_lib.Conn_free.restype = None
+_lib.Conn_get.argtypes = [ctypes.c_void_p, ctypes.POINTER(ctypes.c_size_t)]
+_lib.Conn_get.restype = None
+_lib.Conn_set.argtypes = [ctypes.c_void_p, ctypes.c_size_t]
+_lib.Conn_set.restype = None
+_lib.Conn_raw.argtypes = [ctypes.c_void_p, ctypes.POINTER(ctypes.POINTER(ctypes.c_uint8))]
+_lib.Conn_raw.restype = None
+_lib.Conn_fill_raw.argtypes = [ctypes.c_void_p, ctypes.POINTER(ctypes.c_uint8), ctypes.c_size_t]
+_lib.Conn_fill_raw.restype = None
+_lib.Conn_finish.argtypes = [ctypes.c_void_p, ctypes.POINTER(ctypes.c_size_t)]
+_lib.Conn_finish.restype = None
+_lib.Conn_finish_mut.argtypes = [ctypes.c_void_p, ctypes.POINTER(ctypes.c_size_t)]
+_lib.Conn_finish_mut.restype = None
+_lib.Conn_shared.argtypes = [ctypes.c_size_t, ctypes.POINTER(ctypes.c_void_p)]
+_lib.Conn_shared.restype = None
+_lib.Conn_retain.argtypes = [ctypes.c_void_p]
+_lib.Conn_retain.restype = None
+_lib.Conn_release.argtypes = [ctypes.c_void_p]
+_lib.Conn_release.restype = None
+_lib.ConnShared_get.argtypes = [ctypes.c_void_p, ctypes.POINTER(ctypes.c_size_t)]
+_lib.ConnShared_get.restype = None
+_lib.Pool_new.argtypes = [ctypes.c_size_t, ctypes.POINTER(ctypes.c_void_p)]
+_lib.Pool_new.restype = None
+_lib.Pool_free.argtypes = [ctypes.c_void_p]
+_lib.Pool_free.restype = None
+_lib.Pool_conn.argtypes = [ctypes.c_void_p, ctypes.POINTER(ctypes.c_void_p)]
+_lib.Pool_conn.restype = None
+_lib.Pool_shared_conn.argtypes = [ctypes.c_void_p, ctypes.POINTER(ctypes.c_void_p)]
+_lib.Pool_shared_conn.restype = None
+_lib.Conn_sum.argtypes = [ctypes.c_void_p, ctypes.POINTER(ctypes.c_uint8), ctypes.c_size_t, ctypes.POINTER(ctypes.c_size_t)]
+_lib.Conn_sum.restype = None
+_lib.Conn_try_sum.argtypes = [ctypes.c_void_p, ctypes.POINTER(ctypes.c_uint8), ctypes.c_size_t, ctypes.POINTER(ctypes.c_size_t)]
+_lib.Conn_try_sum.restype = ctypes.c_uint32
+_lib.Conn_merge.argtypes = [ctypes.c_void_p, ctypes.c_void_p]
+_lib.Conn_merge.restype = None
+_lib.Pool_first.argtypes = [ctypes.c_void_p, ctypes.POINTER(ctypes.c_void_p)]
+_lib.Pool_first.restype = None
+_lib.Conn_bytes.argtypes = [ctypes.c_void_p, ctypes.POINTER(ctypes.c_void_p)]
+_lib.Conn_bytes.restype = None
+_lib.Conn_bytes_iter_next.argtypes = [ctypes.c_void_p, ctypes.POINTER(ctypes.c_uint8)]
+_lib.Conn_bytes_iter_next.restype = ctypes.c_bool
+_lib.Conn_bytes_iter_free.argtypes = [ctypes.c_void_p]
+_lib.Conn_bytes_iter_free.restype = None
+_lib.Pool_conns.argtypes = [ctypes.c_void_p, ctypes.POINTER(ctypes.c_void_p)]
+_lib.Pool_conns.restype = None
+_lib.Pool_conns_iter_next.argtypes = [ctypes.c_void_p, ctypes.POINTER(ctypes.c_void_p)]
+_lib.Pool_conns_iter_next.restype = ctypes.c_bool
+_lib.Pool_conns_iter_free.argtypes = [ctypes.c_void_p]
+_lib.Pool_conns_iter_free.restype = None
+_lib.fixture_debug_live_handles.argtypes = []
+_lib.fixture_debug_live_handles.restype = ctypes.c_size_t
+
+
+class Conn(_Handle):
+    """A handle to a `Conn`."""
+
+    @staticmethod
+    def new(value):
+        """Calls `Conn_new`."""
+        ret = ctypes.c_void_p()
+        _lib.Conn_new(value, ctypes.byref(ret))
+        return Conn(ret.value, _lib.Conn_free, ())
+
+    def get(self):
+        """Calls `Conn_get`."""
+        ret = ctypes.c_size_t()
+        _lib.Conn_get(self._ptr, ctypes.byref(ret))
+        return ret.value
+
+    def set(self, value):
+        """Calls `Conn_set`."""
+        _lib.Conn_set(self._ptr, value)
+
+    def raw(self):
+        """Calls `Conn_raw`."""
+        ret = ctypes.POINTER(ctypes.c_uint8)()
+        _lib.Conn_raw(self._ptr, ctypes.byref(ret))
+        return ret
+
+    def fill_raw(self, out, len):
+        """Calls `Conn_fill_raw`."""
+        _lib.Conn_fill_raw(self._ptr, out, len)
+
+    def finish(self):
+        """Calls `Conn_finish`."""
+        ret = ctypes.c_size_t()
+        _lib.Conn_finish(self._take(), ctypes.byref(ret))
+        return ret.value
+
+    def finish_mut(self):
+        """Calls `Conn_finish_mut`."""
+        ret = ctypes.c_size_t()
+        _lib.Conn_finish_mut(self._take(), ctypes.byref(ret))
+        return ret.value
+
+    @staticmethod
+    def shared(value):
+        """Calls `Conn_shared`."""
+        ret = ctypes.c_void_p()
+        _lib.Conn_shared(value, ctypes.byref(ret))
+        return ConnShared(ret.value, _lib.Conn_release, ())
+
+    def sum(self, bytes):
+        """Calls `Conn_sum`."""
+        bytes_buf = (ctypes.c_uint8 * len(bytes))(*bytes)
+        ret = ctypes.c_size_t()
+        _lib.Conn_sum(self._ptr, bytes_buf, len(bytes), ctypes.byref(ret))
+        return ret.value
+
+    def try_sum(self, bytes):
+        """Calls `Conn_try_sum`."""
+        bytes_buf = (ctypes.c_uint8 * len(bytes))(*bytes)
+        ret = ctypes.c_size_t()
+        code = _lib.Conn_try_sum(self._ptr, bytes_buf, len(bytes), ctypes.byref(ret))
+        if code != 0:
+            raise Error(code)
+        return ret.value
+
+    def merge(self, other):
+        """Calls `Conn_merge`."""
+        _lib.Conn_merge(self._ptr, other._ptr)
+
+    def bytes(self):
+        """Calls `Conn_bytes`."""
+        ret = ctypes.c_void_p()
+        _lib.Conn_bytes(self._ptr, ctypes.byref(ret))
+        return Conn_bytes_iter(ret.value, _lib.Conn_bytes_iter_free, (self,))
+
+
+class ConnShared(_Handle):
+    """A handle to a `ConnShared`."""
+
+    def get(self):
+        """Calls `ConnShared_get`."""
+        ret = ctypes.c_size_t()
+        _lib.ConnShared_get(self._ptr, ctypes.byref(ret))
+        return ret.value
+
+
+class Pool(_Handle):
+    """A handle to a `Pool`."""
+
+    @staticmethod
+    def new(value):
+        """Calls `Pool_new`."""
+        ret = ctypes.c_void_p()
+        _lib.Pool_new(value, ctypes.byref(ret))
+        return Pool(ret.value, _lib.Pool_free, ())
+
+    def conn(self):
+        """Calls `Pool_conn`."""
+        ret = ctypes.c_void_p()
+        _lib.Pool_conn(self._ptr, ctypes.byref(ret))
+        return ConnShared(ret.value, None, (self,))
+
+    def shared_conn(self):
+        """Calls `Pool_shared_conn`."""
+        ret = ctypes.c_void_p()
+        _lib.Pool_shared_conn(self._ptr, ctypes.byref(ret))
+        return ConnShared(ret.value, _lib.Conn_release, ())
+
+    def first(self):
+        """Calls `Pool_first`."""
+        ret = ctypes.c_void_p()
+        _lib.Pool_first(self._ptr, ctypes.byref(ret))
+        return Conn(ret.value, _lib.Conn_free, ())
+
+    def conns(self):
+        """Calls `Pool_conns`."""
+        ret = ctypes.c_void_p()
+        _lib.Pool_conns(self._ptr, ctypes.byref(ret))
+        return Pool_conns_iter(ret.value, _lib.Pool_conns_iter_free, (self,))
+
+
+class Conn_bytes_iter(_Handle):
+    """A handle to a `Conn_bytes_iter`."""
+
+    def __iter__(self):
+        return self
+
+    def __next__(self):
+        ret = ctypes.c_uint8()
+        if not _lib.Conn_bytes_iter_next(self._ptr, ctypes.byref(ret)):
+            raise StopIteration
+        return ret.value
+
+
+class Pool_conns_iter(_Handle):
+    """A handle to a `Pool_conns_iter`."""
+
+    def __iter__(self):
+        return self
+
+    def __next__(self):
+        ret = ctypes.c_void_p()
+        if not _lib.Pool_conns_iter_next(self._ptr, ctypes.byref(ret)):
+            raise StopIteration
+        return Conn(ret.value, None, (self,))
+
+
+def debug_live_handles():
+    """Calls `fixture_debug_live_handles`."""
+    return _lib.fixture_debug_live_handles()
+
@@ -0,0 +1,35 @@
+#ifndef FIXTURE_H
+#define FIXTURE_H
+
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+typedef struct Conn Conn;
+
+/**
+ * Consumes `self_`. The handle is invalid after this call.
+ * Writes the return value to `return_ptr`.
+ */
+void Conn_finish(Conn *self_, uintptr_t *return_ptr);
+
+/**
+ * Consumes `self_`. The handle is invalid after this call.
+ * Writes the return value to `return_ptr`.
+ */
+void Conn_finish_mut(Conn *self_, uintptr_t *return_ptr);
+
+/**
+ * Prints every handle owned by C to stderr and returns how many there are. Handles are only tracked when the crate is built with the `debug-handles` feature, and this returns 0 otherwise.
+ */
+uintptr_t fixture_debug_live_handles(void);
+
+#ifdef __cplusplus
+}  // extern "C"
+#endif
+
+#endif  /* FIXTURE_H */
@@ -0,0 +1,30 @@
+use fixture::Conn;
+/// Consumes `self_`. The handle is invalid after this call.
+/// Writes the return value to `return_ptr`.
+#[inline]
+#[no_mangle]
+pub extern "C" fn Conn_finish(self_: *mut ::std::os::raw::c_void, return_ptr: *mut usize) {
+    debug_handles::release(self_ as _, "Box", "Conn", "Conn_finish");
+    let self_ = unsafe { *Box::from_raw(self_ as *mut Conn) };
+    let value = self_.finish();
+    unsafe { *return_ptr = value };
+}
+
+/// Consumes `self_`. The handle is invalid after this call.
+/// Writes the return value to `return_ptr`.
+#[inline]
+#[no_mangle]
+pub extern "C" fn Conn_finish_mut(self_: *mut ::std::os::raw::c_void, return_ptr: *mut usize) {
+    debug_handles::release(self_ as _, "Box", "Conn", "Conn_finish_mut");
+    let self_ = unsafe { *Box::from_raw(self_ as *mut Conn) };
+    let value = self_.finish_mut();
+    unsafe { *return_ptr = value };
+}
+mod debug_handles;
+
+/// Prints every handle owned by C to stderr and returns how many there are. Handles are only tracked when the crate is built with the `debug-handles` feature, and this returns 0 otherwise.
+#[inline]
+#[no_mangle]
+pub extern "C" fn fixture_debug_live_handles() -> usize {
+    debug_handles::live_handles()
+}
@@ -0,0 +1,76 @@
+#ifndef FIXTURE_H
+#define FIXTURE_H
+
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+typedef struct ConnShared ConnShared;
+typedef struct Pool Pool;
+
+/**
+ * Writes the return value to `return_ptr`.
+ *
+ * Ownership:
+ * - `return_ptr`: receives a new handle owned by the caller, which must be freed with `Conn_release`.
+ */
+void Conn_shared(uintptr_t value, ConnShared **return_ptr);
+
+/**
+ * Increments the strong reference count of the handle.
+ */
+void Conn_retain(ConnShared *self_);
+
+/**
+ * Decrements the strong reference count of the handle. The handle is invalid after this call.
+ */
+void Conn_release(ConnShared *self_);
+
+/**
+ * Writes the return value to `return_ptr`.
+ */
+void ConnShared_get(ConnShared *self_, uintptr_t *return_ptr);
+
+/**
+ * Writes the return value to `return_ptr`.
+ *
+ * Ownership:
+ * - `return_ptr`: receives a new handle owned by the caller, which must be freed with `Pool_free`.
+ */
+void Pool_new(uintptr_t value, Pool **return_ptr);
+
+/**
+ * Frees the handle. The handle is invalid after this call.
+ */
+void Pool_free(Pool *self_);
+
+/**
+ * Writes the return value to `return_ptr`.
+ *
+ * Ownership:
+ * - `return_ptr`: receives a reference that is valid while the value it was borrowed from is alive. Do not free it.
+ */
+void Pool_conn(Pool *self_, ConnShared **return_ptr);
+
+/**
+ * Writes the return value to `return_ptr`.
+ *
+ * Ownership:
+ * - `return_ptr`: receives a new handle owned by the caller, which must be freed with `Conn_release`.
+ */
+void Pool_shared_conn(Pool *self_, ConnShared **return_ptr);
+
+/**
+ * Prints every handle owned by C to stderr and returns how many there are. Handles are only tracked when the crate is built with the `debug-handles` feature, and this returns 0 otherwise.
+ */
+uintptr_t fixture_debug_live_handles(void);
+
+#ifdef __cplusplus
+}  // extern "C"
+#endif
+
+#endif  /* FIXTURE_H */
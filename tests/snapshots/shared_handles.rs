@@ -0,0 +1,110 @@
+use fixture::Conn;
+use fixture::Pool;
+/// Writes the return value to `return_ptr`.
+///
+/// Ownership:
+/// - `return_ptr`: receives a new handle owned by the caller, which must be freed with `Conn_release`.
+#[inline]
+#[no_mangle]
+pub extern "C" fn Conn_shared(value: usize, return_ptr: *mut *mut ::std::os::raw::c_void) {
+    let arg0 = value;
+    let value = Conn::shared(arg0);
+    let value = std::sync::Arc::into_raw(value) as *mut Conn;
+    debug_handles::register(value as _, "Arc", "Conn");
+    unsafe { *return_ptr = value as _ };
+}
+
+/// Increments the strong reference count of the handle.
+#[inline]
+#[no_mangle]
+pub extern "C" fn Conn_retain(self_: *mut ::std::os::raw::c_void) {
+    debug_handles::retain(self_ as _, "Arc", "Conn", "Conn_retain");
+    unsafe { std::sync::Arc::increment_strong_count(self_ as *const Conn) };
+}
+
+/// Decrements the strong reference count of the handle. The handle is invalid after this call.
+#[inline]
+#[no_mangle]
+pub extern "C" fn Conn_release(self_: *mut ::std::os::raw::c_void) {
+    debug_handles::release(self_ as _, "Arc", "Conn", "Conn_release");
+    drop(unsafe { std::sync::Arc::from_raw(self_ as *const Conn) });
+}
+
+/// Writes the return value to `return_ptr`.
+#[inline]
+#[no_mangle]
+pub extern "C" fn ConnShared_get(self_: *mut ::std::os::raw::c_void, return_ptr: *mut usize) {
+    debug_handles::check(self_ as _, "Conn", "ConnShared_get");
+    let self_ = unsafe { &*(self_ as *const Conn) };
+    let value = self_.get();
+    unsafe { *return_ptr = value };
+}
+
+/// Writes the return value to `return_ptr`.
+///
+/// Ownership:
+/// - `return_ptr`: receives a new handle owned by the caller, which must be freed with `Pool_free`.
+#[inline]
+#[no_mangle]
+pub extern "C" fn Pool_new(value: usize, return_ptr: *mut *mut ::std::os::raw::c_void) {
+    let arg0 = value;
+    let value = Pool::new(arg0);
+    let value = Box::into_raw(Box::new(value));
+    debug_handles::register(value as _, "Box", "Pool");
+    unsafe { *return_ptr = value as _ };
+}
+
+/// Frees the handle. The handle is invalid after this call.
+#[inline]
+#[no_mangle]
+pub extern "C" fn Pool_free(self_: *mut ::std::os::raw::c_void) {
+    debug_handles::release(self_ as _, "Box", "Pool", "Pool_free");
+    drop(unsafe { Box::from_raw(self_ as *mut Pool) });
+}
+
+/// Writes the return value to `return_ptr`.
+///
+/// Ownership:
+/// - `return_ptr`: receives a reference that is valid while the value it was borrowed from is alive. Do not free it.
+#[inline]
+#[no_mangle]
+pub extern "C" fn Pool_conn(
+    self_: *mut ::std::os::raw::c_void,
+    return_ptr: *mut *mut ::std::os::raw::c_void,
+) {
+    debug_handles::check(self_ as _, "Pool", "Pool_conn");
+    let self_ = unsafe { &*(self_ as *const Pool) };
+    let value = self_.conn();
+    debug_handles::register_borrowed(
+        std::sync::Arc::as_ptr(value) as _,
+        "Conn",
+        self_ as *const _ as _,
+    );
+    unsafe { *return_ptr = std::sync::Arc::as_ptr(value) as _ };
+}
+
+/// Writes the return value to `return_ptr`.
+///
+/// Ownership:
+/// - `return_ptr`: receives a new handle owned by the caller, which must be freed with `Conn_release`.
+#[inline]
+#[no_mangle]
+pub extern "C" fn Pool_shared_conn(
+    self_: *mut ::std::os::raw::c_void,
+    return_ptr: *mut *mut ::std::os::raw::c_void,
+) {
+    debug_handles::check(self_ as _, "Pool", "Pool_shared_conn");
+    let self_ = unsafe { &*(self_ as *const Pool) };
+    let value = self_.shared_conn();
+    let value = std::sync::Arc::into_raw(value) as *mut Conn;
+    debug_handles::register(value as _, "Arc", "Conn");
+    unsafe { *return_ptr = value as _ };
+}
+mod debug_handles;
+
+/// Prints every handle owned by C to stderr and returns how many there are. Handles are only tracked when the crate is built with the `debug-handles` feature, and this returns 0 otherwise.
+#[inline]
+#[no_mangle]
+pub extern "C" fn fixture_debug_live_handles() -> usize {
+    debug_handles::live_handles()
+}
@@ -0,0 +1,21 @@
+# Found by find_package(Fixture), which defines the imported targets Fixture::fixture_c and Fixture::fixture_c_static.
+get_filename_component(_fixture_prefix "${CMAKE_CURRENT_LIST_DIR}/../../.." ABSOLUTE)
+separate_arguments(_fixture_libs UNIX_COMMAND "@NATIVE_STATIC_LIBS@")
+
+if(NOT TARGET Fixture::fixture_c)
+  add_library(Fixture::fixture_c SHARED IMPORTED)
+  set_target_properties(Fixture::fixture_c PROPERTIES
+    IMPORTED_LOCATION "${_fixture_prefix}/lib/${CMAKE_SHARED_LIBRARY_PREFIX}fixture_c${CMAKE_SHARED_LIBRARY_SUFFIX}"
+    INTERFACE_INCLUDE_DIRECTORIES "${_fixture_prefix}/include")
+endif()
+
+if(NOT TARGET Fixture::fixture_c_static)
+  add_library(Fixture::fixture_c_static STATIC IMPORTED)
+  set_target_properties(Fixture::fixture_c_static PROPERTIES
+    IMPORTED_LOCATION "${_fixture_prefix}/lib/${CMAKE_STATIC_LIBRARY_PREFIX}fixture_c${CMAKE_STATIC_LIBRARY_SUFFIX}"
+    INTERFACE_LINK_LIBRARIES "${_fixture_libs}"
+    INTERFACE_INCLUDE_DIRECTORIES "${_fixture_prefix}/include")
+endif()
+
+unset(_fixture_prefix)
+unset(_fixture_libs)
@@ -0,0 +1,22 @@
+//! Checks how the Rust types in scanned signatures are converted.
+
+use ffiber::types::Type;
+
+fn parse(ty: &str) -> Type {
+    Type::from(syn::parse_str::<syn::Type>(ty).unwrap())
+}
+
+#[test]
+fn smart_pointers_are_recognized_with_or_without_a_path() {
+    for ty in ["Box<Conn>", "alloc::boxed::Box<Conn>"] {
+        assert_eq!(parse(ty), Type::new_box("Conn"), "{}", ty);
+    }
+    for ty in ["Arc<Conn>", "std::sync::Arc<Conn>", "::std::sync::Arc<Conn>"] {
+        assert_eq!(parse(ty), Type::new_arc("Conn"), "{}", ty);
+    }
+    for ty in ["Rc<Conn>", "std::rc::Rc<Conn>"] {
+        assert_eq!(parse(ty), Type::new_rc("Conn"), "{}", ty);
+    }
+    assert_eq!(parse("&std::sync::Arc<Conn>"),
+        Type::Ref(Box::new(Type::new_arc("Conn"))));
+}